@@ -0,0 +1,871 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::abi::param_type::Reader;
+use ethers::abi::token::{LenientTokenizer, Tokenizer};
+use ethers::abi::{Abi, AbiParser, Event, Function, Param, ParamType, StateMutability, Token};
+use ethers::types::{I256, U256};
+use ethers::utils::to_checksum;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::eth::parse_wei;
+use crate::util::u8_array_convert_string;
+
+/// 未提供abi时用于匹配函数选择器的常见函数签名，选择器由签名计算得到
+const SELECTOR_SIGNATURES: &[&str] = &[
+    "struct Call { address target; bytes callData; }",
+    "struct Call3 { address target; bool allowFailure; bytes callData; }",
+    "struct ExactInputSingleParams { address tokenIn; address tokenOut; uint24 fee; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; uint160 sqrtPriceLimitX96; }",
+    "struct ExactInputParams { bytes path; address recipient; uint256 deadline; uint256 amountIn; uint256 amountOutMinimum; }",
+    // ERC20
+    "function transfer(address to, uint256 amount)",
+    "function approve(address spender, uint256 amount)",
+    "function transferFrom(address from, address to, uint256 amount)",
+    "function increaseAllowance(address spender, uint256 addedValue)",
+    "function decreaseAllowance(address spender, uint256 subtractedValue)",
+    "function balanceOf(address account)",
+    "function allowance(address owner, address spender)",
+    // ERC721 / ERC1155
+    "function safeTransferFrom(address from, address to, uint256 tokenId)",
+    "function safeTransferFrom(address from, address to, uint256 tokenId, bytes data)",
+    "function setApprovalForAll(address operator, bool approved)",
+    "function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes data)",
+    "function safeBatchTransferFrom(address from, address to, uint256[] ids, uint256[] amounts, bytes data)",
+    // WETH
+    "function deposit()",
+    "function withdraw(uint256 wad)",
+    // multicall
+    "function multicall(bytes[] data)",
+    "function multicall(uint256 deadline, bytes[] data)",
+    "function aggregate(Call[] calls)",
+    "function aggregate3(Call3[] calls)",
+    // Uniswap
+    "function swapExactTokensForTokens(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)",
+    "function swapTokensForExactTokens(uint256 amountOut, uint256 amountInMax, address[] path, address to, uint256 deadline)",
+    "function swapExactETHForTokens(uint256 amountOutMin, address[] path, address to, uint256 deadline)",
+    "function swapExactTokensForETH(uint256 amountIn, uint256 amountOutMin, address[] path, address to, uint256 deadline)",
+    "function exactInputSingle(ExactInputSingleParams params)",
+    "function exactInput(ExactInputParams params)",
+    "function execute(bytes commands, bytes[] inputs, uint256 deadline)",
+    // Gnosis Safe
+    "function execTransaction(address to, uint256 value, bytes data, uint8 operation, uint256 safeTxGas, uint256 baseGas, uint256 gasPrice, address gasToken, address refundReceiver, bytes signatures)",
+];
+
+/// 未提供abi时用于解码日志的常见事件，ERC20与ERC721的Transfer签名相同，按indexed参数个数区分
+const EVENT_SIGNATURES: &[&str] = &[
+    "event Transfer(address indexed from, address indexed to, uint256 value)",
+    "event Transfer(address indexed from, address indexed to, uint256 indexed tokenId)",
+    "event Approval(address indexed owner, address indexed spender, uint256 value)",
+    "event Approval(address indexed owner, address indexed approved, uint256 indexed tokenId)",
+    "event ApprovalForAll(address indexed owner, address indexed operator, bool approved)",
+    "event TransferSingle(address indexed operator, address indexed from, address indexed to, uint256 id, uint256 value)",
+    "event TransferBatch(address indexed operator, address indexed from, address indexed to, uint256[] ids, uint256[] values)",
+    "event Deposit(address indexed dst, uint256 wad)",
+    "event Withdrawal(address indexed src, uint256 wad)",
+];
+
+/// 参数名称，tuple类型的components名称只能从json格式的abi中获取
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParamNames {
+    pub name: String,
+    pub components: Vec<ParamNames>,
+}
+
+impl ParamNames {
+    pub fn from_param(param: &Param) -> Self {
+        ParamNames {
+            name: param.name.clone(),
+            components: vec![],
+        }
+    }
+
+    fn from_json(value: &Value) -> Self {
+        let components = value["components"]
+            .as_array()
+            .map(|components| components.iter().map(ParamNames::from_json).collect())
+            .unwrap_or_default();
+        ParamNames {
+            name: value["name"].as_str().unwrap_or_default().to_string(),
+            components,
+        }
+    }
+}
+
+/// 解析后的abi以及json abi中tuple参数的名称，key为函数选择器
+#[derive(Debug, Clone)]
+pub struct LoadedAbi {
+    pub abi: Abi,
+    pub input_names: HashMap<[u8; 4], Vec<ParamNames>>,
+    pub output_names: HashMap<[u8; 4], Vec<ParamNames>>,
+}
+
+impl LoadedAbi {
+    fn new(abi: Abi) -> Self {
+        LoadedAbi {
+            abi,
+            input_names: HashMap::new(),
+            output_names: HashMap::new(),
+        }
+    }
+
+    fn names(
+        map: &HashMap<[u8; 4], Vec<ParamNames>>,
+        function: &Function,
+        params: &[Param],
+    ) -> Vec<ParamNames> {
+        map.get(&function.short_signature())
+            .cloned()
+            .unwrap_or_else(|| params.iter().map(ParamNames::from_param).collect())
+    }
+
+    pub fn input_names(&self, function: &Function) -> Vec<ParamNames> {
+        Self::names(&self.input_names, function, &function.inputs)
+    }
+
+    pub fn output_names(&self, function: &Function) -> Vec<ParamNames> {
+        Self::names(&self.output_names, function, &function.outputs)
+    }
+
+    /// 按名称查找函数，存在重载时通过选择器区分
+    pub fn function(&self, name: &str, selector: Option<&[u8]>) -> Result<&Function> {
+        let functions = self
+            .abi
+            .functions_by_name(name)
+            .map_err(|_| anyhow!("function {name} not found in abi."))?;
+        match selector {
+            Some(selector) => functions
+                .iter()
+                .find(|f| f.short_signature() == selector)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "function selector 0x{} does not match {name}.",
+                        u8_array_convert_string(selector)
+                    )
+                }),
+            None => Ok(&functions[0]),
+        }
+    }
+
+    /// 查找要调用的函数，存在重载时可以传入签名，或者按参数个数区分
+    pub fn function_for_call(&self, name: &str, arg_count: usize) -> Result<&Function> {
+        if name.contains('(') {
+            let signature = function_signature(&parse_signature(name)?);
+            return self
+                .abi
+                .functions()
+                .find(|f| function_signature(f) == signature)
+                .ok_or_else(|| anyhow!("function {signature} not found in abi."));
+        }
+        let functions = self
+            .abi
+            .functions_by_name(name)
+            .map_err(|_| anyhow!("function {name} not found in abi."))?;
+        let candidates: Vec<&Function> = functions
+            .iter()
+            .filter(|f| f.inputs.len() == arg_count)
+            .collect();
+        match candidates.as_slice() {
+            [function] => Ok(function),
+            [] => Err(anyhow!(
+                "function {name} with {arg_count} arguments not found in abi."
+            )),
+            _ => Err(anyhow!(
+                "function {name} is overloaded, use the full signature: {}",
+                candidates
+                    .iter()
+                    .map(|f| function_signature(f))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )),
+        }
+    }
+
+    pub fn function_by_selector(&self, selector: &[u8]) -> Option<&Function> {
+        self.abi
+            .functions()
+            .find(|f| f.short_signature() == selector)
+    }
+}
+
+fn load_json_abi(value: &Value) -> Result<LoadedAbi> {
+    // 兼容hardhat/foundry编译产物中包含abi字段的json
+    let items = match value {
+        Value::Object(object) if object.contains_key("abi") => &object["abi"],
+        _ => value,
+    };
+    let abi: Abi = serde_json::from_value(items.clone())?;
+    let mut loaded = LoadedAbi::new(abi);
+    for item in items.as_array().into_iter().flatten() {
+        if item["type"] != "function" {
+            continue;
+        }
+        let function: Function = serde_json::from_value(item.clone())?;
+        let selector = function.short_signature();
+        let names = |key: &str| -> Vec<ParamNames> {
+            item[key]
+                .as_array()
+                .map(|params| params.iter().map(ParamNames::from_json).collect())
+                .unwrap_or_default()
+        };
+        loaded.input_names.insert(selector, names("inputs"));
+        loaded.output_names.insert(selector, names("outputs"));
+    }
+    Ok(loaded)
+}
+
+/// 解析human-readable格式的abi，每行一个定义，省略function关键字的签名也可以解析
+fn load_human_readable_abi(content: &str) -> Result<LoadedAbi> {
+    let lines: Vec<String> = content
+        .trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .lines()
+        .map(|line| line.trim().trim_end_matches(',').trim_matches('"').trim())
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let keyword = line.split_whitespace().next().unwrap_or_default();
+            if ["function", "event", "struct", "error", "constructor"].contains(&keyword) {
+                line.to_string()
+            } else {
+                format!("function {line}")
+            }
+        })
+        .collect();
+    let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+    let abi = AbiParser::default()
+        .parse(&lines)
+        .map_err(|e| anyhow!("parse human-readable abi failed: {e}"))?;
+    Ok(LoadedAbi::new(abi))
+}
+
+/// abi参数可以是文件路径或者内容本身，支持json格式与human-readable格式
+pub fn load_abi(abi: &str) -> Result<LoadedAbi> {
+    let content = if Path::new(abi).is_file() {
+        fs::read_to_string(abi)?
+    } else {
+        abi.to_string()
+    };
+    match serde_json::from_str::<Value>(&content) {
+        Ok(value) if value.is_array() || value.is_object() => load_json_abi(&value),
+        _ => load_human_readable_abi(&content),
+    }
+}
+
+pub fn function_signature(function: &Function) -> String {
+    let types: Vec<String> = function.inputs.iter().map(|p| p.kind.to_string()).collect();
+    format!("{}({})", function.name, types.join(","))
+}
+
+pub fn event_signature(event: &Event) -> String {
+    let types: Vec<String> = event.inputs.iter().map(|p| p.kind.to_string()).collect();
+    format!("{}({})", event.name, types.join(","))
+}
+
+/// 解析事件定义，如 "Transfer(address indexed from, address indexed to, uint256 value)"
+pub fn parse_event(event: &str) -> Result<Event> {
+    let event = event.trim();
+    let event = if event.starts_with("event ") {
+        event.to_string()
+    } else {
+        format!("event {event}")
+    };
+    AbiParser::default()
+        .parse_event(&event)
+        .map_err(|e| anyhow!("parse event {event} failed: {e}"))
+}
+
+pub fn common_events() -> Result<Vec<Event>> {
+    EVENT_SIGNATURES
+        .iter()
+        .map(|event| parse_event(event))
+        .collect()
+}
+
+/// 按参数类型格式化解码后的值，tuple有名称时以 {name: value} 的形式输出
+pub fn format_token(token: &Token, names: Option<&ParamNames>) -> String {
+    match token {
+        Token::Address(address) => to_checksum(address, None),
+        Token::Uint(value) => value.to_string(),
+        Token::Int(value) => I256::from_raw(*value).to_string(),
+        Token::Bool(value) => value.to_string(),
+        Token::String(value) => format!("{value:?}"),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => {
+            format!("0x{}", u8_array_convert_string(bytes))
+        }
+        Token::Array(tokens) | Token::FixedArray(tokens) => {
+            let items: Vec<String> = tokens.iter().map(|t| format_token(t, names)).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Token::Tuple(tokens) => {
+            let components = names
+                .map(|n| n.components.as_slice())
+                .filter(|c| c.len() == tokens.len() && c.iter().all(|n| !n.name.is_empty()));
+            match components {
+                Some(components) => {
+                    let items: Vec<String> = tokens
+                        .iter()
+                        .zip(components)
+                        .map(|(t, n)| format!("{}: {}", n.name, format_token(t, Some(n))))
+                        .collect();
+                    format!("{{{}}}", items.join(", "))
+                }
+                None => {
+                    let items: Vec<String> = tokens.iter().map(|t| format_token(t, None)).collect();
+                    format!("({})", items.join(", "))
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedParam {
+    pub name: String,
+    pub kind: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedCall {
+    pub selector: String,
+    pub signature: String,
+    pub params: Vec<DecodedParam>,
+}
+
+pub fn decode_params(
+    params: &[Param],
+    names: &[ParamNames],
+    tokens: &[Token],
+) -> Vec<DecodedParam> {
+    params
+        .iter()
+        .zip(tokens)
+        .enumerate()
+        .map(|(index, (param, token))| {
+            let names = names.get(index);
+            let name = match names.map(|n| n.name.as_str()) {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => format!("arg{index}"),
+            };
+            DecodedParam {
+                name,
+                kind: param.kind.to_string(),
+                value: format_token(token, names),
+            }
+        })
+        .collect()
+}
+
+fn decode_function_call(
+    function: &Function,
+    names: &[ParamNames],
+    data: &[u8],
+) -> Result<DecodedCall> {
+    let tokens = function
+        .decode_input(&data[4..])
+        .map_err(|e| anyhow!("decode {} input failed: {e}", function.name))?;
+    Ok(DecodedCall {
+        selector: format!("0x{}", u8_array_convert_string(&data[..4])),
+        signature: function_signature(function),
+        params: decode_params(&function.inputs, names, &tokens),
+    })
+}
+
+/// 使用abi解码calldata，未指定函数名时按函数选择器匹配
+pub fn decode_call(abi: &LoadedAbi, data: &[u8], func_name: Option<&str>) -> Result<DecodedCall> {
+    if data.len() < 4 {
+        bail!("call data is shorter than a function selector.");
+    }
+    let selector = &data[..4];
+    let function = match func_name {
+        Some(name) => abi.function(name, Some(selector))?,
+        None => abi.function_by_selector(selector).ok_or_else(|| {
+            anyhow!(
+                "function selector 0x{} not found in abi.",
+                u8_array_convert_string(selector)
+            )
+        })?,
+    };
+    decode_function_call(function, &abi.input_names(function), data)
+}
+
+/// 未提供abi时通过内置的常见函数签名匹配选择器，找不到时返回None
+pub fn decode_call_by_selector(data: &[u8]) -> Result<Option<DecodedCall>> {
+    if data.len() < 4 {
+        bail!("call data is shorter than a function selector.");
+    }
+    let abi = AbiParser::default()
+        .parse(SELECTOR_SIGNATURES)
+        .map_err(|e| anyhow!("parse selector signatures failed: {e}"))?;
+    let abi = LoadedAbi::new(abi);
+    match abi.function_by_selector(&data[..4]) {
+        Some(function) => {
+            decode_function_call(function, &abi.input_names(function), data).map(Some)
+        }
+        None => Ok(None),
+    }
+}
+
+/// 找到与起始括号匹配的结束括号位置，忽略引号内的字符
+fn matching_bracket(input: &str, open: usize) -> Result<usize> {
+    let (left, right) = match input.as_bytes().get(open) {
+        Some(b'(') => (b'(', b')'),
+        Some(b'[') => (b'[', b']'),
+        _ => bail!("expect a bracket at {open} in {input}"),
+    };
+    let mut depth = 0;
+    let mut quoted = false;
+    for (index, &c) in input.as_bytes().iter().enumerate().skip(open) {
+        if c == b'"' {
+            quoted = !quoted;
+        } else if !quoted && c == left {
+            depth += 1;
+        } else if !quoted && c == right {
+            depth -= 1;
+            if depth == 0 {
+                return Ok(index);
+            }
+        }
+    }
+    bail!("unbalanced brackets in {input}")
+}
+
+/// 按最外层的逗号分割，括号和引号内的逗号不分割
+fn split_top_level(input: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quoted = false;
+    for c in input.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '(' | '[' if !quoted => depth += 1,
+            ')' | ']' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                items.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() || !items.is_empty() {
+        items.push(current.trim().to_string());
+    }
+    items
+}
+
+fn valid_kind(kind: &ParamType) -> bool {
+    match kind {
+        ParamType::Uint(size) | ParamType::Int(size) => *size > 0 && *size <= 256 && size % 8 == 0,
+        ParamType::FixedBytes(size) => (1..=32).contains(size),
+        ParamType::Array(inner) | ParamType::FixedArray(inner, _) => valid_kind(inner),
+        ParamType::Tuple(kinds) => kinds.iter().all(valid_kind),
+        _ => true,
+    }
+}
+
+/// 解析参数类型，Reader会把未知的类型名当作enum处理为uint8，这里要求类型名必须合法
+fn read_kind(raw: &str) -> Result<ParamType> {
+    let raw: String = raw.chars().filter(|c| !c.is_whitespace()).collect();
+    let kind = Reader::read(&raw).map_err(|e| anyhow!("invalid type {raw}: {e}"))?;
+    let normalized = Regex::new(r"\b(u?int)\b")
+        .unwrap()
+        .replace_all(&raw, "${1}256");
+    if kind.to_string() != normalized || !valid_kind(&kind) {
+        bail!("invalid type {raw}");
+    }
+    Ok(kind)
+}
+
+/// 解析单个参数，如 "uint256"、"address to"、"(address,uint256)[] orders"
+fn parse_param(param: &str) -> Result<Param> {
+    let param = param.trim();
+    let end = if param.starts_with('(') {
+        let close = matching_bracket(param, 0)? + 1;
+        // tuple之后可能还有数组后缀
+        close
+            + param[close..]
+                .find(|c: char| !(c == '[' || c == ']' || c.is_ascii_digit()))
+                .unwrap_or(param.len() - close)
+    } else {
+        param.find(char::is_whitespace).unwrap_or(param.len())
+    };
+    let kind = read_kind(&param[..end])?;
+    let name = param[end..]
+        .split_whitespace()
+        .rfind(|word| !["memory", "calldata", "storage", "indexed"].contains(word))
+        .unwrap_or_default();
+    Ok(Param {
+        name: name.to_string(),
+        kind,
+        internal_type: None,
+    })
+}
+
+fn parse_params(params: &str) -> Result<Vec<Param>> {
+    split_top_level(params)
+        .iter()
+        .map(|param| parse_param(param))
+        .collect()
+}
+
+/// 解析函数签名，如 "transfer(address,uint256)"、"transfer(address to, uint256 amount)"，
+/// 返回值可以写成 "balanceOf(address)(uint256)" 或 "balanceOf(address) returns (uint256)"
+pub fn parse_signature(signature: &str) -> Result<Function> {
+    let signature = signature.trim();
+    let signature = signature
+        .strip_prefix("function ")
+        .unwrap_or(signature)
+        .trim();
+    let open = signature
+        .find('(')
+        .ok_or_else(|| anyhow!("invalid function signature: {signature}"))?;
+    let name = signature[..open].trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        bail!("invalid function name in signature: {signature}");
+    }
+    let close = matching_bracket(signature, open)?;
+    let inputs = parse_params(&signature[open + 1..close])?;
+    let rest = &signature[close + 1..];
+    let outputs = match rest.find('(') {
+        Some(open) => parse_params(&rest[open + 1..matching_bracket(rest, open)?])?,
+        None => vec![],
+    };
+    #[allow(deprecated)]
+    let function = Function {
+        name: name.to_string(),
+        inputs,
+        outputs,
+        constant: None,
+        state_mutability: StateMutability::NonPayable,
+    };
+    Ok(function)
+}
+
+/// 按 `[a, b]`、`(a, b)` 的格式拆分数组和tuple的元素
+fn split_list(value: &str, open: char, close: char) -> Result<Vec<String>> {
+    let inner = value
+        .strip_prefix(open)
+        .and_then(|v| v.strip_suffix(close))
+        .ok_or_else(|| anyhow!("expect {open}...{close}, got {value}"))?;
+    Ok(split_top_level(inner))
+}
+
+/// 将命令行参数转换为abi token，uint支持带单位的数值，如 "1.5 ETH"、"30 GWei"
+pub fn tokenize(kind: &ParamType, value: &str) -> Result<Token> {
+    let value = value.trim();
+    match kind {
+        ParamType::Uint(size) => {
+            let number = match value.strip_prefix("0x") {
+                Some(hex) => U256::from_str_radix(hex, 16)?,
+                None => parse_wei(value)?,
+            };
+            if number.bits() > *size {
+                bail!("{value} out of range for uint{size}");
+            }
+            Ok(Token::Uint(number))
+        }
+        ParamType::Array(inner) => split_list(value, '[', ']')?
+            .iter()
+            .map(|item| tokenize(inner, item))
+            .collect::<Result<Vec<_>>>()
+            .map(Token::Array),
+        ParamType::FixedArray(inner, len) => {
+            let items = split_list(value, '[', ']')?;
+            if items.len() != *len {
+                bail!("expect {len} items for {kind}, got {}", items.len());
+            }
+            items
+                .iter()
+                .map(|item| tokenize(inner, item))
+                .collect::<Result<Vec<_>>>()
+                .map(Token::FixedArray)
+        }
+        ParamType::Tuple(kinds) => {
+            let items = split_list(value, '(', ')')?;
+            if items.len() != kinds.len() {
+                bail!(
+                    "expect {} items for {kind}, got {}",
+                    kinds.len(),
+                    items.len()
+                );
+            }
+            kinds
+                .iter()
+                .zip(&items)
+                .map(|(kind, item)| tokenize(kind, item))
+                .collect::<Result<Vec<_>>>()
+                .map(Token::Tuple)
+        }
+        ParamType::String => {
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            Ok(Token::String(value.to_string()))
+        }
+        _ => LenientTokenizer::tokenize(kind, value)
+            .map_err(|e| anyhow!("invalid {kind} value {value}: {e}")),
+    }
+}
+
+/// 按函数定义编码参数，返回带函数选择器的calldata
+pub fn encode_call(function: &Function, args: &[String]) -> Result<Vec<u8>> {
+    if args.len() != function.inputs.len() {
+        bail!(
+            "{} expects {} arguments, got {}",
+            function_signature(function),
+            function.inputs.len(),
+            args.len()
+        );
+    }
+    let tokens = function
+        .inputs
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| tokenize(&param.kind, arg))
+        .collect::<Result<Vec<_>>>()?;
+    function
+        .encode_input(&tokens)
+        .map_err(|e| anyhow!("encode {} input failed: {e}", function.name))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::hex_string_2_array;
+    use ethers::types::{Address, U256};
+
+    const TEST_JSON_ABI: &str = r#"[
+        {
+            "type": "function",
+            "name": "submit",
+            "stateMutability": "nonpayable",
+            "inputs": [
+                {"name": "id", "type": "uint8"},
+                {"name": "delta", "type": "int256"},
+                {
+                    "name": "orders",
+                    "type": "tuple[]",
+                    "components": [
+                        {"name": "maker", "type": "address"},
+                        {"name": "amounts", "type": "uint256[]"},
+                        {"name": "memo", "type": "string"}
+                    ]
+                },
+                {"name": "flag", "type": "bool"}
+            ],
+            "outputs": []
+        }
+    ]"#;
+
+    #[test]
+    fn test_decode_by_selector_table() {
+        let data = hex_string_2_array("a9059cbb0000000000000000000000000ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d9550000000000000000000000000000000000000000000000000000000218711a00");
+        let call = decode_call_by_selector(&data).unwrap().unwrap();
+        assert_eq!(call.signature, "transfer(address,uint256)");
+        assert_eq!(call.params[0].name, "to");
+        assert_eq!(
+            call.params[0].value,
+            "0x0cA0e077A7D81C8bA0AEb710d2cFE2Aa5dd3D955"
+        );
+        assert_eq!(call.params[1].name, "amount");
+        assert_eq!(call.params[1].value, "9000000000");
+        let unknown = hex_string_2_array("1a1da075");
+        assert!(decode_call_by_selector(&unknown).unwrap().is_none());
+        let abi = AbiParser::default().parse(SELECTOR_SIGNATURES).unwrap();
+        let aggregate = &abi.functions_by_name("aggregate").unwrap()[0];
+        assert_eq!(
+            u8_array_convert_string(&aggregate.short_signature()),
+            "252dba42"
+        );
+    }
+
+    #[test]
+    fn test_decode_json_abi_nested() {
+        let abi = load_abi(TEST_JSON_ABI).unwrap();
+        let function = abi.function("submit", None).unwrap();
+        let maker = Address::from_low_u64_be(0xabc);
+        let data = function
+            .encode_input(&[
+                Token::Uint(U256::from(7)),
+                Token::Int(I256::from(-5).into_raw()),
+                Token::Array(vec![Token::Tuple(vec![
+                    Token::Address(maker),
+                    Token::Array(vec![Token::Uint(1.into()), Token::Uint(2.into())]),
+                    Token::String("hi".to_string()),
+                ])]),
+                Token::Bool(false),
+            ])
+            .unwrap();
+        let call = decode_call(&abi, &data, None).unwrap();
+        assert_eq!(
+            call.signature,
+            "submit(uint8,int256,(address,uint256[],string)[],bool)"
+        );
+        let values: Vec<(&str, &str)> = call
+            .params
+            .iter()
+            .map(|p| (p.name.as_str(), p.value.as_str()))
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                ("id", "7"),
+                ("delta", "-5"),
+                (
+                    "orders",
+                    "[{maker: 0x0000000000000000000000000000000000000aBc, amounts: [1, 2], memo: \"hi\"}]"
+                ),
+                ("flag", "false"),
+            ]
+        );
+        assert!(decode_call(&abi, &data, Some("transfer")).is_err());
+    }
+
+    #[test]
+    fn test_decode_human_readable_abi() {
+        let abi = load_abi(
+            r#"[
+            function balanceOf(address account) external view returns (uint256)
+            function transfer(address to, uint256 amount) external returns (bool)
+        ]"#,
+        )
+        .unwrap();
+        let data = hex_string_2_array("a9059cbb0000000000000000000000000ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d9550000000000000000000000000000000000000000000000000000000218711a00");
+        let call = decode_call(&abi, &data, Some("transfer")).unwrap();
+        assert_eq!(call.params[1].name, "amount");
+        let abi = load_abi("transfer(address,uint256)").unwrap();
+        let call = decode_call(&abi, &data, None).unwrap();
+        assert_eq!(call.params[0].name, "arg0");
+    }
+
+    #[test]
+    fn test_parse_signature() {
+        let function = parse_signature("transfer(address to, uint256 amount)").unwrap();
+        assert_eq!(function_signature(&function), "transfer(address,uint256)");
+        assert_eq!(function.inputs[1].name, "amount");
+        let function =
+            parse_signature("submit(uint8,int256,(address,uint256[],string)[] orders,bool)")
+                .unwrap();
+        assert_eq!(
+            function_signature(&function),
+            "submit(uint8,int256,(address,uint256[],string)[],bool)"
+        );
+        assert_eq!(function.inputs[2].name, "orders");
+        let function = parse_signature("balanceOf(address)(uint256)").unwrap();
+        assert_eq!(function.outputs[0].kind, ParamType::Uint(256));
+        let function =
+            parse_signature("function decimals() external view returns (uint8)").unwrap();
+        assert!(function.inputs.is_empty());
+        assert_eq!(function.outputs[0].kind, ParamType::Uint(8));
+        assert!(parse_signature("transfer(address,uint256").is_err());
+        assert!(parse_signature("transfer(address,amount)").is_err());
+        assert!(parse_signature("transfer(address,uint257)").is_err());
+        let function = parse_signature("f(uint, int[2], (uint,bool)[])").unwrap();
+        assert_eq!(
+            function_signature(&function),
+            "f(uint256,int256[2],(uint256,bool)[])"
+        );
+    }
+
+    #[test]
+    fn test_encode_call() {
+        let function = parse_signature("transfer(address,uint256)").unwrap();
+        let args = ["0x0ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d955", "9 GWei"].map(String::from);
+        let data = encode_call(&function, &args).unwrap();
+        assert_eq!(u8_array_convert_string(&data), "a9059cbb0000000000000000000000000ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d9550000000000000000000000000000000000000000000000000000000218711a00");
+        assert!(encode_call(&function, &args[..1]).is_err());
+        let args = ["0x0ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d955", "-1"].map(String::from);
+        assert!(encode_call(&function, &args).is_err());
+        let function = parse_signature("f(uint8)").unwrap();
+        assert!(encode_call(&function, &["256".to_string()]).is_err());
+
+        // 无法解析的数值和单位返回错误
+        for kind in ["uint256", "int256", "uint256[]"] {
+            let function = parse_signature(&format!("f({kind})")).unwrap();
+            for value in ["1e18", "1.5 wei", "10 usdc", "[1e18]"] {
+                assert!(encode_call(&function, &[value.to_string()]).is_err());
+            }
+        }
+        let err = crate::output::run_cli(&[
+            "eth",
+            "encode-call",
+            "--sig",
+            "f(uint256)",
+            "--args",
+            "10 usdc",
+        ])
+        .unwrap_err();
+        assert!(err.to_string().contains("usdc"));
+    }
+
+    #[test]
+    fn test_encode_call_round_trip() {
+        let function =
+            parse_signature("submit(uint8,int256,(address,uint256[],string)[],bool,bytes,bytes4)")
+                .unwrap();
+        let args = [
+            "7",
+            "-5",
+            r#"[(0x0000000000000000000000000000000000000aBc, [1, 0x02], "a, b"), (0x0000000000000000000000000000000000000abc, [], "")]"#,
+            "true",
+            "0x1234",
+            "0xa9059cbb",
+        ]
+        .map(String::from);
+        let data = encode_call(&function, &args).unwrap();
+        let abi = LoadedAbi::new(Abi {
+            functions: [(function.name.clone(), vec![function])].into(),
+            ..Default::default()
+        });
+        let call = decode_call(&abi, &data, None).unwrap();
+        let values: Vec<&str> = call.params.iter().map(|p| p.value.as_str()).collect();
+        assert_eq!(
+            values,
+            vec![
+                "7",
+                "-5",
+                "[(0x0000000000000000000000000000000000000aBc, [1, 2], \"a, b\"), (0x0000000000000000000000000000000000000aBc, [], \"\")]",
+                "true",
+                "0x1234",
+                "0xa9059cbb",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_function_for_call() {
+        let abi = load_abi(
+            r#"[
+            function approve(address spender, uint256 amount)
+            function safeTransferFrom(address from, address to, uint256 tokenId)
+            function safeTransferFrom(address from, address to, uint256 tokenId, bytes data)
+            function safeTransferFrom(address from, address to, uint256 id, uint256 amount, bytes data)
+        ]"#,
+        )
+        .unwrap();
+        let function = abi.function_for_call("safeTransferFrom", 4).unwrap();
+        assert_eq!(
+            function_signature(function),
+            "safeTransferFrom(address,address,uint256,bytes)"
+        );
+        let function = abi
+            .function_for_call("safeTransferFrom(address,address,uint256)", 0)
+            .unwrap();
+        assert_eq!(
+            u8_array_convert_string(&function.short_signature()),
+            "42842e0e"
+        );
+        assert!(abi.function_for_call("approve", 3).is_err());
+        assert!(abi.function_for_call("transferFrom", 3).is_err());
+        assert!(abi
+            .function_for_call("safeTransferFrom(address,uint256)", 2)
+            .is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::types::Address;
+use ethers::utils::{hex, to_checksum};
+use serde::Serialize;
+use std::str::FromStr;
+
+use crate::btc::{address_to_p2pkh, Network};
+use crate::btc_script::{decode_base58_address, decode_segwit_address, p2sh_script, segwit_script};
+use crate::encrypt_decrypt::write_output;
+
+const NETWORKS: [Network; 3] = [Network::Mainnet, Network::Testnet, Network::Regtest];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressInfo {
+    pub address: String,
+    pub chain: &'static str,
+    /// base58测试网和regtest地址前缀相同，统一为testnet
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<Network>,
+    pub address_type: String,
+    pub encoding: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub witness_version: Option<u8>,
+    /// 公钥/脚本hash或witness program
+    pub payload: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub script_pubkey: Option<String>,
+    /// ETH的EIP-55地址
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum_address: Option<String>,
+}
+
+/// 识别ETH或BTC地址并校验checksum，指定network时同时校验地址所属网络
+pub fn inspect_address(address: &str, network: Option<Network>) -> Result<AddressInfo> {
+    let address = address.trim();
+    if address.starts_with("0x") || address.starts_with("0X") {
+        if network.is_some() {
+            bail!("address {address} is an ETH address, network only applies to BTC");
+        }
+        return inspect_eth(address);
+    }
+    let lowercase = address.to_lowercase();
+    let info = match NETWORKS
+        .iter()
+        .find(|network| lowercase.starts_with(&format!("{}1", network.hrp())))
+    {
+        Some(network) => inspect_segwit(address, *network)?,
+        None => inspect_base58(address)?,
+    };
+    if let (Some(expect), Some(actual)) = (network, info.network) {
+        let matched = match info.witness_version {
+            Some(_) => expect == actual,
+            None => expect.p2pkh_prefix() == actual.p2pkh_prefix(),
+        };
+        if !matched {
+            bail!("address {address} belongs to {actual:?}, expect {expect:?}");
+        }
+    }
+    Ok(info)
+}
+
+fn inspect_eth(address: &str) -> Result<AddressInfo> {
+    let body = &address[2..];
+    if body.len() != 40 {
+        bail!(
+            "invalid ETH address {address}: expect 40 hex chars, got {}",
+            body.len()
+        );
+    }
+    let parsed =
+        Address::from_str(body).map_err(|e| anyhow!("invalid ETH address {address}: {e}"))?;
+    let checksum_address = to_checksum(&parsed, None);
+    let mixed_case = body.chars().any(|ch| ch.is_ascii_lowercase())
+        && body.chars().any(|ch| ch.is_ascii_uppercase());
+    if mixed_case && body != &checksum_address[2..] {
+        bail!("invalid ETH address {address}: EIP-55 checksum mismatch, expect {checksum_address}");
+    }
+    Ok(AddressInfo {
+        address: address.to_string(),
+        chain: "eth",
+        network: None,
+        address_type: "eoa/contract".to_string(),
+        encoding: if mixed_case { "eip55" } else { "hex" },
+        witness_version: None,
+        payload: hex::encode(parsed.as_bytes()),
+        script_pubkey: None,
+        checksum_address: Some(checksum_address),
+    })
+}
+
+fn inspect_segwit(address: &str, network: Network) -> Result<AddressInfo> {
+    let (version, program) = decode_segwit_address(address, network)?;
+    let address_type = match (version, program.len()) {
+        (0, 20) => "p2wpkh".to_string(),
+        (0, 32) => "p2wsh".to_string(),
+        (1, 32) => "p2tr".to_string(),
+        (version, _) => format!("witness_v{version}"),
+    };
+    Ok(AddressInfo {
+        address: address.to_string(),
+        chain: "btc",
+        network: Some(network),
+        address_type,
+        encoding: if version == 0 { "bech32" } else { "bech32m" },
+        witness_version: Some(version),
+        payload: hex::encode(&program),
+        script_pubkey: Some(hex::encode(segwit_script(version, &program))),
+        checksum_address: None,
+    })
+}
+
+fn inspect_base58(address: &str) -> Result<AddressInfo> {
+    let (version, hash) = decode_base58_address(address)?;
+    let Some(network) = [Network::Mainnet, Network::Testnet]
+        .into_iter()
+        .find(|network| [network.p2pkh_prefix(), network.p2sh_prefix()].contains(&version))
+    else {
+        bail!("address {address} has unknown version byte {version:#04x}");
+    };
+    let (address_type, script_pubkey) = if version == network.p2pkh_prefix() {
+        ("p2pkh", address_to_p2pkh(address)?)
+    } else {
+        ("p2sh", hex::encode(p2sh_script(&hash)))
+    };
+    Ok(AddressInfo {
+        address: address.to_string(),
+        chain: "btc",
+        network: Some(network),
+        address_type: address_type.to_string(),
+        encoding: "base58check",
+        witness_version: None,
+        payload: hex::encode(&hash),
+        script_pubkey: Some(script_pubkey),
+        checksum_address: None,
+    })
+}
+
+pub fn inspect(
+    address: String,
+    network: Option<String>,
+    output: Option<String>,
+) -> Result<AddressInfo> {
+    let network = network.as_deref().map(Network::from_str).transpose()?;
+    let info = inspect_address(&address, network)?;
+    write_output(output, &serde_json::to_string_pretty(&info)?)?;
+    Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_inspect_eth() {
+        let address = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let info = inspect_address(address, None).unwrap();
+        assert_eq!(info.encoding, "eip55");
+        assert_eq!(info.payload, "5aaeb6053f3e94c9b9a09f33669435e7ef1beaed");
+
+        let info = inspect_address(&address.to_lowercase(), None).unwrap();
+        assert_eq!(info.checksum_address.unwrap(), address);
+
+        let err = inspect_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD", None).unwrap_err();
+        assert!(err.to_string().contains("EIP-55"));
+        assert!(inspect_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA", None).is_err());
+    }
+
+    #[test]
+    fn test_inspect_base58() {
+        let info = inspect_address("1GKSnhP1XmCjZpEyUoupWsm7c1o64seyow", None).unwrap();
+        assert_eq!(info.address_type, "p2pkh");
+        assert_eq!(info.network, Some(Network::Mainnet));
+        assert_eq!(
+            info.script_pubkey.unwrap(),
+            "76a914a806e693f0de6638d99b90bb3c32bf0ece28abf388ac"
+        );
+
+        let info = inspect_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy", None).unwrap();
+        assert_eq!(info.address_type, "p2sh");
+        assert_eq!(
+            info.script_pubkey.unwrap(),
+            "a914b472a266d0bd89c13706a4132ccfb16f7c3b9fcb87"
+        );
+
+        // 测试网地址也可以用于regtest
+        let address = "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn";
+        let info = inspect_address(address, Some(Network::Regtest)).unwrap();
+        assert_eq!(info.network, Some(Network::Testnet));
+        let err = inspect_address(address, Some(Network::Mainnet)).unwrap_err();
+        assert!(err.to_string().contains("expect Mainnet"));
+
+        let err = inspect_address("1GKSnhP1XmCjZpEyUoupWsm7c1o64seyox", None).unwrap_err();
+        assert!(err.to_string().contains("checksum"));
+    }
+
+    #[test]
+    fn test_inspect_segwit() {
+        let info = inspect_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4", None).unwrap();
+        assert_eq!(info.address_type, "p2wpkh");
+        assert_eq!(info.encoding, "bech32");
+        assert_eq!(
+            info.script_pubkey.unwrap(),
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+
+        let address = "tb1pqqqqp399et2xygdj5xreqhjjvcmzhxw4aywxecjdzew6hylgvsesf3hn0c";
+        let info = inspect_address(address, Some(Network::Testnet)).unwrap();
+        assert_eq!(info.address_type, "p2tr");
+        assert_eq!(info.witness_version, Some(1));
+        assert!(inspect_address(address, Some(Network::Regtest)).is_err());
+
+        let info = inspect_address("bcrt1qw508d6qejxtdg4y5r3zarvary0c5xw7kygt080", None).unwrap();
+        assert_eq!(info.network, Some(Network::Regtest));
+
+        // v1使用bech32编码
+        let err = inspect_address(
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+            None,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("must use bech32m"));
+        // checksum错误
+        assert!(inspect_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5", None).is_err());
+    }
+}
@@ -1,7 +1,191 @@
-use bip32::{DerivationPath, Prefix, XPrv, XPub};
+use anyhow::{anyhow, bail, Result};
+use bip32::{ChildNumber, DerivationPath, ExtendedKey, Prefix, XPrv, XPub};
 use bip39::Mnemonic;
+use serde::Serialize;
 use std::str::FromStr;
 
+use crate::btc::{AddressType, Network};
+
+/// SLIP-132扩展密钥前缀隐含的脚本类型，大写的Y/Z/U/V为多签
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScriptType {
+    #[serde(rename = "p2pkh")]
+    P2pkh,
+    #[serde(rename = "p2sh-p2wpkh")]
+    P2shP2wpkh,
+    #[serde(rename = "p2wpkh")]
+    P2wpkh,
+    #[serde(rename = "p2sh-p2wsh")]
+    P2shP2wsh,
+    #[serde(rename = "p2wsh")]
+    P2wsh,
+}
+
+impl ScriptType {
+    /// 单签对应的地址类型，多签没有单一公钥的地址
+    pub fn address_type(&self) -> Option<AddressType> {
+        match self {
+            ScriptType::P2pkh => Some(AddressType::P2pkh),
+            ScriptType::P2shP2wpkh => Some(AddressType::P2shP2wpkh),
+            ScriptType::P2wpkh => Some(AddressType::P2wpkh),
+            ScriptType::P2shP2wsh | ScriptType::P2wsh => None,
+        }
+    }
+}
+
+/// SLIP-132中一组公私钥版本号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slip132 {
+    pub public: Prefix,
+    pub private: Prefix,
+    pub script_type: ScriptType,
+    pub network: Network,
+}
+
+const fn slip132(
+    public: (&str, u32),
+    private: (&str, u32),
+    script_type: ScriptType,
+    network: Network,
+) -> Slip132 {
+    Slip132 {
+        public: Prefix::from_parts_unchecked(public.0, public.1),
+        private: Prefix::from_parts_unchecked(private.0, private.1),
+        script_type,
+        network,
+    }
+}
+
+/// https://github.com/satoshilabs/slips/blob/master/slip-0132.md
+pub const SLIP132_VERSIONS: [Slip132; 10] = [
+    slip132(
+        ("xpub", 0x0488b21e),
+        ("xprv", 0x0488ade4),
+        ScriptType::P2pkh,
+        Network::Mainnet,
+    ),
+    slip132(
+        ("ypub", 0x049d7cb2),
+        ("yprv", 0x049d7878),
+        ScriptType::P2shP2wpkh,
+        Network::Mainnet,
+    ),
+    slip132(
+        ("Ypub", 0x0295b43f),
+        ("Yprv", 0x0295b005),
+        ScriptType::P2shP2wsh,
+        Network::Mainnet,
+    ),
+    slip132(
+        ("zpub", 0x04b24746),
+        ("zprv", 0x04b2430c),
+        ScriptType::P2wpkh,
+        Network::Mainnet,
+    ),
+    slip132(
+        ("Zpub", 0x02aa7ed3),
+        ("Zprv", 0x02aa7a99),
+        ScriptType::P2wsh,
+        Network::Mainnet,
+    ),
+    slip132(
+        ("tpub", 0x043587cf),
+        ("tprv", 0x04358394),
+        ScriptType::P2pkh,
+        Network::Testnet,
+    ),
+    slip132(
+        ("upub", 0x044a5262),
+        ("uprv", 0x044a4e28),
+        ScriptType::P2shP2wpkh,
+        Network::Testnet,
+    ),
+    slip132(
+        ("Upub", 0x024289ef),
+        ("Uprv", 0x024285b5),
+        ScriptType::P2shP2wsh,
+        Network::Testnet,
+    ),
+    slip132(
+        ("vpub", 0x045f1cf6),
+        ("vprv", 0x045f18bc),
+        ScriptType::P2wpkh,
+        Network::Testnet,
+    ),
+    slip132(
+        ("Vpub", 0x02575483),
+        ("Vprv", 0x02575048),
+        ScriptType::P2wsh,
+        Network::Testnet,
+    ),
+];
+
+impl Slip132 {
+    /// 按前缀查找，如 zpub 或 zprv，区分大小写
+    pub fn from_prefix(prefix: &str) -> Result<Self> {
+        SLIP132_VERSIONS
+            .into_iter()
+            .find(|v| v.public.as_str() == prefix || v.private.as_str() == prefix)
+            .ok_or_else(|| anyhow!("unknown extended key prefix {prefix}"))
+    }
+
+    pub fn from_version(version: u32) -> Result<Self> {
+        SLIP132_VERSIONS
+            .into_iter()
+            .find(|v| v.public.version() == version || v.private.version() == version)
+            .ok_or_else(|| anyhow!("unknown extended key version {version:#010x}"))
+    }
+}
+
+/// 解析任意SLIP-132前缀的扩展密钥，并返回前缀对应的版本信息
+pub fn parse_extended_key(key: &str) -> Result<(ExtendedKey, Slip132)> {
+    let extended_key =
+        ExtendedKey::from_str(key.trim()).map_err(|e| anyhow!("invalid extended key: {e}"))?;
+    let version = Slip132::from_version(extended_key.prefix.version())?;
+    Ok((extended_key, version))
+}
+
+/// 解析扩展公钥，私钥会转换为对应的公钥
+pub fn parse_x_pub(key: &str) -> Result<(XPub, Slip132)> {
+    let (extended_key, version) = parse_extended_key(key)?;
+    let x_pub = if extended_key.prefix.is_private() {
+        XPrv::try_from(extended_key)
+            .map_err(|e| anyhow!("invalid extended private key: {e}"))?
+            .public_key()
+    } else {
+        XPub::try_from(extended_key).map_err(|e| anyhow!("invalid extended public key: {e}"))?
+    };
+    Ok((x_pub, version))
+}
+
+/// 替换扩展密钥的版本号，如 zpub -> xpub；私钥可以转为任意前缀的公钥，公钥不能转为私钥
+pub fn convert_extended_key(key: &str, target: &str) -> Result<String> {
+    let (extended_key, _) = parse_extended_key(key)?;
+    let target_version = Slip132::from_prefix(target)?;
+    let target_public = target_version.public.as_str() == target;
+    match (extended_key.prefix.is_private(), target_public) {
+        (true, true) => {
+            let x_prv = XPrv::try_from(extended_key)
+                .map_err(|e| anyhow!("invalid extended private key: {e}"))?;
+            Ok(x_prv.public_key().to_string(target_version.public))
+        }
+        (false, false) => bail!("can not convert extended public key to {target}."),
+        (is_private, _) => {
+            let prefix = if is_private {
+                target_version.private
+            } else {
+                target_version.public
+            };
+            Ok(ExtendedKey {
+                prefix,
+                attrs: extended_key.attrs.clone(),
+                key_bytes: extended_key.key_bytes,
+            }
+            .to_string())
+        }
+    }
+}
+
 pub fn mnemonic_to_x_prv(words: String, passphrase: String) -> XPrv {
     let mnemonic = Mnemonic::from_str(words.as_str()).unwrap();
     let seed = mnemonic.to_seed(&passphrase);
@@ -27,6 +211,26 @@ pub fn derive_public_by_path(path: String, x_pub: String) -> XPub {
     x_pub
 }
 
+/// 按路径派生私钥，如 "m/84'/0'/0'"
+pub fn derive_x_prv(x_prv: &XPrv, path: &str) -> Result<XPrv> {
+    let path = DerivationPath::from_str(path).map_err(|e| anyhow!("invalid path {path}: {e}"))?;
+    let mut x_prv = x_prv.clone();
+    for child in path.iter() {
+        x_prv = x_prv
+            .derive_child(child)
+            .map_err(|e| anyhow!("derive child {child} failed: {e}"))?;
+    }
+    Ok(x_prv)
+}
+
+/// 扩展公钥只能派生非harden的子节点
+pub fn derive_x_pub_child(x_pub: &XPub, index: u32) -> Result<XPub> {
+    let child = ChildNumber::new(index, false)?;
+    x_pub
+        .derive_child(child)
+        .map_err(|e| anyhow!("derive child {child} failed: {e}"))
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -56,4 +260,33 @@ mod test {
         );
         assert_eq!(derive_pub_key, XPub::from_str("xpub6GA7ErDewmHKd39n4FmHiaCe8fPeHRKW95P1bqy5kisuG67jS4HkBrnUg8ccxVbou2WKHJgXRojm4p2gMHcRoLvjsu9Ty7Q9HGa6ruJfJ5N").unwrap());
     }
+
+    #[test]
+    fn test_convert_extended_key() {
+        // BIP-84测试向量中的账户扩展公钥和私钥
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let zprv = "zprvAdG4iTXWBoARxkkzNpNh8r6Qag3irQB8PzEMkAFeTRXxHpbF9z4QgEvBRmfvqWvGp42t42nvgGpNgYSJA9iefm1yYNZKEm7z6qUWCroSQnE";
+        let xpub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        assert_eq!(convert_extended_key(zpub, "xpub").unwrap(), xpub);
+        assert_eq!(convert_extended_key(xpub, "zpub").unwrap(), zpub);
+        assert_eq!(convert_extended_key(zprv, "zpub").unwrap(), zpub);
+        assert_eq!(convert_extended_key(zprv, "xpub").unwrap(), xpub);
+        let yprv = convert_extended_key(zprv, "yprv").unwrap();
+        assert!(yprv.starts_with("yprv"));
+        assert_eq!(convert_extended_key(&yprv, "zprv").unwrap(), zprv);
+        assert!(convert_extended_key(zpub, "zprv").is_err());
+        assert!(convert_extended_key(zpub, "wpub").is_err());
+        for version in SLIP132_VERSIONS {
+            let public = convert_extended_key(zpub, version.public.as_str()).unwrap();
+            assert!(public.starts_with(version.public.as_str()));
+            let (_, detected) = parse_extended_key(&public).unwrap();
+            assert_eq!(detected, version);
+            let private = convert_extended_key(zprv, version.private.as_str()).unwrap();
+            assert!(private.starts_with(version.private.as_str()));
+        }
+        let (x_pub, version) = parse_x_pub(zprv).unwrap();
+        assert_eq!(x_pub, XPub::from_str(xpub).unwrap());
+        assert_eq!(version.script_type, ScriptType::P2wpkh);
+        assert_eq!(version.network, Network::Mainnet);
+    }
 }
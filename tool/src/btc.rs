@@ -1,10 +1,16 @@
-use crate::bip32::{derive_private_by_path, derive_public_by_path, mnemonic_to_x_prv};
+use crate::bip32::{
+    convert_extended_key, derive_private_by_path, derive_public_by_path, mnemonic_to_x_prv,
+    parse_extended_key, ScriptType,
+};
+use crate::btc_script::{decode_base58_address, p2pkh_script};
 use crate::eth::get_public_key;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
+use bech32::{u5, ToBase32, Variant};
 use bip32::{Prefix, PublicKey as Bip32PubKey};
 use bitcoin_hashes::{ripemd160, Hash};
-use bs58::{decode, encode};
-use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bs58::encode;
+use secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey};
+use serde::Serialize;
 use sha2::{Digest, Sha256};
 use std::str::FromStr;
 use tracing::info;
@@ -13,11 +19,202 @@ use crate::util::{hex_string_2_array, u8_array_convert_string};
 
 // base58 for 1
 const P2PKH_PREFIX: u8 = 0x00;
+// base58 for 3
+const P2SH_PREFIX: u8 = 0x05;
+// 测试网和regtest的m/n和2开头
+const TESTNET_P2PKH_PREFIX: u8 = 0x6f;
+const TESTNET_P2SH_PREFIX: u8 = 0xc4;
+const OP_0: u8 = 0x00;
 const OP_DUP: u8 = 0x76;
 const OP_HASH160: u8 = 0xa9;
 const OP_EQUALVERIFY: u8 = 0x88;
 const OP_CHECKSIG: u8 = 0xac;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+    Regtest,
+}
+
+impl Network {
+    pub fn p2pkh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => P2PKH_PREFIX,
+            _ => TESTNET_P2PKH_PREFIX,
+        }
+    }
+
+    pub fn p2sh_prefix(&self) -> u8 {
+        match self {
+            Network::Mainnet => P2SH_PREFIX,
+            _ => TESTNET_P2SH_PREFIX,
+        }
+    }
+
+    /// bech32地址的human-readable part
+    pub fn hrp(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "bc",
+            Network::Testnet => "tb",
+            Network::Regtest => "bcrt",
+        }
+    }
+
+    /// BIP-44中的coin type，测试网统一为1
+    pub fn coin_type(&self) -> u32 {
+        match self {
+            Network::Mainnet => 0,
+            _ => 1,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mainnet" | "main" | "bitcoin" => Ok(Network::Mainnet),
+            "testnet" | "test" | "signet" => Ok(Network::Testnet),
+            "regtest" => Ok(Network::Regtest),
+            _ => Err(anyhow!("network {s} not supported.")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum AddressType {
+    #[serde(rename = "p2pkh")]
+    P2pkh,
+    #[serde(rename = "p2sh-p2wpkh")]
+    P2shP2wpkh,
+    #[serde(rename = "p2wpkh")]
+    P2wpkh,
+    #[serde(rename = "p2tr")]
+    P2tr,
+}
+
+impl AddressType {
+    /// BIP-44/49/84/86对应的地址类型
+    pub fn from_purpose(purpose: u32) -> Result<Self> {
+        match purpose {
+            44 => Ok(AddressType::P2pkh),
+            49 => Ok(AddressType::P2shP2wpkh),
+            84 => Ok(AddressType::P2wpkh),
+            86 => Ok(AddressType::P2tr),
+            _ => Err(anyhow!("purpose {purpose} not supported.")),
+        }
+    }
+
+    pub fn purpose(&self) -> u32 {
+        match self {
+            AddressType::P2pkh => 44,
+            AddressType::P2shP2wpkh => 49,
+            AddressType::P2wpkh => 84,
+            AddressType::P2tr => 86,
+        }
+    }
+}
+
+impl FromStr for AddressType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "p2pkh" => Ok(AddressType::P2pkh),
+            "p2sh-p2wpkh" => Ok(AddressType::P2shP2wpkh),
+            "p2wpkh" => Ok(AddressType::P2wpkh),
+            "p2tr" => Ok(AddressType::P2tr),
+            _ => Err(anyhow!("address type {s} not supported.")),
+        }
+    }
+}
+
+/// ripemd160(sha256(data))
+pub fn hash160(data: &[u8]) -> Vec<u8> {
+    let sha256 = Sha256::digest(data);
+    let hash = ripemd160::Hash::hash(&sha256);
+    let out: &[u8] = hash.as_ref();
+    out.to_vec()
+}
+
+/// version + payload + 4字节checksum 后进行base58编码
+pub fn base58check_encode(version: u8, payload: &[u8]) -> String {
+    let mut data = vec![version];
+    data.extend_from_slice(payload);
+    let checksum = double_sha256(&data);
+    data.extend_from_slice(&checksum[..4]);
+    encode(data).into_string()
+}
+
+/// segwit v0使用bech32，v1及以上使用bech32m(BIP-350)
+pub fn segwit_address(network: Network, version: u8, program: &[u8]) -> Result<String> {
+    let variant = if version == 0 {
+        Variant::Bech32
+    } else {
+        Variant::Bech32m
+    };
+    let mut data = vec![u5::try_from_u8(version)?];
+    data.extend(program.to_base32());
+    Ok(bech32::encode(network.hrp(), data, variant)?)
+}
+
+/// BIP-340 tagged hash: sha256(sha256(tag) + sha256(tag) + msg)
+pub fn tagged_hash(tag: &str, msg: &[u8]) -> [u8; 32] {
+    let tag_hash = Sha256::digest(tag.as_bytes());
+    let mut sha256 = Sha256::new();
+    sha256.update(tag_hash);
+    sha256.update(tag_hash);
+    sha256.update(msg);
+    sha256.finalize().into()
+}
+
+/// BIP-86 没有script path时的taproot输出公钥 Q = P + H_TapTweak(P)·G
+pub fn taproot_output_key(public_key: &PublicKey) -> Result<XOnlyPublicKey> {
+    taproot_tweak_key(&public_key.x_only_public_key().0, None)
+}
+
+/// BIP-341 输出公钥 Q = P + H_TapTweak(P || merkle_root)·G
+pub fn taproot_tweak_key(
+    internal_key: &XOnlyPublicKey,
+    merkle_root: Option<&[u8]>,
+) -> Result<XOnlyPublicKey> {
+    let tweak_data = [
+        &internal_key.serialize()[..],
+        merkle_root.unwrap_or_default(),
+    ]
+    .concat();
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &tweak_data))?;
+    let (output_key, _) = internal_key.add_tweak(secp256k1::SECP256K1, &tweak)?;
+    Ok(output_key)
+}
+
+/// 按地址类型和网络由压缩公钥生成地址
+pub fn public_key_to_address(
+    public_key: &PublicKey,
+    address_type: AddressType,
+    network: Network,
+) -> Result<String> {
+    let pubkey_hash = hash160(&public_key.serialize());
+    match address_type {
+        AddressType::P2pkh => Ok(base58check_encode(network.p2pkh_prefix(), &pubkey_hash)),
+        AddressType::P2shP2wpkh => {
+            // redeem script为 OP_0 <20字节公钥hash>
+            let redeem_script = [&[OP_0, 20][..], &pubkey_hash].concat();
+            Ok(base58check_encode(
+                network.p2sh_prefix(),
+                &hash160(&redeem_script),
+            ))
+        }
+        AddressType::P2wpkh => segwit_address(network, 0, &pubkey_hash),
+        AddressType::P2tr => {
+            segwit_address(network, 1, &taproot_output_key(public_key)?.serialize())
+        }
+    }
+}
+
 // 先实现最基础的P2PKH和P2WPKH，其他的多签和script的类型暂不考虑，后续考虑优化，这里也不过多依赖第三方库
 fn pub_key_to_address(public_key: PublicKey) -> String {
     // sha256
@@ -48,40 +245,40 @@ fn pub_key_to_address(public_key: PublicKey) -> String {
     encode(out).into_string()
 }
 
-pub fn secret_to_address(secret_key: String) -> Result<()> {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BtcAddress {
+    pub address: String,
+}
+
+pub fn secret_to_address(secret_key: String) -> Result<BtcAddress> {
     let public_key = get_public_key(&secret_key);
     let address = pub_key_to_address(public_key);
-    info!("address: {:?}", address);
-    Ok(())
+    Ok(BtcAddress { address })
 }
 
-fn address_to_p2pkh(address: String) -> String {
-    let address_bytes = decode(address).into_vec().unwrap();
-    // check
-    let sep = address_bytes.len() - 4;
-    let checksum = double_sha256(&address_bytes[..sep]);
-    assert_eq!(&checksum[..4], &address_bytes[sep..]);
-    let pub_key_bytes = &address_bytes[1..sep];
-    let p2pkh = [
-        &[OP_DUP, OP_HASH160, pub_key_bytes.len() as u8][..],
-        &pub_key_bytes[..],
-        &[OP_EQUALVERIFY, OP_CHECKSIG],
-    ]
-    .concat();
-    let script_hex = u8_array_convert_string(&p2pkh);
-    script_hex
+/// P2PKH地址转为scriptPubKey的hex，主网和测试网地址都支持
+pub(crate) fn address_to_p2pkh(address: &str) -> Result<String> {
+    let (version, hash) = decode_base58_address(address)?;
+    if version != P2PKH_PREFIX && version != TESTNET_P2PKH_PREFIX {
+        bail!("address {address} version {version:#04x} is not P2PKH");
+    }
+    Ok(u8_array_convert_string(&p2pkh_script(&hash)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TxHash {
+    pub tx_hash: String,
 }
 
-pub fn get_tx_hash(raw_tx: String) -> Result<()> {
+pub fn get_tx_hash(raw_tx: String) -> Result<TxHash> {
     let tx_bytes = hex_string_2_array(&raw_tx);
     let mut hash_bytes = double_sha256(&tx_bytes);
     hash_bytes.reverse();
     let tx_hash = u8_array_convert_string(&hash_bytes);
-    println!("tx hash: {:?}", tx_hash);
-    Ok(())
+    Ok(TxHash { tx_hash })
 }
 
-fn double_sha256(input: &[u8]) -> Vec<u8> {
+pub fn double_sha256(input: &[u8]) -> Vec<u8> {
     let mut sha256 = Sha256::new();
     sha256.update(&input[..]);
     let out = sha256.finalize().to_vec();
@@ -91,46 +288,88 @@ fn double_sha256(input: &[u8]) -> Vec<u8> {
     result
 }
 
-pub fn bip32_to_address(xkey: String, path: String) -> Result<()> {
-    let x_pub_key = if xkey.starts_with("xprv") {
+/// 地址类型和网络由扩展密钥的SLIP-132前缀决定，如 zpub 为P2WPKH，tpub 为测试网P2PKH
+pub fn bip32_to_address(xkey: String, path: String) -> Result<BtcAddress> {
+    let (extended_key, version) = parse_extended_key(&xkey)?;
+    let address_type = version.script_type.address_type().ok_or_else(|| {
+        anyhow!(
+            "{} is a multisig extended key, can not derive a single key address.",
+            extended_key.prefix.as_str()
+        )
+    })?;
+    let x_pub_key = if extended_key.prefix.is_private() {
         let x_priv_key = derive_private_by_path(path, xkey);
         x_priv_key.public_key()
     } else {
         derive_public_by_path(path, xkey)
     };
-    let address = pub_key_to_address(
-        PublicKey::from_slice(x_pub_key.public_key().to_bytes().to_vec().as_slice()).unwrap(),
-    );
-    info!("address: {:?}", address);
-    Ok(())
+    let address = public_key_to_address(
+        &PublicKey::from_slice(x_pub_key.public_key().to_bytes().to_vec().as_slice())?,
+        address_type,
+        version.network,
+    )?;
+    Ok(BtcAddress { address })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConvertedKey {
+    pub source: String,
+    pub script_type: ScriptType,
+    pub network: Network,
+    pub key: String,
+}
+
+pub fn convert_x_key(key: String, target: String) -> Result<ConvertedKey> {
+    let (extended_key, version) = parse_extended_key(&key)?;
+    Ok(ConvertedKey {
+        source: extended_key.prefix.as_str().to_string(),
+        script_type: version.script_type,
+        network: version.network,
+        key: convert_extended_key(&key, &target)?,
+    })
 }
 
-pub fn bip39_to_key(mnemonic: String, passphrase: String) -> Result<()> {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bip39Key {
+    pub x_prv: String,
+    pub path: String,
+    pub account_x_prv: String,
+    pub account_x_pub: String,
+}
+
+pub fn bip39_to_key(mnemonic: String, passphrase: String) -> Result<Bip39Key> {
     let key = mnemonic_to_x_prv(mnemonic, passphrase);
     let xkey_str = &*key.to_string(Prefix::XPRV).to_string();
-    let extend_key = derive_private_by_path("m/44'/0'/0'".to_string(), xkey_str.to_string());
-    info!("xPrivKey: {:?}", xkey_str);
-    info!(
-        "extend key for m/44'/0'/0': {:?}\n xPubKey: {:?}",
-        extend_key.to_string(Prefix::XPRV),
-        extend_key.public_key().to_string(Prefix::XPUB)
-    );
-    Ok(())
+    let path = "m/44'/0'/0'".to_string();
+    let extend_key = derive_private_by_path(path.clone(), xkey_str.to_string());
+    Ok(Bip39Key {
+        x_prv: xkey_str.to_string(),
+        path,
+        account_x_prv: extend_key.to_string(Prefix::XPRV).to_string(),
+        account_x_pub: extend_key.public_key().to_string(Prefix::XPUB),
+    })
 }
 
-pub fn address_to_script(address: String) -> Result<()> {
-    let p2pkh = address_to_p2pkh(address);
-    info!("script_hex P2PKH: {:?}", p2pkh);
-    Ok(())
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AddressScript {
+    pub address: String,
+    pub script_pubkey: String,
+}
+
+pub fn address_to_script(address: String) -> Result<AddressScript> {
+    let script_pubkey = address_to_p2pkh(&address)?;
+    Ok(AddressScript {
+        address,
+        script_pubkey,
+    })
 }
 
 // 后续加network和地址类型参数
-pub fn network_pub_key_to_address(public_key: String) -> Result<()> {
-    let public_key =
-        PublicKey::from_str(&public_key).expect("btc get public key from string failed");
+pub fn network_pub_key_to_address(public_key: String) -> Result<BtcAddress> {
+    let public_key = PublicKey::from_str(&public_key)
+        .map_err(|e| anyhow!("invalid public key {public_key}: {e}"))?;
     let address = pub_key_to_address(public_key);
-    info!("address: {:?}", address);
-    Ok(())
+    Ok(BtcAddress { address })
 }
 
 pub fn wif_2_private_key(wif: String, is_compressed: bool) -> String {
@@ -157,15 +396,20 @@ pub fn private_2_wif_key(private_key: String, is_compressed: bool) -> String {
     bs58::encode(key_bytes).into_string()
 }
 
-pub fn private_key_convert(private_key: String, format: String) -> Result<()> {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ConvertedPrivateKey {
+    pub format: &'static str,
+    pub key: String,
+}
+
+pub fn private_key_convert(private_key: String, format: String) -> Result<ConvertedPrivateKey> {
     if format.eq(&"hex".to_string()) {
         let key = private_2_wif_key(private_key, true);
-        info!("hex => wif: {:?}", key);
+        Ok(ConvertedPrivateKey { format: "wif", key })
     } else {
         let key = wif_2_private_key(private_key, true);
-        info!("wif => hex: {:?}", key);
+        Ok(ConvertedPrivateKey { format: "hex", key })
     }
-    Ok(())
 }
 
 #[cfg(test)]
@@ -185,11 +429,26 @@ mod test {
     #[test]
     pub fn to_script() {
         let address = "1GKSnhP1XmCjZpEyUoupWsm7c1o64seyow".to_string();
-        let p2pkh = address_to_p2pkh(address);
+        let p2pkh = address_to_p2pkh(&address).unwrap();
         assert_eq!(
             p2pkh,
             "76a914a806e693f0de6638d99b90bb3c32bf0ece28abf388ac".to_string()
         );
+        // 校验和错误和P2SH地址都返回错误
+        assert!(address_to_p2pkh("1GKSnhP1XmCjZpEyUoupWsm7c1o64seyox").is_err());
+        assert!(address_to_p2pkh("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy").is_err());
+    }
+
+    #[test]
+    pub fn test_convert_x_key() {
+        // BIP-49测试向量中的账户扩展公钥
+        let ypub = "ypub6Ww3ibxVfGzLrAH1PNcjyAWenMTbbAosGNB6VvmSEgytSER9azLDWCxoJwW7Ke7icmizBMXrzBx9979FfaHxHcrArf3zbeJJJUZPf663zsP";
+        let converted = convert_x_key(ypub.to_string(), "zpub".to_string()).unwrap();
+        assert_eq!(converted.source, "ypub");
+        assert_eq!(converted.script_type, ScriptType::P2shP2wpkh);
+        assert!(converted.key.starts_with("zpub"));
+        let value = serde_json::to_value(&converted).unwrap();
+        assert_eq!(value["script_type"], "p2sh-p2wpkh");
     }
 
     #[test]
@@ -216,4 +475,35 @@ mod test {
         let path = "m/0/1".to_string();
         bip32_to_address(xpub_key, path);
     }
+
+    #[test]
+    pub fn test_public_key_to_address() {
+        // BIP-173中的示例公钥
+        let public_key = PublicKey::from_str(
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+        )
+        .unwrap();
+        let address = |address_type, network| {
+            public_key_to_address(&public_key, address_type, network).unwrap()
+        };
+        assert_eq!(
+            address(AddressType::P2wpkh, Network::Mainnet),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"
+        );
+        assert_eq!(
+            address(AddressType::P2wpkh, Network::Testnet),
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx"
+        );
+        assert_eq!(
+            address(AddressType::P2pkh, Network::Mainnet),
+            "1BgGZ9tcN4rm9KBzDn7KprQz87SZ26SAMH"
+        );
+        assert_eq!(
+            address(AddressType::P2pkh, Network::Mainnet),
+            pub_key_to_address(public_key)
+        );
+        assert!(address(AddressType::P2tr, Network::Regtest).starts_with("bcrt1p"));
+        assert_eq!(AddressType::from_purpose(84).unwrap(), AddressType::P2wpkh);
+        assert!(AddressType::from_purpose(45).is_err());
+    }
 }
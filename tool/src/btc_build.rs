@@ -0,0 +1,569 @@
+use anyhow::{anyhow, bail, Result};
+use bip32::{ChildNumber, DerivationPath, XPrv};
+use secp256k1::{KeyPair, Message, PublicKey, Scalar, SecretKey, SECP256K1};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::bip32::derive_x_prv;
+use crate::btc::{hash160, tagged_hash, AddressType, Network};
+use crate::btc_script::{
+    address_to_script_pubkey, p2pkh_script, public_key_to_script_pubkey, push_bytes, segwit_script,
+};
+use crate::btc_tx::{format_btc, parse_hex, OutPoint, Transaction, TxIn, TxOut, SIGHASH_ALL};
+use crate::cli::BuildTxArgs;
+use crate::encrypt_decrypt::read_input;
+use crate::output::Output;
+use crate::util::u8_array_convert_string;
+
+/// 低于该金额的找零并入手续费
+pub const DUST_LIMIT: u64 = 546;
+/// 启用RBF(BIP-125)
+const SEQUENCE_RBF: u32 = 0xffff_fffd;
+// 估算手续费时使用的最大签名长度
+const MAX_ECDSA_SIGNATURE_SIZE: usize = 72;
+const SCHNORR_SIGNATURE_SIZE: usize = 64;
+/// 与Bitcoin Core默认的maxfeerate(0.1 BTC/kvB)一致，超过时大概率是输入错误
+pub const MAX_FEE_RATE: f64 = 10_000.0;
+
+/// UTXO文件中的一项，amount单位为sat，path为相对xprv根节点的完整路径，地址类型默认按path的purpose推断
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    pub amount: u64,
+    pub path: String,
+    #[serde(default)]
+    pub address_type: Option<String>,
+}
+
+/// 已经派生出私钥的输入
+#[derive(Debug, Clone)]
+pub struct SpendableInput {
+    pub outpoint: OutPoint,
+    pub amount: u64,
+    pub address_type: AddressType,
+    pub secret_key: SecretKey,
+    pub public_key: PublicKey,
+    pub script_pubkey: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BuiltTransaction {
+    pub txid: String,
+    pub raw_transaction: String,
+    pub fee: u64,
+    pub fee_rate: f64,
+    pub vsize: usize,
+    pub input_amount: u64,
+    pub change: u64,
+}
+
+/// 由BIP-44/49/84/86路径的purpose推断地址类型
+fn path_address_type(path: &DerivationPath) -> Result<AddressType> {
+    let purpose = path
+        .iter()
+        .next()
+        .map(ChildNumber::index)
+        .ok_or_else(|| anyhow!("empty derivation path"))?;
+    AddressType::from_purpose(purpose)
+}
+
+pub fn derive_key(root: &XPrv, path: &str) -> Result<(SecretKey, PublicKey)> {
+    let x_prv = derive_x_prv(root, path)?;
+    let secret_key = SecretKey::from_slice(&x_prv.private_key().to_bytes())?;
+    Ok((secret_key, secret_key.public_key(SECP256K1)))
+}
+
+impl SpendableInput {
+    pub fn from_utxo(root: &XPrv, utxo: &Utxo) -> Result<Self> {
+        let path = DerivationPath::from_str(&utxo.path)
+            .map_err(|e| anyhow!("invalid path {}: {e}", utxo.path))?;
+        let address_type = match &utxo.address_type {
+            Some(address_type) => AddressType::from_str(address_type)?,
+            None => path_address_type(&path)?,
+        };
+        let (secret_key, public_key) = derive_key(root, &utxo.path)?;
+        let mut txid: [u8; 32] = parse_hex(&utxo.txid)?
+            .try_into()
+            .map_err(|_| anyhow!("txid {} must be 32 bytes", utxo.txid))?;
+        txid.reverse();
+        Ok(SpendableInput {
+            outpoint: OutPoint {
+                txid,
+                vout: utxo.vout,
+            },
+            amount: utxo.amount,
+            address_type,
+            secret_key,
+            public_key,
+            script_pubkey: public_key_to_script_pubkey(&public_key, address_type)?,
+        })
+    }
+
+    fn tx_in(&self) -> TxIn {
+        TxIn {
+            previous_output: self.outpoint.clone(),
+            script_sig: vec![],
+            sequence: SEQUENCE_RBF,
+            witness: vec![],
+        }
+    }
+
+    /// 用最大长度的占位签名填充scriptSig和witness，用于估算交易大小
+    fn dummy_tx_in(&self) -> TxIn {
+        let signature = vec![0u8; MAX_ECDSA_SIGNATURE_SIZE];
+        let public_key = self.public_key.serialize().to_vec();
+        let mut tx_in = self.tx_in();
+        match self.address_type {
+            AddressType::P2pkh => {
+                tx_in.script_sig = [push_bytes(&signature), push_bytes(&public_key)].concat()
+            }
+            AddressType::P2shP2wpkh => {
+                tx_in.script_sig = push_bytes(&segwit_script(0, &hash160(&public_key)));
+                tx_in.witness = vec![signature, public_key];
+            }
+            AddressType::P2wpkh => tx_in.witness = vec![signature, public_key],
+            AddressType::P2tr => tx_in.witness = vec![vec![0u8; SCHNORR_SIGNATURE_SIZE]],
+        }
+        tx_in
+    }
+}
+
+/// 目标输出，格式为 address:amount，amount单位为sat
+pub fn parse_destination(destination: &str, network: Network) -> Result<TxOut> {
+    let (address, amount) = destination
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("destination {destination} must be address:amount"))?;
+    let value = amount
+        .trim()
+        .parse::<u64>()
+        .map_err(|e| anyhow!("invalid amount {amount}: {e}"))?;
+    if value < DUST_LIMIT {
+        bail!("amount {value} of {address} is below dust limit {DUST_LIMIT}");
+    }
+    Ok(TxOut {
+        value,
+        script_pubkey: address_to_script_pubkey(address, network)?,
+    })
+}
+
+fn estimate_fee(inputs: &[SpendableInput], outputs: &[TxOut], fee_rate: f64) -> (u64, usize) {
+    let tx = Transaction {
+        version: 2,
+        inputs: inputs.iter().map(SpendableInput::dummy_tx_in).collect(),
+        outputs: outputs.to_vec(),
+        lock_time: 0,
+    };
+    let vsize = tx.vsize();
+    ((vsize as f64 * fee_rate).ceil() as u64, vsize)
+}
+
+/// 选币结果，change为0时没有找零输出
+#[derive(Debug, Clone)]
+pub struct Selection {
+    pub inputs: Vec<SpendableInput>,
+    pub change: u64,
+    pub fee: u64,
+}
+
+/// 按金额从大到小选币，金额足够支付输出和手续费后停止；找零低于dust时并入手续费
+pub fn select_coins(
+    mut candidates: Vec<SpendableInput>,
+    outputs: &[TxOut],
+    change_script: Option<&[u8]>,
+    fee_rate: f64,
+) -> Result<Selection> {
+    let target = outputs
+        .iter()
+        .try_fold(0u64, |sum, output| sum.checked_add(output.value))
+        .ok_or_else(|| anyhow!("amount overflow: total of destinations exceeds u64"))?;
+    candidates.sort_by_key(|candidate| std::cmp::Reverse(candidate.amount));
+    let mut selected = vec![];
+    let mut total = 0u64;
+    for candidate in candidates {
+        total = total
+            .checked_add(candidate.amount)
+            .ok_or_else(|| anyhow!("amount overflow: total of inputs exceeds u64"))?;
+        selected.push(candidate);
+        let (fee, _) = estimate_fee(&selected, outputs, fee_rate);
+        // 输出加手续费溢出u64时，任何输入都不够支付
+        let Some(excess) = target
+            .checked_add(fee)
+            .and_then(|need| total.checked_sub(need))
+        else {
+            continue;
+        };
+        let Some(change_script) = change_script else {
+            if excess >= DUST_LIMIT {
+                bail!("change {excess} sat needs a change address or change path");
+            }
+            return Ok(Selection {
+                inputs: selected,
+                change: 0,
+                fee: fee + excess,
+            });
+        };
+        let mut with_change = outputs.to_vec();
+        with_change.push(TxOut {
+            value: 0,
+            script_pubkey: change_script.to_vec(),
+        });
+        let (fee_with_change, _) = estimate_fee(&selected, &with_change, fee_rate);
+        let change = target
+            .checked_add(fee_with_change)
+            .and_then(|need| total.checked_sub(need))
+            .unwrap_or(0);
+        return Ok(if change >= DUST_LIMIT {
+            Selection {
+                inputs: selected,
+                change,
+                fee: fee_with_change,
+            }
+        } else {
+            Selection {
+                inputs: selected,
+                change: 0,
+                fee: fee + excess,
+            }
+        });
+    }
+    bail!(
+        "insufficient funds: need {} sat plus fee, have {total} sat",
+        target
+    )
+}
+
+/// DER编码的ECDSA签名，末尾追加SIGHASH_ALL
+pub fn ecdsa_signature(secret_key: &SecretKey, sighash: &[u8; 32]) -> Result<Vec<u8>> {
+    let signature = SECP256K1.sign_ecdsa(&Message::from_slice(sighash)?, secret_key);
+    let mut signature = signature.serialize_der().to_vec();
+    signature.push(SIGHASH_ALL as u8);
+    Ok(signature)
+}
+
+/// taproot key path签名，私钥按 H_TapTweak(P || merkle_root) 调整，BIP-86没有script path时merkle_root为空
+pub fn schnorr_signature(
+    secret_key: &SecretKey,
+    merkle_root: Option<&[u8]>,
+    sighash: &[u8; 32],
+) -> Result<Vec<u8>> {
+    let key_pair = KeyPair::from_secret_key(SECP256K1, secret_key);
+    let (internal_key, _) = key_pair.x_only_public_key();
+    let tweak_data = [
+        &internal_key.serialize()[..],
+        merkle_root.unwrap_or_default(),
+    ]
+    .concat();
+    let tweak = Scalar::from_be_bytes(tagged_hash("TapTweak", &tweak_data))?;
+    let tweaked = key_pair.add_xonly_tweak(SECP256K1, &tweak)?;
+    let signature = SECP256K1.sign_schnorr_no_aux_rand(&Message::from_slice(sighash)?, &tweaked);
+    Ok(signature.as_ref().to_vec())
+}
+
+/// 对所有输入签名，tx的输入顺序与inputs一致
+pub fn sign_transaction(tx: &mut Transaction, inputs: &[SpendableInput]) -> Result<()> {
+    let prevouts: Vec<TxOut> = inputs
+        .iter()
+        .map(|input| TxOut {
+            value: input.amount,
+            script_pubkey: input.script_pubkey.clone(),
+        })
+        .collect();
+    for (index, input) in inputs.iter().enumerate() {
+        let public_key = input.public_key.serialize().to_vec();
+        let pubkey_hash = hash160(&public_key);
+        match input.address_type {
+            AddressType::P2pkh => {
+                let sighash = tx.legacy_sighash(index, &input.script_pubkey);
+                let signature = ecdsa_signature(&input.secret_key, &sighash)?;
+                tx.inputs[index].script_sig =
+                    [push_bytes(&signature), push_bytes(&public_key)].concat();
+            }
+            AddressType::P2shP2wpkh | AddressType::P2wpkh => {
+                let sighash =
+                    tx.segwit_v0_sighash(index, &p2pkh_script(&pubkey_hash), input.amount);
+                let signature = ecdsa_signature(&input.secret_key, &sighash)?;
+                if input.address_type == AddressType::P2shP2wpkh {
+                    tx.inputs[index].script_sig = push_bytes(&segwit_script(0, &pubkey_hash));
+                }
+                tx.inputs[index].witness = vec![signature, public_key];
+            }
+            AddressType::P2tr => {
+                let sighash = tx.taproot_key_spend_sighash(index, &prevouts)?;
+                tx.inputs[index].witness =
+                    vec![schnorr_signature(&input.secret_key, None, &sighash)?];
+            }
+        }
+    }
+    Ok(())
+}
+
+pub fn build_transaction(
+    root: &XPrv,
+    utxos: &[Utxo],
+    destinations: Vec<TxOut>,
+    change_script: Option<Vec<u8>>,
+    fee_rate: f64,
+) -> Result<(Transaction, Selection)> {
+    if destinations.is_empty() {
+        bail!("at least one destination is required.");
+    }
+    if !fee_rate.is_finite() || fee_rate <= 0.0 {
+        bail!("fee rate must be a finite number greater than 0.");
+    }
+    if fee_rate > MAX_FEE_RATE {
+        bail!("fee rate {fee_rate} sat/vB exceeds the maximum {MAX_FEE_RATE} sat/vB.");
+    }
+    let candidates = utxos
+        .iter()
+        .map(|utxo| SpendableInput::from_utxo(root, utxo))
+        .collect::<Result<Vec<_>>>()?;
+    let selection = select_coins(
+        candidates,
+        &destinations,
+        change_script.as_deref(),
+        fee_rate,
+    )?;
+    let mut outputs = destinations;
+    if let (Some(change_script), true) = (change_script, selection.change > 0) {
+        outputs.push(TxOut {
+            value: selection.change,
+            script_pubkey: change_script,
+        });
+    }
+    let mut tx = Transaction {
+        version: 2,
+        inputs: selection.inputs.iter().map(SpendableInput::tx_in).collect(),
+        outputs,
+        lock_time: 0,
+    };
+    sign_transaction(&mut tx, &selection.inputs)?;
+    Ok((tx, selection))
+}
+
+pub fn build_tx(args: BuildTxArgs) -> Result<Output> {
+    let network = Network::from_str(&args.network)?;
+    let root = XPrv::from_str(args.x_prv.trim())
+        .map_err(|e| anyhow!("invalid extended private key: {e}"))?;
+    let utxos: Vec<Utxo> = serde_json::from_str(&read_input(&args.utxos)?)
+        .map_err(|e| anyhow!("invalid utxo file: {e}"))?;
+    let destinations = args
+        .to
+        .iter()
+        .map(|destination| parse_destination(destination, network))
+        .collect::<Result<Vec<_>>>()?;
+    let change_script = match (args.change_address, args.change_path) {
+        (Some(_), Some(_)) => bail!("change_address and change_path can not be used together."),
+        (Some(address), None) => Some(address_to_script_pubkey(&address, network)?),
+        (None, Some(path)) => {
+            let address_type = path_address_type(
+                &DerivationPath::from_str(&path)
+                    .map_err(|e| anyhow!("invalid path {path}: {e}"))?,
+            )?;
+            let (_, public_key) = derive_key(&root, &path)?;
+            Some(public_key_to_script_pubkey(&public_key, address_type)?)
+        }
+        (None, None) => None,
+    };
+    let (tx, selection) =
+        build_transaction(&root, &utxos, destinations, change_script, args.fee_rate)?;
+    let input_amount = selection.inputs.iter().map(|input| input.amount).sum();
+    let built = BuiltTransaction {
+        txid: tx.txid(),
+        raw_transaction: u8_array_convert_string(&tx.serialize()),
+        fee: selection.fee,
+        fee_rate: selection.fee as f64 / tx.vsize() as f64,
+        vsize: tx.vsize(),
+        input_amount,
+        change: selection.change,
+    };
+    info!(
+        "spend {} inputs ({} BTC), fee {} sat, change {} sat",
+        selection.inputs.len(),
+        format_btc(input_amount),
+        selection.fee,
+        selection.change
+    );
+    Output::artifact(args.output, "transaction", &built)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bip32::mnemonic_to_x_prv;
+    use crate::btc_tx::hash_to_hex;
+    use secp256k1::ecdsa::Signature;
+    use secp256k1::schnorr;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn utxo(txid_byte: u8, amount: u64, path: &str) -> Utxo {
+        Utxo {
+            txid: hash_to_hex(&[txid_byte; 32]),
+            vout: txid_byte as u32,
+            amount,
+            path: path.to_string(),
+            address_type: None,
+        }
+    }
+
+    #[test]
+    fn test_legacy_signature() {
+        // BIP-143 native P2WPKH示例中第一个输入为P2PK，签名由RFC6979确定
+        let unsigned = "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000";
+        let tx = Transaction::parse(&parse_hex(unsigned).unwrap()).unwrap();
+        let script_code =
+            parse_hex("2103c9f4836b9a4f77fc0d81f7bcb01b7f1b35916864b9476c241ce9fc198bd25432ac")
+                .unwrap();
+        let secret_key =
+            SecretKey::from_str("bbc27228ddcb9209d7fd6f36b02f7dfa6252af40bb2f1cbc7a557da8027ff866")
+                .unwrap();
+        let signature = ecdsa_signature(&secret_key, &tx.legacy_sighash(0, &script_code)).unwrap();
+        assert_eq!(u8_array_convert_string(&signature), "30450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01");
+        let secret_key =
+            SecretKey::from_str("619c335025c7f4012e556c2a58b2506e30b8511b53ade95ea316fd8c3286feb9")
+                .unwrap();
+        let script_code = parse_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+        let signature = ecdsa_signature(
+            &secret_key,
+            &tx.segwit_v0_sighash(1, &script_code, 600000000),
+        )
+        .unwrap();
+        assert_eq!(u8_array_convert_string(&signature), "304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee01");
+    }
+
+    #[test]
+    fn test_build_transaction() {
+        let root = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let utxos = vec![
+            utxo(1, 20_000, "m/44'/0'/0'/0/0"),
+            utxo(2, 50_000, "m/84'/0'/0'/0/0"),
+            utxo(3, 40_000, "m/86'/0'/0'/0/0"),
+            utxo(4, 1_000, "m/49'/0'/0'/0/0"),
+        ];
+        let destination = parse_destination(
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4:60000",
+            Network::Mainnet,
+        )
+        .unwrap();
+        let (_, change_key) = derive_key(&root, "m/84'/0'/0'/1/0").unwrap();
+        let change_script = public_key_to_script_pubkey(&change_key, AddressType::P2wpkh).unwrap();
+        let (tx, selection) = build_transaction(
+            &root,
+            &utxos,
+            vec![destination.clone()],
+            Some(change_script.clone()),
+            10.0,
+        )
+        .unwrap();
+        // 从大到小选择了 50000 和 40000 两个输入
+        assert_eq!(selection.inputs.len(), 2);
+        assert_eq!(tx.outputs[0], destination);
+        assert_eq!(tx.outputs[1].script_pubkey, change_script);
+        assert_eq!(90_000 - 60_000 - selection.fee, selection.change);
+        assert!(selection.fee as f64 >= tx.vsize() as f64 * 10.0);
+        // 估算使用72字节签名，实际大小不超过估算值
+        assert!(selection.fee as f64 <= (tx.vsize() + 2) as f64 * 10.0);
+        let decoded = Transaction::parse(&tx.serialize()).unwrap();
+        assert_eq!(decoded, tx);
+        // 非法或过高的费率
+        for fee_rate in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e30, MAX_FEE_RATE + 1.0] {
+            assert!(
+                build_transaction(&root, &utxos, vec![destination.clone()], None, fee_rate)
+                    .is_err(),
+                "{fee_rate}"
+            );
+        }
+
+        let prevouts: Vec<TxOut> = selection
+            .inputs
+            .iter()
+            .map(|input| TxOut {
+                value: input.amount,
+                script_pubkey: input.script_pubkey.clone(),
+            })
+            .collect();
+        // P2WPKH 签名校验
+        let input = &selection.inputs[0];
+        assert_eq!(input.address_type, AddressType::P2wpkh);
+        let sighash = tx.segwit_v0_sighash(
+            0,
+            &p2pkh_script(&hash160(&input.public_key.serialize())),
+            input.amount,
+        );
+        let witness = &tx.inputs[0].witness;
+        let signature = Signature::from_der(&witness[0][..witness[0].len() - 1]).unwrap();
+        SECP256K1
+            .verify_ecdsa(
+                &Message::from_slice(&sighash).unwrap(),
+                &signature,
+                &input.public_key,
+            )
+            .unwrap();
+        // P2TR 签名使用输出公钥校验
+        let input = &selection.inputs[1];
+        assert_eq!(input.address_type, AddressType::P2tr);
+        let sighash = tx.taproot_key_spend_sighash(1, &prevouts).unwrap();
+        let signature = schnorr::Signature::from_slice(&tx.inputs[1].witness[0]).unwrap();
+        let output_key = crate::btc::taproot_output_key(&input.public_key).unwrap();
+        SECP256K1
+            .verify_schnorr(
+                &signature,
+                &Message::from_slice(&sighash).unwrap(),
+                &output_key,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_select_coins() {
+        let root = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let candidates = |amounts: &[u64]| {
+            amounts
+                .iter()
+                .enumerate()
+                .map(|(i, amount)| {
+                    SpendableInput::from_utxo(&root, &utxo(i as u8 + 1, *amount, "m/44'/0'/0'/0/0"))
+                        .unwrap()
+                })
+                .collect::<Vec<_>>()
+        };
+        let outputs =
+            vec![
+                parse_destination("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa:10000", Network::Mainnet)
+                    .unwrap(),
+            ];
+        // 余额不足
+        assert!(select_coins(candidates(&[5_000, 4_000]), &outputs, None, 1.0).is_err());
+        // 找零低于dust时并入手续费，不需要找零地址
+        let selection = select_coins(candidates(&[10_300]), &outputs, None, 1.0).unwrap();
+        assert_eq!(selection.change, 0);
+        assert_eq!(selection.fee, 300);
+        // 需要找零但没有找零地址
+        assert!(select_coins(candidates(&[20_000]), &outputs, None, 1.0).is_err());
+        let change_script = outputs[0].script_pubkey.clone();
+        let selection =
+            select_coins(candidates(&[20_000]), &outputs, Some(&change_script), 1.0).unwrap();
+        assert_eq!(selection.change + selection.fee, 10_000);
+        assert!(
+            parse_destination("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa:100", Network::Mainnet).is_err()
+        );
+
+        // 金额接近u64::MAX时返回错误而不是溢出
+        let huge = vec![TxOut {
+            value: u64::MAX,
+            script_pubkey: change_script.clone(),
+        }];
+        let err = select_coins(candidates(&[20_000]), &huge, None, 1.0).unwrap_err();
+        assert!(err.to_string().contains("insufficient funds"), "{err}");
+        let overflow = vec![huge[0].clone(), outputs[0].clone()];
+        let err = select_coins(candidates(&[20_000]), &overflow, None, 1.0).unwrap_err();
+        assert!(err.to_string().contains("amount overflow"), "{err}");
+        let err = select_coins(candidates(&[u64::MAX, u64::MAX]), &huge, None, 1.0).unwrap_err();
+        assert!(err.to_string().contains("amount overflow"), "{err}");
+        // 手续费估算溢出时视为余额不足
+        let err = select_coins(candidates(&[20_000]), &outputs, None, 1e30).unwrap_err();
+        assert!(err.to_string().contains("insufficient funds"), "{err}");
+    }
+}
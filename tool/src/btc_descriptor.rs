@@ -0,0 +1,560 @@
+use anyhow::{anyhow, bail, Result};
+use bip32::XPub;
+use ethers::utils::hex;
+use secp256k1::PublicKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+use crate::bip32::{derive_x_pub_child, parse_x_pub};
+use crate::btc::{hash160, AddressType, Network};
+use crate::btc_script::{
+    p2sh_script, public_key_to_script_pubkey, push_bytes, script_to_address, segwit_script, OP_1,
+    OP_CHECKMULTISIG,
+};
+use crate::encrypt_decrypt::write_output;
+use crate::util::u8_array_convert_string;
+
+/// BIP-380 checksum使用的字符集，字符在其中的位置参与计算
+const INPUT_CHARSET: &str = "0123456789()[],'/*abcdefgh@:$%{}IJKLMNOPQRSTUVWXYZ&+-.;<=>?!^_|~ijklmnopqrstuvwxyzABCDEFGH`#\"\\ ";
+const CHECKSUM_CHARSET: &[u8] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const CHECKSUM_LENGTH: usize = 8;
+/// OP_1..OP_16 能表示的最大公钥数量
+const MAX_MULTISIG_KEYS: usize = 16;
+/// P2SH的redeem script不能超过520字节，压缩公钥最多15个
+const MAX_P2SH_MULTISIG_KEYS: usize = 15;
+
+fn poly_mod(c: u64, value: u64) -> u64 {
+    let c0 = c >> 35;
+    let mut c = ((c & 0x7_ffff_ffff) << 5) ^ value;
+    for (bit, generator) in [
+        0xf5dee51989,
+        0xa9fdca3312,
+        0x1bab10e32d,
+        0x3706b1677a,
+        0x644d626ffd,
+    ]
+    .into_iter()
+    .enumerate()
+    {
+        if c0 >> bit & 1 == 1 {
+            c ^= generator;
+        }
+    }
+    c
+}
+
+/// BIP-380 描述符checksum
+pub fn descriptor_checksum(descriptor: &str) -> Result<String> {
+    let mut c = 1u64;
+    let mut class = 0u64;
+    let mut class_count = 0;
+    for ch in descriptor.chars() {
+        let position = INPUT_CHARSET
+            .find(ch)
+            .ok_or_else(|| anyhow!("invalid descriptor character {ch:?}"))?
+            as u64;
+        c = poly_mod(c, position & 31);
+        class = class * 3 + (position >> 5);
+        class_count += 1;
+        if class_count == 3 {
+            c = poly_mod(c, class);
+            class = 0;
+            class_count = 0;
+        }
+    }
+    if class_count > 0 {
+        c = poly_mod(c, class);
+    }
+    for _ in 0..CHECKSUM_LENGTH {
+        c = poly_mod(c, 0);
+    }
+    c ^= 1;
+    Ok((0..CHECKSUM_LENGTH)
+        .map(|i| CHECKSUM_CHARSET[(c >> (5 * (7 - i)) & 31) as usize] as char)
+        .collect())
+}
+
+/// 描述符中的公钥，扩展公钥可以带非harden的路径，以 /* 结尾时按index派生
+#[derive(Debug, Clone)]
+pub enum DescriptorKey {
+    Single(PublicKey),
+    Extended {
+        x_pub: XPub,
+        path: Vec<u32>,
+        wildcard: bool,
+    },
+}
+
+impl DescriptorKey {
+    /// 可选的 [fingerprint/path] 只用于记录来源，不参与派生；x_only为true时允许taproot的32字节公钥
+    fn parse(key: &str, network: Network, x_only: bool) -> Result<Self> {
+        let key = match key.strip_prefix('[') {
+            Some(rest) => {
+                let (origin, key) = rest
+                    .split_once(']')
+                    .ok_or_else(|| anyhow!("unclosed key origin in {key}"))?;
+                let fingerprint = origin.split('/').next().unwrap_or_default();
+                if fingerprint.len() != 8 || hex::decode(fingerprint).is_err() {
+                    bail!("invalid key origin fingerprint {fingerprint}");
+                }
+                key
+            }
+            None => key,
+        };
+        if key.chars().all(|ch| ch.is_ascii_hexdigit()) {
+            let bytes = hex::decode(key).map_err(|e| anyhow!("invalid public key {key}: {e}"))?;
+            let public_key = match bytes.len() {
+                33 => PublicKey::from_slice(&bytes)?,
+                32 if x_only => PublicKey::from_slice(&[&[0x02][..], &bytes].concat())?,
+                _ => bail!("public key {key} must be 33 bytes compressed key"),
+            };
+            return Ok(DescriptorKey::Single(public_key));
+        }
+        let mut parts = key.split('/');
+        let (x_pub, version) = parse_x_pub(parts.next().unwrap_or_default())?;
+        if (network == Network::Mainnet) != (version.network == Network::Mainnet) {
+            bail!(
+                "network {network:?} conflicts with extended key prefix {}.",
+                version.public.as_str()
+            );
+        }
+        let mut path = vec![];
+        let mut wildcard = false;
+        for part in parts {
+            if wildcard {
+                bail!("wildcard must be the last path element in {key}");
+            }
+            match part {
+                "*" => wildcard = true,
+                _ if part.ends_with(['\'', 'h', 'H']) => {
+                    bail!("hardened derivation {part} needs a private key")
+                }
+                _ => path.push(
+                    part.parse::<u32>()
+                        .map_err(|e| anyhow!("invalid path element {part}: {e}"))?,
+                ),
+            }
+        }
+        Ok(DescriptorKey::Extended {
+            x_pub,
+            path,
+            wildcard,
+        })
+    }
+
+    fn is_ranged(&self) -> bool {
+        matches!(self, DescriptorKey::Extended { wildcard: true, .. })
+    }
+
+    fn derive(&self, index: u32) -> Result<PublicKey> {
+        match self {
+            DescriptorKey::Single(public_key) => Ok(*public_key),
+            DescriptorKey::Extended {
+                x_pub,
+                path,
+                wildcard,
+            } => {
+                let mut x_pub = x_pub.clone();
+                for child in path.iter().chain(wildcard.then_some(&index)) {
+                    x_pub = derive_x_pub_child(&x_pub, *child)?;
+                }
+                Ok(PublicKey::from_slice(&x_pub.to_bytes())?)
+            }
+        }
+    }
+}
+
+/// 多签脚本的外层封装
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultisigWrapper {
+    Sh,
+    Wsh,
+    ShWsh,
+}
+
+#[derive(Debug, Clone)]
+pub enum Descriptor {
+    Pkh(DescriptorKey),
+    Wpkh(DescriptorKey),
+    ShWpkh(DescriptorKey),
+    Tr(DescriptorKey),
+    Multi {
+        wrapper: MultisigWrapper,
+        threshold: usize,
+        keys: Vec<DescriptorKey>,
+        sorted: bool,
+    },
+}
+
+/// 拆分 name(args)
+fn split_call(expression: &str) -> Result<(&str, &str)> {
+    let (name, rest) = expression
+        .split_once('(')
+        .ok_or_else(|| anyhow!("invalid descriptor expression {expression}"))?;
+    let args = rest
+        .strip_suffix(')')
+        .ok_or_else(|| anyhow!("missing ')' in {expression}"))?;
+    Ok((name, args))
+}
+
+/// 按最外层的逗号拆分参数
+fn split_args(args: &str) -> Vec<&str> {
+    let mut result = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, ch) in args.char_indices() {
+        match ch {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                result.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    result.push(&args[start..]);
+    result
+}
+
+/// 派生结果，P2SH有redeem script，P2WSH有witness script
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DescriptorAddress {
+    pub index: u32,
+    pub address: String,
+    pub script_pubkey: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redeem_script: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub witness_script: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DescriptorInfo {
+    /// 带checksum的描述符
+    pub descriptor: String,
+    pub checksum: String,
+    pub is_range: bool,
+    pub addresses: Vec<DescriptorAddress>,
+}
+
+impl Descriptor {
+    /// 解析描述符，带 #checksum 时校验
+    pub fn parse(descriptor: &str, network: Network) -> Result<(Self, String)> {
+        let descriptor = descriptor.trim();
+        let (body, checksum) = match descriptor.split_once('#') {
+            Some((body, checksum)) => (body, Some(checksum)),
+            None => (descriptor, None),
+        };
+        let expected = descriptor_checksum(body)?;
+        if let Some(checksum) = checksum {
+            if checksum != expected {
+                bail!("descriptor checksum mismatch, expect {expected}, got {checksum}");
+            }
+        }
+        Ok((Self::parse_expression(body, network)?, expected))
+    }
+
+    fn parse_expression(expression: &str, network: Network) -> Result<Self> {
+        let (name, args) = split_call(expression)?;
+        let key = |x_only| DescriptorKey::parse(args, network, x_only);
+        Ok(match name {
+            "pkh" => Descriptor::Pkh(key(false)?),
+            "wpkh" => Descriptor::Wpkh(key(false)?),
+            "tr" => {
+                if split_args(args).len() != 1 {
+                    bail!("tr() script path is not supported");
+                }
+                Descriptor::Tr(key(true)?)
+            }
+            "sh" => {
+                let (inner, inner_args) = split_call(args)?;
+                match inner {
+                    "wpkh" => Descriptor::ShWpkh(DescriptorKey::parse(inner_args, network, false)?),
+                    "wsh" => Self::parse_multi(inner_args, MultisigWrapper::ShWsh, network)?,
+                    _ => Self::parse_multi(args, MultisigWrapper::Sh, network)?,
+                }
+            }
+            "wsh" => Self::parse_multi(args, MultisigWrapper::Wsh, network)?,
+            _ => bail!("unsupported descriptor {name}()"),
+        })
+    }
+
+    fn parse_multi(expression: &str, wrapper: MultisigWrapper, network: Network) -> Result<Self> {
+        let (name, args) = split_call(expression)?;
+        let sorted = match name {
+            "multi" => false,
+            "sortedmulti" => true,
+            _ => bail!("unsupported script {name}(), expect multi or sortedmulti"),
+        };
+        let args = split_args(args);
+        let threshold = args[0]
+            .parse::<usize>()
+            .map_err(|e| anyhow!("invalid multisig threshold {}: {e}", args[0]))?;
+        let keys = args[1..]
+            .iter()
+            .map(|key| DescriptorKey::parse(key, network, false))
+            .collect::<Result<Vec<_>>>()?;
+        let max_keys = if wrapper == MultisigWrapper::Sh {
+            MAX_P2SH_MULTISIG_KEYS
+        } else {
+            MAX_MULTISIG_KEYS
+        };
+        if keys.is_empty() || keys.len() > max_keys {
+            bail!("multisig needs 1 to {max_keys} keys, got {}", keys.len());
+        }
+        if threshold == 0 || threshold > keys.len() {
+            bail!(
+                "multisig threshold {threshold} out of range 1..={}",
+                keys.len()
+            );
+        }
+        Ok(Descriptor::Multi {
+            wrapper,
+            threshold,
+            keys,
+            sorted,
+        })
+    }
+
+    pub fn is_ranged(&self) -> bool {
+        match self {
+            Descriptor::Pkh(key)
+            | Descriptor::Wpkh(key)
+            | Descriptor::ShWpkh(key)
+            | Descriptor::Tr(key) => key.is_ranged(),
+            Descriptor::Multi { keys, .. } => keys.iter().any(DescriptorKey::is_ranged),
+        }
+    }
+
+    /// 派生指定index的scriptPubKey及redeem/witness script
+    pub fn derive(&self, index: u32, network: Network) -> Result<DescriptorAddress> {
+        let (script_pubkey, redeem_script, witness_script) = match self {
+            Descriptor::Pkh(key) => (
+                public_key_to_script_pubkey(&key.derive(index)?, AddressType::P2pkh)?,
+                None,
+                None,
+            ),
+            Descriptor::Wpkh(key) => (
+                public_key_to_script_pubkey(&key.derive(index)?, AddressType::P2wpkh)?,
+                None,
+                None,
+            ),
+            Descriptor::ShWpkh(key) => {
+                let public_key = key.derive(index)?;
+                let redeem_script = segwit_script(0, &hash160(&public_key.serialize()));
+                (
+                    p2sh_script(&hash160(&redeem_script)),
+                    Some(redeem_script),
+                    None,
+                )
+            }
+            Descriptor::Tr(key) => (
+                public_key_to_script_pubkey(&key.derive(index)?, AddressType::P2tr)?,
+                None,
+                None,
+            ),
+            Descriptor::Multi {
+                wrapper,
+                threshold,
+                keys,
+                sorted,
+            } => {
+                let mut public_keys = keys
+                    .iter()
+                    .map(|key| Ok(key.derive(index)?.serialize()))
+                    .collect::<Result<Vec<_>>>()?;
+                if *sorted {
+                    public_keys.sort();
+                }
+                let script = multisig_script(*threshold, &public_keys);
+                let p2wsh = segwit_script(0, &Sha256::digest(&script));
+                match wrapper {
+                    MultisigWrapper::Sh => (p2sh_script(&hash160(&script)), Some(script), None),
+                    MultisigWrapper::Wsh => (p2wsh, None, Some(script)),
+                    MultisigWrapper::ShWsh => {
+                        (p2sh_script(&hash160(&p2wsh)), Some(p2wsh), Some(script))
+                    }
+                }
+            }
+        };
+        Ok(DescriptorAddress {
+            index,
+            address: script_to_address(&script_pubkey, network)
+                .ok_or_else(|| anyhow!("can not encode address"))?,
+            script_pubkey: u8_array_convert_string(&script_pubkey),
+            redeem_script: redeem_script.as_deref().map(u8_array_convert_string),
+            witness_script: witness_script.as_deref().map(u8_array_convert_string),
+        })
+    }
+}
+
+/// OP_m <pubkey>... OP_n OP_CHECKMULTISIG
+pub fn multisig_script(threshold: usize, public_keys: &[[u8; 33]]) -> Vec<u8> {
+    let mut script = vec![OP_1 + threshold as u8 - 1];
+    for public_key in public_keys {
+        script.extend(push_bytes(public_key));
+    }
+    script.extend([OP_1 + public_keys.len() as u8 - 1, OP_CHECKMULTISIG]);
+    script
+}
+
+pub fn derive_descriptor(
+    descriptor: String,
+    start: u32,
+    count: u32,
+    network: String,
+    output: Option<String>,
+) -> Result<DescriptorInfo> {
+    let network = Network::from_str(&network)?;
+    let (parsed, checksum) = Descriptor::parse(&descriptor, network)?;
+    let is_range = parsed.is_ranged();
+    // 非range描述符只有一个地址
+    let indexes = if is_range {
+        start..start.saturating_add(count)
+    } else {
+        0..1
+    };
+    let addresses = indexes
+        .map(|index| parsed.derive(index, network))
+        .collect::<Result<Vec<_>>>()?;
+    let body = descriptor.trim().split('#').next().unwrap_or_default();
+    let info = DescriptorInfo {
+        descriptor: format!("{body}#{checksum}"),
+        checksum,
+        is_range,
+        addresses,
+    };
+    write_output(output, &serde_json::to_string_pretty(&info)?)?;
+    Ok(info)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bip32::{derive_x_prv, mnemonic_to_x_prv};
+    use bip32::Prefix;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn account_x_pub(passphrase: &str, path: &str) -> String {
+        let root = mnemonic_to_x_prv(MNEMONIC.to_string(), passphrase.to_string());
+        derive_x_prv(&root, path)
+            .unwrap()
+            .public_key()
+            .to_string(Prefix::XPUB)
+    }
+
+    fn first_address(descriptor: &str) -> DescriptorAddress {
+        let (descriptor, _) = Descriptor::parse(descriptor, Network::Mainnet).unwrap();
+        descriptor.derive(0, Network::Mainnet).unwrap()
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(descriptor_checksum("raw(deadbeef)").unwrap(), "89f8spxm");
+        let descriptor = "pkh([d34db33f/44'/0'/0']xpub6ERApfZwUNrhLCkDtcHTcxd75RbzS1ed54G1LkBUHQVHQKqhMkhgbmJbZRkrgZw4koxb5JaHWkY4ALHY2grBGRjaDMzQLcgJvLJuZZvRcEL/1/*)";
+        let (_, checksum) =
+            Descriptor::parse(&format!("{descriptor}#ml40v0wf"), Network::Mainnet).unwrap();
+        assert_eq!(checksum, "ml40v0wf");
+        assert!(Descriptor::parse(&format!("{descriptor}#ml40v0wg"), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_single_key() {
+        let x_pub = account_x_pub("", "m/84'/0'/0'");
+        let (descriptor, _) = Descriptor::parse(
+            &format!("wpkh([73c5da0a/84'/0'/0']{x_pub}/0/*)"),
+            Network::Mainnet,
+        )
+        .unwrap();
+        assert!(descriptor.is_ranged());
+        assert_eq!(
+            descriptor.derive(0, Network::Mainnet).unwrap().address,
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            first_address(&format!("wpkh({x_pub}/1/*)")).address,
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+        let x_pub = account_x_pub("", "m/44'/0'/0'");
+        assert_eq!(
+            first_address(&format!("pkh({x_pub}/0/*)")).address,
+            "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+        );
+        let x_pub = account_x_pub("", "m/49'/0'/0'");
+        let address = first_address(&format!("sh(wpkh({x_pub}/0/*))"));
+        assert_eq!(address.address, "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf");
+        assert!(address.redeem_script.unwrap().starts_with("0014"));
+        let x_pub = account_x_pub("", "m/86'/0'/0'");
+        assert_eq!(
+            first_address(&format!("tr({x_pub}/0/*)")).address,
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+
+        assert!(Descriptor::parse(&format!("wpkh({x_pub}/0'/*)"), Network::Mainnet).is_err());
+        assert!(Descriptor::parse(&format!("wpkh({x_pub}/*/0)"), Network::Mainnet).is_err());
+        assert!(Descriptor::parse(&format!("wpkh({x_pub}/0/*)"), Network::Testnet).is_err());
+        assert!(Descriptor::parse(&format!("combo({x_pub})"), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_multisig() {
+        let a = account_x_pub("", "m/48'/0'/0'/2'");
+        let b = account_x_pub("TREZOR", "m/48'/0'/0'/2'");
+        let sorted = first_address(&format!("wsh(sortedmulti(2,{a}/0/*,{b}/0/*))"));
+        let reversed = first_address(&format!("wsh(sortedmulti(2,{b}/0/*,{a}/0/*))"));
+        assert_eq!(sorted, reversed);
+        assert!(sorted.address.starts_with("bc1q"));
+        assert!(sorted.redeem_script.is_none());
+        let witness_script = sorted.witness_script.unwrap();
+        assert!(witness_script.starts_with("5221") && witness_script.ends_with("52ae"));
+        assert_eq!(
+            sorted.script_pubkey,
+            u8_array_convert_string(&segwit_script(
+                0,
+                &Sha256::digest(hex::decode(&witness_script).unwrap())
+            ))
+        );
+
+        let nested = first_address(&format!("sh(wsh(sortedmulti(2,{a}/0/*,{b}/0/*)))"));
+        assert!(nested.address.starts_with('3'));
+        assert_eq!(nested.redeem_script.unwrap(), sorted.script_pubkey);
+        assert_eq!(nested.witness_script.unwrap(), witness_script);
+
+        let legacy = first_address(&format!("sh(multi(1,{a}/0/*,{b}/0/*))"));
+        assert!(legacy.redeem_script.unwrap().starts_with("5121"));
+        assert!(legacy.witness_script.is_none());
+
+        assert!(
+            Descriptor::parse(&format!("wsh(multi(3,{a}/0/*,{b}/0/*))"), Network::Mainnet).is_err()
+        );
+        assert!(Descriptor::parse(&format!("wsh(multi(0,{a}/0/*))"), Network::Mainnet).is_err());
+    }
+
+    #[test]
+    fn test_multisig_vector() {
+        // BIP-67 测试向量1，sortedmulti按公钥排序后的脚本和P2SH地址
+        let keys = "02ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f8,02fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f";
+        let script = "522102fe6f0a5a297eb38c391581c4413e084773ea23954d93f7753db7dc0adc188b2f2102ff12471208c14bd580709cb2358d98975247d8765f92bc25eab3b2763ed605f852ae";
+        let legacy = first_address(&format!("sh(sortedmulti(2,{keys}))"));
+        assert_eq!(legacy.address, "39bgKC7RFbpoCRbtD5KEdkYKtNyhpsNa3Z");
+        assert_eq!(legacy.redeem_script.as_deref(), Some(script));
+        assert!(legacy.witness_script.is_none());
+
+        let segwit = first_address(&format!("wsh(sortedmulti(2,{keys}))"));
+        assert_eq!(
+            segwit.address,
+            "bc1qknwt9mhqpd7hrjrvpqz57zjqk28xlp2h90te6v22en0m3uctnams3pq5ce"
+        );
+        assert_eq!(segwit.witness_script.as_deref(), Some(script));
+        assert_eq!(
+            segwit.script_pubkey,
+            "0020b4dcb2eee00b7d71c86c08054f0a40b28e6f85572bd79d314accdfb8f30b9f77"
+        );
+        // multi保持给定顺序，与排序后的脚本不同
+        let unsorted = first_address(&format!("wsh(multi(2,{keys}))"));
+        assert_ne!(unsorted.witness_script.as_deref(), Some(script));
+    }
+}
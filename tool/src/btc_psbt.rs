@@ -0,0 +1,1205 @@
+use anyhow::{anyhow, bail, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bip32::XPrv;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use tracing::{info, warn};
+
+use crate::btc::{hash160, taproot_tweak_key, Network};
+use crate::btc_build::{derive_key, ecdsa_signature, schnorr_signature};
+use crate::btc_script::{
+    classify, p2pkh_script, parse_multisig, push_bytes, script_to_address, segwit_script,
+    ScriptTemplate, OP_0,
+};
+use crate::btc_tx::{
+    write_var_bytes, write_var_int, DecodedTransaction, Reader, Transaction, TxOut, SIGHASH_ALL,
+    SIGHASH_DEFAULT,
+};
+use crate::encrypt_decrypt::{read_input, write_output};
+use crate::output::Output;
+use crate::util::u8_array_convert_string;
+
+const PSBT_MAGIC: &[u8] = b"psbt\xff";
+const HARDENED: u32 = 0x8000_0000;
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_REDEEM_SCRIPT: u8 = 0x04;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+const PSBT_IN_BIP32_DERIVATION: u8 = 0x06;
+const PSBT_IN_FINAL_SCRIPTSIG: u8 = 0x07;
+const PSBT_IN_FINAL_SCRIPTWITNESS: u8 = 0x08;
+const PSBT_IN_TAP_KEY_SIG: u8 = 0x13;
+const PSBT_IN_TAP_BIP32_DERIVATION: u8 = 0x16;
+const PSBT_IN_TAP_INTERNAL_KEY: u8 = 0x17;
+const PSBT_IN_TAP_MERKLE_ROOT: u8 = 0x18;
+
+const PSBT_OUT_BIP32_DERIVATION: u8 = 0x02;
+const PSBT_OUT_TAP_BIP32_DERIVATION: u8 = 0x07;
+
+/// finalize后保留的输入字段，其余已知字段会被清除
+const FINALIZED_KEEP: [u8; 4] = [
+    PSBT_IN_NON_WITNESS_UTXO,
+    PSBT_IN_WITNESS_UTXO,
+    PSBT_IN_FINAL_SCRIPTSIG,
+    PSBT_IN_FINAL_SCRIPTWITNESS,
+];
+
+/// key为 类型 + key data，按key排序保证序列化结果稳定
+pub type PsbtMap = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// BIP-174 version 0 PSBT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Psbt {
+    pub global: PsbtMap,
+    pub inputs: Vec<PsbtMap>,
+    pub outputs: Vec<PsbtMap>,
+    pub tx: Transaction,
+}
+
+/// BIP-32派生信息：主密钥指纹 + 路径
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySource {
+    pub fingerprint: [u8; 4],
+    pub path: Vec<u32>,
+}
+
+impl KeySource {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.len() < 4 || !data.len().is_multiple_of(4) {
+            bail!("invalid bip32 derivation length {}", data.len());
+        }
+        let mut chunks = data
+            .chunks_exact(4)
+            .map(|chunk| [chunk[0], chunk[1], chunk[2], chunk[3]]);
+        let fingerprint = chunks.next().unwrap_or_default();
+        Ok(KeySource {
+            fingerprint,
+            path: chunks.map(u32::from_le_bytes).collect(),
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = self.fingerprint.to_vec();
+        for index in &self.path {
+            buffer.extend_from_slice(&index.to_le_bytes());
+        }
+        buffer
+    }
+
+    pub fn path_string(&self) -> String {
+        let mut path = "m".to_string();
+        for index in &self.path {
+            if index & HARDENED != 0 {
+                path.push_str(&format!("/{}'", index & !HARDENED));
+            } else {
+                path.push_str(&format!("/{index}"));
+            }
+        }
+        path
+    }
+}
+
+/// 输入的花费方式，由UTXO的scriptPubKey和redeem/witness script确定
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpendType {
+    P2pkh,
+    P2wpkh,
+    P2shP2wpkh,
+    P2sh(Vec<u8>),
+    P2wsh(Vec<u8>),
+    P2shP2wsh(Vec<u8>),
+    P2tr,
+    Unknown,
+}
+
+impl SpendType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SpendType::P2pkh => "p2pkh",
+            SpendType::P2wpkh => "p2wpkh",
+            SpendType::P2shP2wpkh => "p2sh-p2wpkh",
+            SpendType::P2sh(_) => "p2sh",
+            SpendType::P2wsh(_) => "p2wsh",
+            SpendType::P2shP2wsh(_) => "p2sh-p2wsh",
+            SpendType::P2tr => "p2tr",
+            SpendType::Unknown => "unknown",
+        }
+    }
+
+    /// redeem script或witness script
+    fn script(&self) -> Option<&[u8]> {
+        match self {
+            SpendType::P2sh(script) | SpendType::P2wsh(script) | SpendType::P2shP2wsh(script) => {
+                Some(script)
+            }
+            _ => None,
+        }
+    }
+
+    /// 需要的签名数量，只支持单签和多签脚本
+    pub fn required_signatures(&self) -> Option<usize> {
+        match self {
+            SpendType::Unknown => None,
+            SpendType::P2sh(_) | SpendType::P2wsh(_) | SpendType::P2shP2wsh(_) => {
+                parse_multisig(self.script()?).map(|(m, _)| m)
+            }
+            _ => Some(1),
+        }
+    }
+}
+
+fn read_map(reader: &mut Reader) -> Result<PsbtMap> {
+    let mut map = PsbtMap::new();
+    loop {
+        let key_len = reader.read_len()?;
+        if key_len == 0 {
+            return Ok(map);
+        }
+        let key = reader.read_bytes(key_len)?.to_vec();
+        let value = reader.read_var_bytes()?;
+        if map.insert(key.clone(), value).is_some() {
+            bail!("duplicate psbt key {}", u8_array_convert_string(&key));
+        }
+    }
+}
+
+fn write_map(buffer: &mut Vec<u8>, map: &PsbtMap) {
+    for (key, value) in map {
+        write_var_bytes(buffer, key);
+        write_var_bytes(buffer, value);
+    }
+    buffer.push(0x00);
+}
+
+/// 指定类型的所有字段，返回 (key data, value)
+fn entries(map: &PsbtMap, key_type: u8) -> impl Iterator<Item = (&[u8], &[u8])> {
+    map.range(vec![key_type]..)
+        .take_while(move |(key, _)| key[0] == key_type)
+        .map(|(key, value)| (&key[1..], value.as_slice()))
+}
+
+fn get(map: &PsbtMap, key_type: u8) -> Option<&[u8]> {
+    map.get(&vec![key_type]).map(Vec::as_slice)
+}
+
+/// taproot派生信息：叶子hash列表 + 派生信息，返回叶子数量
+fn parse_tap_key_source(value: &[u8]) -> Result<(usize, KeySource)> {
+    let mut reader = Reader::new(value);
+    let leaves = reader.read_len()?;
+    reader.read_bytes(leaves * 32)?;
+    Ok((leaves, KeySource::parse(reader.remaining())?))
+}
+
+fn serialize_witness(witness: &[Vec<u8>]) -> Vec<u8> {
+    let mut buffer = vec![];
+    write_var_int(&mut buffer, witness.len() as u64);
+    for item in witness {
+        write_var_bytes(&mut buffer, item);
+    }
+    buffer
+}
+
+fn parse_witness(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut reader = Reader::new(data);
+    let count = reader.read_len()?;
+    let witness = (0..count)
+        .map(|_| reader.read_var_bytes())
+        .collect::<Result<Vec<_>>>()?;
+    if !reader.is_empty() {
+        bail!("trailing bytes after final script witness");
+    }
+    Ok(witness)
+}
+
+impl Psbt {
+    /// 由未签名交易创建PSBT
+    pub fn new(tx: Transaction) -> Result<Self> {
+        if tx
+            .inputs
+            .iter()
+            .any(|input| !input.script_sig.is_empty() || !input.witness.is_empty())
+        {
+            bail!("psbt transaction must be unsigned");
+        }
+        let mut global = PsbtMap::new();
+        global.insert(vec![PSBT_GLOBAL_UNSIGNED_TX], tx.serialize());
+        Ok(Psbt {
+            global,
+            inputs: vec![PsbtMap::new(); tx.inputs.len()],
+            outputs: vec![PsbtMap::new(); tx.outputs.len()],
+            tx,
+        })
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let Some(data) = data.strip_prefix(PSBT_MAGIC) else {
+            bail!("invalid psbt magic");
+        };
+        let mut reader = Reader::new(data);
+        let global = read_map(&mut reader)?;
+        let tx = get(&global, PSBT_GLOBAL_UNSIGNED_TX)
+            .ok_or_else(|| anyhow!("psbt missing unsigned transaction"))?;
+        let tx = Transaction::parse(tx)?;
+        if tx.has_witness() || tx.inputs.iter().any(|input| !input.script_sig.is_empty()) {
+            bail!("psbt unsigned transaction has signatures");
+        }
+        let inputs = (0..tx.inputs.len())
+            .map(|_| read_map(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+        let outputs = (0..tx.outputs.len())
+            .map(|_| read_map(&mut reader))
+            .collect::<Result<Vec<_>>>()?;
+        if !reader.is_empty() {
+            bail!("trailing bytes after psbt");
+        }
+        Ok(Psbt {
+            global,
+            inputs,
+            outputs,
+            tx,
+        })
+    }
+
+    pub fn from_base64(data: &str) -> Result<Self> {
+        let data = STANDARD
+            .decode(data.trim())
+            .map_err(|e| anyhow!("invalid base64 psbt: {e}"))?;
+        Self::parse(&data)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = PSBT_MAGIC.to_vec();
+        write_map(&mut buffer, &self.global);
+        for map in self.inputs.iter().chain(&self.outputs) {
+            write_map(&mut buffer, map);
+        }
+        buffer
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.serialize())
+    }
+
+    /// 输入花费的输出，优先使用witness utxo，non-witness utxo需要与outpoint的txid一致
+    pub fn utxo(&self, index: usize) -> Result<Option<TxOut>> {
+        let input = &self.inputs[index];
+        if let Some(utxo) = get(input, PSBT_IN_WITNESS_UTXO) {
+            return Ok(Some(TxOut::parse(utxo)?));
+        }
+        let Some(prev_tx) = get(input, PSBT_IN_NON_WITNESS_UTXO) else {
+            return Ok(None);
+        };
+        let prev_tx = Transaction::parse(prev_tx)?;
+        let outpoint = &self.tx.inputs[index].previous_output;
+        let mut txid = outpoint.txid;
+        txid.reverse();
+        if prev_tx.txid() != u8_array_convert_string(&txid) {
+            bail!("input {index} non-witness utxo does not match outpoint");
+        }
+        prev_tx
+            .outputs
+            .get(outpoint.vout as usize)
+            .cloned()
+            .map(Some)
+            .ok_or_else(|| anyhow!("input {index} vout {} out of range", outpoint.vout))
+    }
+
+    pub fn spend_type(&self, index: usize) -> Result<SpendType> {
+        let Some(utxo) = self.utxo(index)? else {
+            return Ok(SpendType::Unknown);
+        };
+        let input = &self.inputs[index];
+        let witness_script = get(input, PSBT_IN_WITNESS_SCRIPT).map(<[u8]>::to_vec);
+        let wsh = |script: &Vec<u8>| segwit_script(0, &Sha256::digest(script));
+        Ok(match classify(&utxo.script_pubkey) {
+            ScriptTemplate::P2pkh => SpendType::P2pkh,
+            ScriptTemplate::P2wpkh => SpendType::P2wpkh,
+            ScriptTemplate::P2tr => SpendType::P2tr,
+            ScriptTemplate::P2wsh => match witness_script {
+                Some(script) if wsh(&script) == utxo.script_pubkey => SpendType::P2wsh(script),
+                Some(_) => bail!("input {index} witness script does not match utxo"),
+                None => SpendType::Unknown,
+            },
+            ScriptTemplate::P2sh => {
+                let Some(redeem_script) = get(input, PSBT_IN_REDEEM_SCRIPT) else {
+                    return Ok(SpendType::Unknown);
+                };
+                if utxo.script_pubkey[2..22] != hash160(redeem_script) {
+                    bail!("input {index} redeem script does not match utxo");
+                }
+                match (classify(redeem_script), witness_script) {
+                    (ScriptTemplate::P2wpkh, _) => SpendType::P2shP2wpkh,
+                    (ScriptTemplate::P2wsh, Some(script)) if wsh(&script) == redeem_script => {
+                        SpendType::P2shP2wsh(script)
+                    }
+                    (ScriptTemplate::P2wsh, Some(_)) => {
+                        bail!("input {index} witness script does not match redeem script")
+                    }
+                    (ScriptTemplate::P2wsh, None) => SpendType::Unknown,
+                    _ => SpendType::P2sh(redeem_script.to_vec()),
+                }
+            }
+            _ => SpendType::Unknown,
+        })
+    }
+
+    pub fn is_finalized(&self, index: usize) -> bool {
+        let input = &self.inputs[index];
+        get(input, PSBT_IN_FINAL_SCRIPTSIG).is_some()
+            || get(input, PSBT_IN_FINAL_SCRIPTWITNESS).is_some()
+    }
+
+    /// 已有的签名数量，taproot只统计key path签名
+    pub fn signature_count(&self, index: usize) -> usize {
+        let input = &self.inputs[index];
+        entries(input, PSBT_IN_PARTIAL_SIG).count()
+            + usize::from(get(input, PSBT_IN_TAP_KEY_SIG).is_some())
+    }
+
+    fn check_sighash_type(&self, index: usize, spend_type: &SpendType) -> Result<()> {
+        let Some(sighash_type) = get(&self.inputs[index], PSBT_IN_SIGHASH_TYPE) else {
+            return Ok(());
+        };
+        let sighash_type = u32::from_le_bytes(
+            sighash_type
+                .try_into()
+                .map_err(|_| anyhow!("input {index} invalid sighash type"))?,
+        );
+        // taproot只支持SIGHASH_DEFAULT，签名时不追加sighash字节
+        let supported = match spend_type {
+            SpendType::P2tr => sighash_type == u32::from(SIGHASH_DEFAULT),
+            _ => sighash_type == SIGHASH_ALL,
+        };
+        if !supported {
+            bail!("input {index} sighash type {sighash_type:#x} is not supported");
+        }
+        Ok(())
+    }
+
+    fn prevouts(&self) -> Result<Vec<TxOut>> {
+        (0..self.inputs.len())
+            .map(|index| {
+                self.utxo(index)?
+                    .ok_or_else(|| anyhow!("taproot signing needs utxo of input {index}"))
+            })
+            .collect()
+    }
+
+    /// 用根扩展私钥签名，按BIP-32派生信息中的指纹匹配公钥，返回新增的签名数量
+    pub fn sign(&mut self, root: &XPrv) -> Result<usize> {
+        let fingerprint = root.public_key().fingerprint();
+        let mut signed = 0;
+        for index in 0..self.inputs.len() {
+            if self.is_finalized(index) {
+                continue;
+            }
+            let spend_type = self.spend_type(index)?;
+            if spend_type == SpendType::Unknown {
+                continue;
+            }
+            self.check_sighash_type(index, &spend_type)?;
+            signed += if spend_type == SpendType::P2tr {
+                self.sign_taproot(index, root, fingerprint)?
+            } else {
+                self.sign_ecdsa(index, &spend_type, root, fingerprint)?
+            };
+        }
+        Ok(signed)
+    }
+
+    fn sign_ecdsa(
+        &mut self,
+        index: usize,
+        spend_type: &SpendType,
+        root: &XPrv,
+        fingerprint: [u8; 4],
+    ) -> Result<usize> {
+        let utxo = self
+            .utxo(index)?
+            .ok_or_else(|| anyhow!("input {index} missing utxo"))?;
+        let input = &self.inputs[index];
+        let mut signatures = vec![];
+        for (public_key, source) in entries(input, PSBT_IN_BIP32_DERIVATION) {
+            let source = KeySource::parse(source)?;
+            let sig_key = [&[PSBT_IN_PARTIAL_SIG][..], public_key].concat();
+            if source.fingerprint != fingerprint || input.contains_key(&sig_key) {
+                continue;
+            }
+            let (secret_key, derived) = derive_key(root, &source.path_string())?;
+            if derived.serialize()[..] != *public_key {
+                warn!(
+                    "input {index} key {} does not match path {}",
+                    u8_array_convert_string(public_key),
+                    source.path_string()
+                );
+                continue;
+            }
+            if !self.spends_with_key(index, spend_type, &utxo, public_key) {
+                bail!(
+                    "input {index} key {} does not match utxo script",
+                    u8_array_convert_string(public_key)
+                );
+            }
+            let sighash = match spend_type {
+                SpendType::P2pkh => self.tx.legacy_sighash(index, &utxo.script_pubkey),
+                SpendType::P2sh(script) => self.tx.legacy_sighash(index, script),
+                SpendType::P2wpkh | SpendType::P2shP2wpkh => self.tx.segwit_v0_sighash(
+                    index,
+                    &p2pkh_script(&hash160(public_key)),
+                    utxo.value,
+                ),
+                SpendType::P2wsh(script) | SpendType::P2shP2wsh(script) => {
+                    self.tx.segwit_v0_sighash(index, script, utxo.value)
+                }
+                SpendType::P2tr | SpendType::Unknown => continue,
+            };
+            signatures.push((sig_key, ecdsa_signature(&secret_key, &sighash)?));
+        }
+        let count = signatures.len();
+        self.inputs[index].extend(signatures);
+        Ok(count)
+    }
+
+    /// 公钥能否花费该输入，单签比较公钥hash与utxo或redeem script，多签要求公钥在脚本中
+    fn spends_with_key(
+        &self,
+        index: usize,
+        spend_type: &SpendType,
+        utxo: &TxOut,
+        public_key: &[u8],
+    ) -> bool {
+        let pubkey_hash = hash160(public_key);
+        match spend_type {
+            SpendType::P2pkh => utxo.script_pubkey == p2pkh_script(&pubkey_hash),
+            SpendType::P2wpkh => utxo.script_pubkey == segwit_script(0, &pubkey_hash),
+            SpendType::P2shP2wpkh => {
+                get(&self.inputs[index], PSBT_IN_REDEEM_SCRIPT)
+                    == Some(&segwit_script(0, &pubkey_hash)[..])
+            }
+            SpendType::P2sh(script) | SpendType::P2wsh(script) | SpendType::P2shP2wsh(script) => {
+                parse_multisig(script)
+                    .is_some_and(|(_, keys)| keys.iter().any(|key| key == public_key))
+            }
+            SpendType::P2tr | SpendType::Unknown => false,
+        }
+    }
+
+    fn sign_taproot(&mut self, index: usize, root: &XPrv, fingerprint: [u8; 4]) -> Result<usize> {
+        let input = &self.inputs[index];
+        if get(input, PSBT_IN_TAP_KEY_SIG).is_some() {
+            return Ok(0);
+        }
+        let merkle_root = get(input, PSBT_IN_TAP_MERKLE_ROOT).map(<[u8]>::to_vec);
+        let mut signer = None;
+        for (x_only_key, value) in entries(input, PSBT_IN_TAP_BIP32_DERIVATION) {
+            // key path只使用没有叶子hash的公钥
+            let (leaves, source) = parse_tap_key_source(value)?;
+            if leaves != 0 || source.fingerprint != fingerprint {
+                continue;
+            }
+            if let Some(internal_key) = get(input, PSBT_IN_TAP_INTERNAL_KEY) {
+                if internal_key != x_only_key {
+                    continue;
+                }
+            }
+            let (key, derived) = derive_key(root, &source.path_string())?;
+            if derived.x_only_public_key().0.serialize()[..] != *x_only_key {
+                warn!(
+                    "input {index} key {} does not match path {}",
+                    u8_array_convert_string(x_only_key),
+                    source.path_string()
+                );
+                continue;
+            }
+            signer = Some((key, derived.x_only_public_key().0));
+            break;
+        }
+        let Some((secret_key, internal_key)) = signer else {
+            return Ok(0);
+        };
+        // 内部公钥经merkle root调整后必须与utxo中的输出公钥一致
+        let utxo = self
+            .utxo(index)?
+            .ok_or_else(|| anyhow!("input {index} missing utxo"))?;
+        let output_key = taproot_tweak_key(&internal_key, merkle_root.as_deref())?;
+        if utxo.script_pubkey != segwit_script(1, &output_key.serialize()) {
+            bail!("input {index} taproot output key does not match utxo script pubkey");
+        }
+        let sighash = self
+            .tx
+            .taproot_key_spend_sighash(index, &self.prevouts()?)?;
+        let signature = schnorr_signature(&secret_key, merkle_root.as_deref(), &sighash)?;
+        self.inputs[index].insert(vec![PSBT_IN_TAP_KEY_SIG], signature);
+        Ok(1)
+    }
+
+    /// 合并另一个PSBT的字段，两者的未签名交易必须相同
+    pub fn combine(&mut self, other: Psbt) -> Result<()> {
+        if self.tx != other.tx {
+            bail!("can not combine psbt of different transactions");
+        }
+        self.global.extend(other.global);
+        for (input, other) in self.inputs.iter_mut().zip(other.inputs) {
+            input.extend(other);
+        }
+        for (output, other) in self.outputs.iter_mut().zip(other.outputs) {
+            output.extend(other);
+        }
+        Ok(())
+    }
+
+    /// 按公钥在脚本中的顺序取前m个签名
+    fn multisig_signatures(&self, index: usize, script: &[u8]) -> Result<Vec<Vec<u8>>> {
+        let (m, keys) = parse_multisig(script)
+            .ok_or_else(|| anyhow!("input {index} script is not multisig"))?;
+        let signatures: Vec<Vec<u8>> = keys
+            .iter()
+            .filter_map(|key| {
+                let sig_key = [&[PSBT_IN_PARTIAL_SIG][..], key].concat();
+                self.inputs[index].get(&sig_key).cloned()
+            })
+            .take(m)
+            .collect();
+        if signatures.len() < m {
+            bail!("input {index} has {} of {m} signatures", signatures.len());
+        }
+        Ok(signatures)
+    }
+
+    /// 单签输入取公钥与utxo匹配的签名，忽略其他公钥的签名
+    fn single_signature(&self, index: usize, spend_type: &SpendType) -> Result<(Vec<u8>, Vec<u8>)> {
+        let utxo = self
+            .utxo(index)?
+            .ok_or_else(|| anyhow!("input {index} missing utxo"))?;
+        entries(&self.inputs[index], PSBT_IN_PARTIAL_SIG)
+            .find(|(key, _)| self.spends_with_key(index, spend_type, &utxo, key))
+            .map(|(key, signature)| (signature.to_vec(), key.to_vec()))
+            .ok_or_else(|| anyhow!("input {index} has no signature of the utxo key"))
+    }
+
+    /// 由签名生成最终的scriptSig和witness，并清除其他字段
+    pub fn finalize_input(&mut self, index: usize) -> Result<()> {
+        if self.is_finalized(index) {
+            return Ok(());
+        }
+        let spend_type = self.spend_type(index)?;
+        let (script_sig, witness) = match &spend_type {
+            SpendType::P2pkh => {
+                let (signature, key) = self.single_signature(index, &spend_type)?;
+                ([push_bytes(&signature), push_bytes(&key)].concat(), vec![])
+            }
+            SpendType::P2wpkh => {
+                let (signature, key) = self.single_signature(index, &spend_type)?;
+                (vec![], vec![signature, key])
+            }
+            SpendType::P2shP2wpkh => {
+                let (signature, key) = self.single_signature(index, &spend_type)?;
+                let redeem_script = segwit_script(0, &hash160(&key));
+                (push_bytes(&redeem_script), vec![signature, key])
+            }
+            SpendType::P2sh(script) => {
+                let mut script_sig = vec![OP_0];
+                for signature in self.multisig_signatures(index, script)? {
+                    script_sig.extend(push_bytes(&signature));
+                }
+                script_sig.extend(push_bytes(script));
+                (script_sig, vec![])
+            }
+            SpendType::P2wsh(script) | SpendType::P2shP2wsh(script) => {
+                let mut witness = vec![vec![]];
+                witness.extend(self.multisig_signatures(index, script)?);
+                witness.push(script.clone());
+                let script_sig = match spend_type {
+                    SpendType::P2shP2wsh(_) => {
+                        push_bytes(&segwit_script(0, &Sha256::digest(script)))
+                    }
+                    _ => vec![],
+                };
+                (script_sig, witness)
+            }
+            SpendType::P2tr => {
+                let signature = get(&self.inputs[index], PSBT_IN_TAP_KEY_SIG)
+                    .ok_or_else(|| anyhow!("input {index} has no taproot key signature"))?;
+                (vec![], vec![signature.to_vec()])
+            }
+            SpendType::Unknown => bail!("input {index} has unknown utxo or script"),
+        };
+        let input = &mut self.inputs[index];
+        input.retain(|key, _| key[0] > PSBT_IN_TAP_MERKLE_ROOT || FINALIZED_KEEP.contains(&key[0]));
+        if !script_sig.is_empty() {
+            input.insert(vec![PSBT_IN_FINAL_SCRIPTSIG], script_sig);
+        }
+        if !witness.is_empty() {
+            input.insert(
+                vec![PSBT_IN_FINAL_SCRIPTWITNESS],
+                serialize_witness(&witness),
+            );
+        }
+        Ok(())
+    }
+
+    /// 返回无法finalize的输入及原因
+    pub fn finalize(&mut self) -> Vec<(usize, String)> {
+        (0..self.inputs.len())
+            .filter_map(|index| {
+                self.finalize_input(index)
+                    .err()
+                    .map(|e| (index, e.to_string()))
+            })
+            .collect()
+    }
+
+    /// 所有输入finalize后提取最终交易
+    pub fn extract(&self) -> Result<Transaction> {
+        let mut tx = self.tx.clone();
+        for (index, input) in self.inputs.iter().enumerate() {
+            if !self.is_finalized(index) {
+                bail!("input {index} is not finalized");
+            }
+            let tx_in = &mut tx.inputs[index];
+            tx_in.script_sig = get(input, PSBT_IN_FINAL_SCRIPTSIG)
+                .unwrap_or_default()
+                .to_vec();
+            if let Some(witness) = get(input, PSBT_IN_FINAL_SCRIPTWITNESS) {
+                tx_in.witness = parse_witness(witness)?;
+            }
+        }
+        Ok(tx)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bip32Derivation {
+    pub public_key: String,
+    pub fingerprint: String,
+    pub path: String,
+}
+
+fn derivations(map: &PsbtMap, key_type: u8, tap_key_type: u8) -> Result<Vec<Bip32Derivation>> {
+    let mut result = vec![];
+    for (public_key, value) in entries(map, key_type) {
+        result.push((public_key, KeySource::parse(value)?));
+    }
+    for (x_only_key, value) in entries(map, tap_key_type) {
+        result.push((x_only_key, parse_tap_key_source(value)?.1));
+    }
+    Ok(result
+        .into_iter()
+        .map(|(public_key, source)| Bip32Derivation {
+            public_key: u8_array_convert_string(public_key),
+            fingerprint: u8_array_convert_string(&source.fingerprint),
+            path: source.path_string(),
+        })
+        .collect())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedPsbtInput {
+    pub index: usize,
+    pub amount: Option<u64>,
+    pub address: Option<String>,
+    pub spend_type: String,
+    pub signatures: usize,
+    pub required_signatures: Option<usize>,
+    pub finalized: bool,
+    pub bip32_derivation: Vec<Bip32Derivation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedPsbtOutput {
+    pub index: usize,
+    pub amount: u64,
+    pub address: Option<String>,
+    pub bip32_derivation: Vec<Bip32Derivation>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedPsbt {
+    pub tx: DecodedTransaction,
+    pub inputs: Vec<DecodedPsbtInput>,
+    pub outputs: Vec<DecodedPsbtOutput>,
+    /// 所有输入的UTXO已知时才能计算
+    pub fee: Option<u64>,
+    /// 缺少签名的输入
+    pub missing_signatures: Vec<usize>,
+    pub complete: bool,
+}
+
+impl DecodedPsbt {
+    pub fn new(psbt: &Psbt, network: Network) -> Result<Self> {
+        let mut inputs = vec![];
+        for (index, map) in psbt.inputs.iter().enumerate() {
+            let utxo = psbt.utxo(index)?;
+            let spend_type = psbt.spend_type(index)?;
+            inputs.push(DecodedPsbtInput {
+                index,
+                amount: utxo.as_ref().map(|utxo| utxo.value),
+                address: utxo
+                    .as_ref()
+                    .and_then(|utxo| script_to_address(&utxo.script_pubkey, network)),
+                spend_type: spend_type.name().to_string(),
+                signatures: psbt.signature_count(index),
+                required_signatures: spend_type.required_signatures(),
+                finalized: psbt.is_finalized(index),
+                bip32_derivation: derivations(
+                    map,
+                    PSBT_IN_BIP32_DERIVATION,
+                    PSBT_IN_TAP_BIP32_DERIVATION,
+                )?,
+            });
+        }
+        let mut outputs = vec![];
+        for (index, (map, output)) in psbt.outputs.iter().zip(&psbt.tx.outputs).enumerate() {
+            outputs.push(DecodedPsbtOutput {
+                index,
+                amount: output.value,
+                address: script_to_address(&output.script_pubkey, network),
+                bip32_derivation: derivations(
+                    map,
+                    PSBT_OUT_BIP32_DERIVATION,
+                    PSBT_OUT_TAP_BIP32_DERIVATION,
+                )?,
+            });
+        }
+        let input_amount: Option<u64> = inputs.iter().map(|input| input.amount).sum();
+        let output_amount: u64 = psbt.tx.outputs.iter().map(|output| output.value).sum();
+        let missing_signatures: Vec<usize> = inputs
+            .iter()
+            .filter(|input| {
+                !input.finalized
+                    && input
+                        .required_signatures
+                        .is_none_or(|required| input.signatures < required)
+            })
+            .map(|input| input.index)
+            .collect();
+        Ok(DecodedPsbt {
+            tx: DecodedTransaction::new(&psbt.tx, network),
+            fee: input_amount.and_then(|amount| amount.checked_sub(output_amount)),
+            complete: inputs.iter().all(|input| input.finalized),
+            inputs,
+            outputs,
+            missing_signatures,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ExtractedTransaction {
+    pub txid: String,
+    pub raw_transaction: String,
+}
+
+fn read_psbt(input: &str) -> Result<Psbt> {
+    Psbt::from_base64(&read_input(input)?)
+}
+
+pub fn decode_psbt(input: String, network: String, output: Option<String>) -> Result<DecodedPsbt> {
+    let network = Network::from_str(&network)?;
+    let decoded = DecodedPsbt::new(&read_psbt(&input)?, network)?;
+    write_output(output, &serde_json::to_string_pretty(&decoded)?)?;
+    Ok(decoded)
+}
+
+pub fn sign_psbt(input: String, x_prv: String, output: Option<String>) -> Result<Output> {
+    let root =
+        XPrv::from_str(x_prv.trim()).map_err(|e| anyhow!("invalid extended private key: {e}"))?;
+    let mut psbt = read_psbt(&input)?;
+    let signed = psbt.sign(&root)?;
+    info!("added {signed} signatures");
+    report_missing(&psbt);
+    Output::artifact(output, "psbt", &psbt.to_base64())
+}
+
+pub fn combine_psbt(inputs: Vec<String>, output: Option<String>) -> Result<Output> {
+    let mut inputs = inputs.iter();
+    let first = inputs
+        .next()
+        .ok_or_else(|| anyhow!("at least one psbt is required."))?;
+    let mut psbt = read_psbt(first)?;
+    for input in inputs {
+        psbt.combine(read_psbt(input)?)?;
+    }
+    report_missing(&psbt);
+    Output::artifact(output, "psbt", &psbt.to_base64())
+}
+
+pub fn finalize_psbt(input: String, output: Option<String>) -> Result<Output> {
+    let mut psbt = read_psbt(&input)?;
+    for (index, reason) in psbt.finalize() {
+        warn!("input {index} not finalized: {reason}");
+    }
+    Output::artifact(output, "psbt", &psbt.to_base64())
+}
+
+pub fn extract_psbt(input: String, output: Option<String>) -> Result<Output> {
+    let tx = read_psbt(&input)?.extract()?;
+    let extracted = ExtractedTransaction {
+        txid: tx.txid(),
+        raw_transaction: u8_array_convert_string(&tx.serialize()),
+    };
+    Output::artifact(output, "transaction", &extracted)
+}
+
+fn report_missing(psbt: &Psbt) {
+    for index in 0..psbt.inputs.len() {
+        if psbt.is_finalized(index) {
+            continue;
+        }
+        let required = psbt
+            .spend_type(index)
+            .ok()
+            .and_then(|spend_type| spend_type.required_signatures());
+        let signatures = psbt.signature_count(index);
+        match required {
+            Some(required) if signatures >= required => {}
+            Some(required) => warn!("input {index} has {signatures} of {required} signatures"),
+            None => warn!("input {index} has unknown script, {signatures} signatures"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bip32::mnemonic_to_x_prv;
+    use crate::btc::{taproot_output_key, AddressType};
+    use crate::btc_script::{public_key_to_script_pubkey, OP_1, OP_CHECKMULTISIG};
+    use crate::btc_tx::{OutPoint, TxIn};
+    use bip32::DerivationPath;
+    use secp256k1::{ecdsa, schnorr, Message, PublicKey, XOnlyPublicKey, SECP256K1};
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn key_source(root: &XPrv, path: &str) -> KeySource {
+        KeySource {
+            fingerprint: root.public_key().fingerprint(),
+            path: DerivationPath::from_str(path)
+                .unwrap()
+                .iter()
+                .map(|child| child.into())
+                .collect(),
+        }
+    }
+
+    fn public_key(root: &XPrv, path: &str) -> PublicKey {
+        derive_key(root, path).unwrap().1
+    }
+
+    fn tx_in(byte: u8) -> TxIn {
+        TxIn {
+            previous_output: OutPoint {
+                txid: [byte; 32],
+                vout: 0,
+            },
+            script_sig: vec![],
+            sequence: 0xffff_fffd,
+            witness: vec![],
+        }
+    }
+
+    /// 输入0为 a、b 的2-of-2 P2WSH多签，输入1为a的P2TR
+    fn unsigned_psbt(a: &XPrv, b: &XPrv) -> Psbt {
+        let multisig_path = "m/48'/0'/0'/2'/0/0";
+        let taproot_path = "m/86'/0'/0'/0/0";
+        let keys = [public_key(a, multisig_path), public_key(b, multisig_path)];
+        let mut witness_script = vec![OP_1 + 1];
+        for key in &keys {
+            witness_script.extend(push_bytes(&key.serialize()));
+        }
+        witness_script.extend([OP_1 + 1, OP_CHECKMULTISIG]);
+        let taproot_key = public_key(a, taproot_path);
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![tx_in(1), tx_in(2)],
+            outputs: vec![TxOut {
+                value: 140_000,
+                script_pubkey: segwit_script(0, &[3; 20]),
+            }],
+            lock_time: 0,
+        };
+        let mut psbt = Psbt::new(tx).unwrap();
+        let input = &mut psbt.inputs[0];
+        let utxo = TxOut {
+            value: 100_000,
+            script_pubkey: segwit_script(0, &Sha256::digest(&witness_script)),
+        };
+        input.insert(vec![PSBT_IN_WITNESS_UTXO], utxo.serialize());
+        input.insert(vec![PSBT_IN_WITNESS_SCRIPT], witness_script);
+        for (root, key) in [a, b].into_iter().zip(keys) {
+            input.insert(
+                [&[PSBT_IN_BIP32_DERIVATION][..], &key.serialize()].concat(),
+                key_source(root, multisig_path).serialize(),
+            );
+        }
+        let input = &mut psbt.inputs[1];
+        let utxo = TxOut {
+            value: 50_000,
+            script_pubkey: public_key_to_script_pubkey(&taproot_key, AddressType::P2tr).unwrap(),
+        };
+        input.insert(vec![PSBT_IN_WITNESS_UTXO], utxo.serialize());
+        input.insert(
+            [
+                &[PSBT_IN_TAP_BIP32_DERIVATION][..],
+                &taproot_key.x_only_public_key().0.serialize(),
+            ]
+            .concat(),
+            [&[0x00][..], &key_source(a, taproot_path).serialize()].concat(),
+        );
+        psbt
+    }
+
+    #[test]
+    fn test_serialize() {
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let b = mnemonic_to_x_prv(MNEMONIC.to_string(), "TREZOR".to_string());
+        let psbt = unsigned_psbt(&a, &b);
+        let parsed = Psbt::from_base64(&psbt.to_base64()).unwrap();
+        assert_eq!(parsed, psbt);
+        assert!(psbt.to_base64().starts_with("cHNidP8B"));
+        assert_eq!(psbt.spend_type(0).unwrap().required_signatures(), Some(2));
+        assert_eq!(psbt.spend_type(1).unwrap(), SpendType::P2tr);
+
+        let source = KeySource::parse(&key_source(&a, "m/48'/0'/0'/2'/0/0").serialize()).unwrap();
+        assert_eq!(source.path_string(), "m/48'/0'/0'/2'/0/0");
+        assert_eq!(u8_array_convert_string(&source.fingerprint), "73c5da0a");
+
+        assert!(Psbt::parse(b"psbx\xff\x00").is_err());
+        let mut data = psbt.serialize();
+        data.push(0x00);
+        assert!(Psbt::parse(&data).is_err());
+    }
+
+    #[test]
+    fn test_sign_combine_finalize() {
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let b = mnemonic_to_x_prv(MNEMONIC.to_string(), "TREZOR".to_string());
+        let unsigned = unsigned_psbt(&a, &b);
+
+        let mut signed_a = unsigned.clone();
+        assert_eq!(signed_a.sign(&a).unwrap(), 2);
+        // 重复签名不会新增
+        assert_eq!(signed_a.sign(&a).unwrap(), 0);
+        let decoded = DecodedPsbt::new(&signed_a, Network::Mainnet).unwrap();
+        assert_eq!(decoded.missing_signatures, vec![0]);
+        assert_eq!(decoded.inputs[0].signatures, 1);
+        assert_eq!(decoded.inputs[0].bip32_derivation.len(), 2);
+        assert_eq!(decoded.fee, Some(10_000));
+        assert!(!decoded.complete);
+        assert!(signed_a.clone().finalize_input(0).is_err());
+
+        let mut signed_b = unsigned.clone();
+        assert_eq!(signed_b.sign(&b).unwrap(), 1);
+
+        let mut combined = Psbt::from_base64(&signed_a.to_base64()).unwrap();
+        combined.combine(signed_b).unwrap();
+        assert!(DecodedPsbt::new(&combined, Network::Mainnet)
+            .unwrap()
+            .missing_signatures
+            .is_empty());
+        assert!(combined.extract().is_err());
+        assert!(combined.finalize().is_empty());
+        assert_eq!(combined.inputs[0].len(), 2);
+        assert!(
+            DecodedPsbt::new(&combined, Network::Mainnet)
+                .unwrap()
+                .complete
+        );
+
+        let tx = combined.extract().unwrap();
+        let prevouts = unsigned.prevouts().unwrap();
+        let witness = &tx.inputs[0].witness;
+        assert_eq!(witness.len(), 4);
+        assert!(witness[0].is_empty());
+        let (_, keys) = parse_multisig(&witness[3]).unwrap();
+        let sighash = Message::from_slice(&unsigned.tx.segwit_v0_sighash(
+            0,
+            &witness[3],
+            prevouts[0].value,
+        ))
+        .unwrap();
+        for (signature, key) in witness[1..3].iter().zip(&keys) {
+            let signature = ecdsa::Signature::from_der(&signature[..signature.len() - 1]).unwrap();
+            let key = PublicKey::from_slice(key).unwrap();
+            SECP256K1.verify_ecdsa(&sighash, &signature, &key).unwrap();
+        }
+
+        let witness = &tx.inputs[1].witness;
+        assert_eq!(witness.len(), 1);
+        let sighash = unsigned.tx.taproot_key_spend_sighash(1, &prevouts).unwrap();
+        let output_key = taproot_output_key(&public_key(&a, "m/86'/0'/0'/0/0")).unwrap();
+        assert_eq!(
+            XOnlyPublicKey::from_slice(&prevouts[1].script_pubkey[2..]).unwrap(),
+            output_key
+        );
+        SECP256K1
+            .verify_schnorr(
+                &schnorr::Signature::from_slice(&witness[0]).unwrap(),
+                &Message::from_slice(&sighash).unwrap(),
+                &output_key,
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_sign_taproot_checks() {
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let unsigned = unsigned_psbt(&a, &a);
+        // taproot输入只接受SIGHASH_DEFAULT
+        for (sighash_type, ok) in [(0u32, true), (SIGHASH_ALL, false), (0x81, false)] {
+            let mut psbt = unsigned.clone();
+            psbt.inputs[1].insert(
+                vec![PSBT_IN_SIGHASH_TYPE],
+                sighash_type.to_le_bytes().to_vec(),
+            );
+            assert_eq!(psbt.sign(&a).is_ok(), ok);
+        }
+        // merkle root与utxo不一致时拒绝签名
+        let mut psbt = unsigned.clone();
+        psbt.inputs[1].insert(vec![PSBT_IN_TAP_MERKLE_ROOT], vec![1; 32]);
+        assert!(psbt
+            .sign(&a)
+            .unwrap_err()
+            .to_string()
+            .contains("output key"));
+        let mut psbt = unsigned;
+        let utxo = TxOut {
+            value: 50_000,
+            script_pubkey: segwit_script(1, &[2; 32]),
+        };
+        psbt.inputs[1].insert(vec![PSBT_IN_WITNESS_UTXO], utxo.serialize());
+        assert!(psbt.sign(&a).is_err());
+        assert!(get(&psbt.inputs[1], PSBT_IN_TAP_KEY_SIG).is_none());
+    }
+
+    #[test]
+    fn test_sign_ecdsa_key_checks() {
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let b = mnemonic_to_x_prv(MNEMONIC.to_string(), "TREZOR".to_string());
+        // 派生信息中的公钥不在多签脚本中时拒绝签名
+        let mut psbt = unsigned_psbt(&a, &b);
+        let other_path = "m/48'/0'/0'/2'/0/1";
+        psbt.inputs[0].insert(
+            [
+                &[PSBT_IN_BIP32_DERIVATION][..],
+                &public_key(&a, other_path).serialize(),
+            ]
+            .concat(),
+            key_source(&a, other_path).serialize(),
+        );
+        assert!(psbt
+            .sign(&a)
+            .unwrap_err()
+            .to_string()
+            .contains("does not match utxo script"));
+
+        // P2WPKH输入只能由utxo对应的公钥签名
+        let utxo_path = "m/84'/0'/0'/0/0";
+        let other_path = "m/84'/0'/0'/0/1";
+        let utxo_key = public_key(&a, utxo_path).serialize();
+        let other_key = public_key(&a, other_path).serialize();
+        let tx = Transaction {
+            version: 2,
+            inputs: vec![tx_in(3)],
+            outputs: vec![TxOut {
+                value: 90_000,
+                script_pubkey: segwit_script(0, &[3; 20]),
+            }],
+            lock_time: 0,
+        };
+        let mut unsigned = Psbt::new(tx).unwrap();
+        let utxo = TxOut {
+            value: 100_000,
+            script_pubkey: segwit_script(0, &hash160(&utxo_key)),
+        };
+        unsigned.inputs[0].insert(vec![PSBT_IN_WITNESS_UTXO], utxo.serialize());
+        let derivation = |key: &[u8], path: &str| {
+            (
+                [&[PSBT_IN_BIP32_DERIVATION][..], key].concat(),
+                key_source(&a, path).serialize(),
+            )
+        };
+        let mut psbt = unsigned.clone();
+        psbt.inputs[0].extend([derivation(&other_key, other_path)]);
+        assert!(psbt.sign(&a).is_err());
+        assert_eq!(psbt.signature_count(0), 0);
+
+        let mut psbt = unsigned;
+        psbt.inputs[0].extend([derivation(&utxo_key, utxo_path)]);
+        // 只有其他公钥的签名时无法finalize
+        let other_sig = [&[PSBT_IN_PARTIAL_SIG][..], &other_key].concat();
+        psbt.inputs[0].insert(other_sig, vec![0x30, 0x01]);
+        assert!(psbt.clone().finalize_input(0).is_err());
+        assert_eq!(psbt.sign(&a).unwrap(), 1);
+        psbt.finalize_input(0).unwrap();
+        let tx = psbt.extract().unwrap();
+        assert_eq!(tx.inputs[0].witness[1], utxo_key.to_vec());
+        assert_ne!(tx.inputs[0].witness[0], vec![0x30, 0x01]);
+    }
+
+    #[test]
+    fn test_combine_different_tx() {
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let mut psbt = unsigned_psbt(&a, &a);
+        let mut other = psbt.clone();
+        other.tx.lock_time = 1;
+        assert!(psbt.combine(other).is_err());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        use crate::output::{pipe, run_cli};
+        use bip32::Prefix;
+
+        // 每一步输出的base64 psbt都可以作为下一步的输入，extract的输出可以作为decode-tx的输入
+        let a = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        let b = mnemonic_to_x_prv(MNEMONIC.to_string(), "TREZOR".to_string());
+        let unsigned = pipe("unsigned.psbt", &unsigned_psbt(&a, &b).to_base64());
+        let mut signed = vec![];
+        for (name, root) in [("a", &a), ("b", &b)] {
+            let x_prv = root.to_string(Prefix::XPRV);
+            let psbt = run_cli(&["btc", "psbt", "sign", "-i", &unsigned, "-x", &x_prv]).unwrap();
+            assert!(Psbt::from_base64(&psbt).is_ok());
+            signed.push(pipe(&format!("signed-{name}.psbt"), &psbt));
+        }
+        let combined = run_cli(&[
+            "--output-format",
+            "json",
+            "btc",
+            "psbt",
+            "combine",
+            "-i",
+            &signed[0],
+            "-i",
+            &signed[1],
+        ])
+        .unwrap();
+        // json格式下psbt包装在psbt字段中
+        let combined = serde_json::from_str::<serde_json::Value>(&combined).unwrap();
+        let combined = pipe("combined.psbt", combined["psbt"].as_str().unwrap());
+        let finalized = run_cli(&["btc", "psbt", "finalize", "-i", &combined]).unwrap();
+        let finalized = pipe("finalized.psbt", &finalized);
+        let extracted = run_cli(&["btc", "psbt", "extract", "-i", &finalized]).unwrap();
+        let txid = serde_json::from_str::<serde_json::Value>(&extracted).unwrap()["txid"].clone();
+        let extracted = pipe("extracted.json", &extracted);
+        let decoded = run_cli(&[
+            "--output-format",
+            "json",
+            "btc",
+            "decode-tx",
+            "-f",
+            &extracted,
+        ])
+        .unwrap();
+        let decoded: serde_json::Value = serde_json::from_str(&decoded).unwrap();
+        assert_eq!(decoded["txid"], txid);
+        assert_eq!(decoded["vin"].as_array().unwrap().len(), 2);
+    }
+}
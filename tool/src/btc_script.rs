@@ -0,0 +1,776 @@
+use anyhow::{anyhow, bail, Result};
+use bech32::{FromBase32, Variant};
+use ethers::utils::hex;
+use secp256k1::PublicKey;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+use crate::btc::{
+    base58check_encode, double_sha256, hash160, segwit_address, taproot_output_key, AddressType,
+    Network,
+};
+use crate::btc_tx::parse_hex;
+use crate::encrypt_decrypt::write_output;
+use crate::util::u8_array_convert_string;
+
+pub const OP_0: u8 = 0x00;
+pub const OP_PUSHDATA1: u8 = 0x4c;
+pub const OP_PUSHDATA2: u8 = 0x4d;
+pub const OP_PUSHDATA4: u8 = 0x4e;
+pub const OP_1NEGATE: u8 = 0x4f;
+pub const OP_1: u8 = 0x51;
+pub const OP_16: u8 = 0x60;
+pub const OP_RETURN: u8 = 0x6a;
+pub const OP_DUP: u8 = 0x76;
+pub const OP_EQUAL: u8 = 0x87;
+pub const OP_EQUALVERIFY: u8 = 0x88;
+pub const OP_HASH160: u8 = 0xa9;
+pub const OP_CHECKSIG: u8 = 0xac;
+pub const OP_CHECKMULTISIG: u8 = 0xae;
+
+/// 操作码名称，0x4c-0xba
+const OPCODE_NAMES: [(u8, &str); 111] = [
+    (0x4c, "OP_PUSHDATA1"),
+    (0x4d, "OP_PUSHDATA2"),
+    (0x4e, "OP_PUSHDATA4"),
+    (0x4f, "OP_1NEGATE"),
+    (0x50, "OP_RESERVED"),
+    (0x51, "OP_1"),
+    (0x52, "OP_2"),
+    (0x53, "OP_3"),
+    (0x54, "OP_4"),
+    (0x55, "OP_5"),
+    (0x56, "OP_6"),
+    (0x57, "OP_7"),
+    (0x58, "OP_8"),
+    (0x59, "OP_9"),
+    (0x5a, "OP_10"),
+    (0x5b, "OP_11"),
+    (0x5c, "OP_12"),
+    (0x5d, "OP_13"),
+    (0x5e, "OP_14"),
+    (0x5f, "OP_15"),
+    (0x60, "OP_16"),
+    (0x61, "OP_NOP"),
+    (0x62, "OP_VER"),
+    (0x63, "OP_IF"),
+    (0x64, "OP_NOTIF"),
+    (0x65, "OP_VERIF"),
+    (0x66, "OP_VERNOTIF"),
+    (0x67, "OP_ELSE"),
+    (0x68, "OP_ENDIF"),
+    (0x69, "OP_VERIFY"),
+    (0x6a, "OP_RETURN"),
+    (0x6b, "OP_TOALTSTACK"),
+    (0x6c, "OP_FROMALTSTACK"),
+    (0x6d, "OP_2DROP"),
+    (0x6e, "OP_2DUP"),
+    (0x6f, "OP_3DUP"),
+    (0x70, "OP_2OVER"),
+    (0x71, "OP_2ROT"),
+    (0x72, "OP_2SWAP"),
+    (0x73, "OP_IFDUP"),
+    (0x74, "OP_DEPTH"),
+    (0x75, "OP_DROP"),
+    (0x76, "OP_DUP"),
+    (0x77, "OP_NIP"),
+    (0x78, "OP_OVER"),
+    (0x79, "OP_PICK"),
+    (0x7a, "OP_ROLL"),
+    (0x7b, "OP_ROT"),
+    (0x7c, "OP_SWAP"),
+    (0x7d, "OP_TUCK"),
+    (0x7e, "OP_CAT"),
+    (0x7f, "OP_SUBSTR"),
+    (0x80, "OP_LEFT"),
+    (0x81, "OP_RIGHT"),
+    (0x82, "OP_SIZE"),
+    (0x83, "OP_INVERT"),
+    (0x84, "OP_AND"),
+    (0x85, "OP_OR"),
+    (0x86, "OP_XOR"),
+    (0x87, "OP_EQUAL"),
+    (0x88, "OP_EQUALVERIFY"),
+    (0x89, "OP_RESERVED1"),
+    (0x8a, "OP_RESERVED2"),
+    (0x8b, "OP_1ADD"),
+    (0x8c, "OP_1SUB"),
+    (0x8d, "OP_2MUL"),
+    (0x8e, "OP_2DIV"),
+    (0x8f, "OP_NEGATE"),
+    (0x90, "OP_ABS"),
+    (0x91, "OP_NOT"),
+    (0x92, "OP_0NOTEQUAL"),
+    (0x93, "OP_ADD"),
+    (0x94, "OP_SUB"),
+    (0x95, "OP_MUL"),
+    (0x96, "OP_DIV"),
+    (0x97, "OP_MOD"),
+    (0x98, "OP_LSHIFT"),
+    (0x99, "OP_RSHIFT"),
+    (0x9a, "OP_BOOLAND"),
+    (0x9b, "OP_BOOLOR"),
+    (0x9c, "OP_NUMEQUAL"),
+    (0x9d, "OP_NUMEQUALVERIFY"),
+    (0x9e, "OP_NUMNOTEQUAL"),
+    (0x9f, "OP_LESSTHAN"),
+    (0xa0, "OP_GREATERTHAN"),
+    (0xa1, "OP_LESSTHANOREQUAL"),
+    (0xa2, "OP_GREATERTHANOREQUAL"),
+    (0xa3, "OP_MIN"),
+    (0xa4, "OP_MAX"),
+    (0xa5, "OP_WITHIN"),
+    (0xa6, "OP_RIPEMD160"),
+    (0xa7, "OP_SHA1"),
+    (0xa8, "OP_SHA256"),
+    (0xa9, "OP_HASH160"),
+    (0xaa, "OP_HASH256"),
+    (0xab, "OP_CODESEPARATOR"),
+    (0xac, "OP_CHECKSIG"),
+    (0xad, "OP_CHECKSIGVERIFY"),
+    (0xae, "OP_CHECKMULTISIG"),
+    (0xaf, "OP_CHECKMULTISIGVERIFY"),
+    (0xb0, "OP_NOP1"),
+    (0xb1, "OP_CHECKLOCKTIMEVERIFY"),
+    (0xb2, "OP_CHECKSEQUENCEVERIFY"),
+    (0xb3, "OP_NOP4"),
+    (0xb4, "OP_NOP5"),
+    (0xb5, "OP_NOP6"),
+    (0xb6, "OP_NOP7"),
+    (0xb7, "OP_NOP8"),
+    (0xb8, "OP_NOP9"),
+    (0xb9, "OP_NOP10"),
+    (0xba, "OP_CHECKSIGADD"),
+];
+
+pub fn opcode_name(opcode: u8) -> Option<&'static str> {
+    OPCODE_NAMES
+        .iter()
+        .find(|(code, _)| *code == opcode)
+        .map(|(_, name)| *name)
+}
+
+/// 脚本中的一条指令，push数据或者操作码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Instruction {
+    Push(Vec<u8>),
+    Op(u8),
+}
+
+/// 按push规则拆分脚本，同时返回每条指令在脚本中的原始字节，push长度超出脚本时报错
+fn instruction_spans(script: &[u8]) -> Result<Vec<(Instruction, &[u8])>> {
+    let mut result = vec![];
+    let mut index = 0;
+    while index < script.len() {
+        let start = index;
+        let opcode = script[index];
+        index += 1;
+        let (len_size, len) = match opcode {
+            OP_0 => (0, 0),
+            0x01..=0x4b => (0, opcode as usize),
+            OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4 => {
+                let len_size = 1 << (opcode - OP_PUSHDATA1);
+                let Some(bytes) = script.get(index..index + len_size) else {
+                    bail!("script truncated at push length, offset {index}");
+                };
+                let len = bytes
+                    .iter()
+                    .rev()
+                    .fold(0usize, |len, b| (len << 8) | *b as usize);
+                (len_size, len)
+            }
+            _ => {
+                result.push((Instruction::Op(opcode), &script[start..index]));
+                continue;
+            }
+        };
+        index += len_size;
+        let Some(data) = script.get(index..index + len) else {
+            bail!("script truncated, push {len} bytes at offset {index}");
+        };
+        index += len;
+        result.push((Instruction::Push(data.to_vec()), &script[start..index]));
+    }
+    Ok(result)
+}
+
+pub fn instructions(script: &[u8]) -> Result<Vec<Instruction>> {
+    Ok(instruction_spans(script)?
+        .into_iter()
+        .map(|(instruction, _)| instruction)
+        .collect())
+}
+
+/// 使用最短的push操作码
+pub fn push_bytes(data: &[u8]) -> Vec<u8> {
+    let len = data.len();
+    let mut result = match len {
+        0..=0x4b => vec![len as u8],
+        0x4c..=0xff => vec![OP_PUSHDATA1, len as u8],
+        0x100..=0xffff => [&[OP_PUSHDATA2][..], &(len as u16).to_le_bytes()].concat(),
+        _ => [&[OP_PUSHDATA4][..], &(len as u32).to_le_bytes()].concat(),
+    };
+    result.extend_from_slice(data);
+    result
+}
+
+/// 与Bitcoin Core的ASM格式一致：push数据为hex，OP_0/OP_1NEGATE/OP_1-16显示为数字。
+/// 未知操作码、非最短push以及会被当作数字的push(如 10-16)显示为0x开头的原始字节，保证能用from_asm还原
+pub fn to_asm(script: &[u8]) -> Result<String> {
+    let asm: Vec<String> = instruction_spans(script)?
+        .into_iter()
+        .map(|(instruction, raw)| match instruction {
+            Instruction::Push(data) if data.is_empty() && raw == [OP_0] => "0".to_string(),
+            Instruction::Push(data) => {
+                let hex = u8_array_convert_string(&data);
+                if data.is_empty() || push_bytes(&data) != raw || small_number(&hex).is_some() {
+                    format!("0x{}", u8_array_convert_string(raw))
+                } else {
+                    hex
+                }
+            }
+            Instruction::Op(OP_1NEGATE) => "-1".to_string(),
+            Instruction::Op(opcode @ OP_1..=OP_16) => (opcode - OP_1 + 1).to_string(),
+            Instruction::Op(opcode) => opcode_name(opcode)
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("0x{opcode:02x}")),
+        })
+        .collect();
+    Ok(asm.join(" "))
+}
+
+/// ASM中的数字 -1 到 16 对应的操作码
+fn small_number(token: &str) -> Option<u8> {
+    match token {
+        "-1" => Some(OP_1NEGATE),
+        "0" => Some(OP_0),
+        _ => match token.parse::<u8>() {
+            Ok(n @ 1..=16) if !token.starts_with('0') => Some(OP_1 + n - 1),
+            _ => None,
+        },
+    }
+}
+
+/// 操作码名称，兼容 OP_FALSE/OP_TRUE/OP_NOP2/OP_NOP3 等别名，OP_前缀可省略
+fn parse_opcode(token: &str) -> Option<u8> {
+    let name = token.to_uppercase();
+    let name = if name.starts_with("OP_") {
+        name
+    } else {
+        format!("OP_{name}")
+    };
+    match name.as_str() {
+        "OP_0" | "OP_FALSE" => Some(OP_0),
+        "OP_TRUE" => Some(OP_1),
+        "OP_NOP2" | "OP_CLTV" => Some(0xb1),
+        "OP_NOP3" | "OP_CSV" => Some(0xb2),
+        _ => OPCODE_NAMES
+            .iter()
+            .find(|(_, opcode_name)| *opcode_name == name)
+            .map(|(opcode, _)| *opcode),
+    }
+}
+
+/// 由ASM生成脚本：数字-1到16、操作码名称、0x开头的原始字节，其他按hex数据push
+pub fn from_asm(asm: &str) -> Result<Vec<u8>> {
+    let mut script = vec![];
+    for token in asm.split_whitespace() {
+        if let Some(opcode) = small_number(token) {
+            script.push(opcode);
+        } else if let Some(raw) = token.strip_prefix("0x") {
+            script.extend(hex::decode(raw).map_err(|e| anyhow!("invalid raw bytes {token}: {e}"))?);
+        } else if let Some(opcode) = parse_opcode(token) {
+            if matches!(opcode, OP_PUSHDATA1 | OP_PUSHDATA2 | OP_PUSHDATA4) {
+                bail!("{token} can not be used directly, use hex data instead.");
+            }
+            script.push(opcode);
+        } else if let Ok(data) = hex::decode(token) {
+            script.extend(push_bytes(&data));
+        } else {
+            bail!("unknown asm token {token}");
+        }
+    }
+    instructions(&script)?;
+    Ok(script)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ScriptTemplate {
+    #[serde(rename = "p2pk")]
+    P2pk,
+    #[serde(rename = "p2pkh")]
+    P2pkh,
+    #[serde(rename = "p2sh")]
+    P2sh,
+    #[serde(rename = "p2wpkh")]
+    P2wpkh,
+    #[serde(rename = "p2wsh")]
+    P2wsh,
+    #[serde(rename = "p2tr")]
+    P2tr,
+    #[serde(rename = "witness_unknown")]
+    WitnessUnknown,
+    #[serde(rename = "multisig")]
+    Multisig,
+    #[serde(rename = "nulldata")]
+    OpReturn,
+    #[serde(rename = "nonstandard")]
+    NonStandard,
+}
+
+/// segwit输出：版本操作码 + 2到40字节的witness program
+pub fn witness_program(script: &[u8]) -> Option<(u8, &[u8])> {
+    let version = match *script.first()? {
+        OP_0 => 0,
+        opcode @ OP_1..=OP_16 => opcode - OP_1 + 1,
+        _ => return None,
+    };
+    let len = *script.get(1)? as usize;
+    (script.len() == len + 2 && (2..=40).contains(&len)).then(|| (version, &script[2..]))
+}
+
+fn small_int(opcode: u8) -> Option<usize> {
+    matches!(opcode, OP_1..=OP_16).then(|| (opcode - OP_1 + 1) as usize)
+}
+
+fn is_public_key(data: &[u8]) -> bool {
+    matches!(
+        (data.len(), data.first()),
+        (33, Some(0x02 | 0x03)) | (65, Some(0x04))
+    )
+}
+
+pub fn classify(script: &[u8]) -> ScriptTemplate {
+    match script {
+        [OP_DUP, OP_HASH160, 20, .., OP_EQUALVERIFY, OP_CHECKSIG] if script.len() == 25 => {
+            return ScriptTemplate::P2pkh
+        }
+        [OP_HASH160, 20, .., OP_EQUAL] if script.len() == 23 => return ScriptTemplate::P2sh,
+        [OP_RETURN, ..] => return ScriptTemplate::OpReturn,
+        _ => {}
+    }
+    match witness_program(script) {
+        Some((0, program)) if program.len() == 20 => return ScriptTemplate::P2wpkh,
+        Some((0, program)) if program.len() == 32 => return ScriptTemplate::P2wsh,
+        Some((1, program)) if program.len() == 32 => return ScriptTemplate::P2tr,
+        Some((0, _)) => return ScriptTemplate::NonStandard,
+        Some(_) => return ScriptTemplate::WitnessUnknown,
+        None => {}
+    }
+    if parse_multisig(script).is_some() {
+        return ScriptTemplate::Multisig;
+    }
+    match instructions(script).as_deref() {
+        Ok([Instruction::Push(key), Instruction::Op(OP_CHECKSIG)]) if is_public_key(key) => {
+            ScriptTemplate::P2pk
+        }
+        _ => ScriptTemplate::NonStandard,
+    }
+}
+
+/// 解析 OP_m <pubkey>... OP_n OP_CHECKMULTISIG，返回m和公钥列表
+pub fn parse_multisig(script: &[u8]) -> Option<(usize, Vec<Vec<u8>>)> {
+    let instructions = instructions(script).ok()?;
+    let [Instruction::Op(m), keys @ .., Instruction::Op(n), Instruction::Op(OP_CHECKMULTISIG)] =
+        instructions.as_slice()
+    else {
+        return None;
+    };
+    let (m, n) = (small_int(*m)?, small_int(*n)?);
+    let keys = keys
+        .iter()
+        .map(|key| match key {
+            Instruction::Push(key) if is_public_key(key) => Some(key.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    (m <= n && n == keys.len()).then_some((m, keys))
+}
+
+pub fn p2pkh_script(pubkey_hash: &[u8]) -> Vec<u8> {
+    [
+        &[OP_DUP, OP_HASH160][..],
+        &push_bytes(pubkey_hash),
+        &[OP_EQUALVERIFY, OP_CHECKSIG],
+    ]
+    .concat()
+}
+
+pub fn p2sh_script(script_hash: &[u8]) -> Vec<u8> {
+    [&[OP_HASH160][..], &push_bytes(script_hash), &[OP_EQUAL]].concat()
+}
+
+/// 版本操作码 + witness program
+pub fn segwit_script(version: u8, program: &[u8]) -> Vec<u8> {
+    let version = if version == 0 {
+        OP_0
+    } else {
+        OP_1 + version - 1
+    };
+    [&[version][..], &push_bytes(program)].concat()
+}
+
+/// 按地址类型由压缩公钥生成scriptPubKey
+pub fn public_key_to_script_pubkey(
+    public_key: &PublicKey,
+    address_type: AddressType,
+) -> Result<Vec<u8>> {
+    let pubkey_hash = hash160(&public_key.serialize());
+    Ok(match address_type {
+        AddressType::P2pkh => p2pkh_script(&pubkey_hash),
+        AddressType::P2shP2wpkh => p2sh_script(&hash160(&segwit_script(0, &pubkey_hash))),
+        AddressType::P2wpkh => segwit_script(0, &pubkey_hash),
+        AddressType::P2tr => segwit_script(1, &taproot_output_key(public_key)?.serialize()),
+    })
+}
+
+/// 解析segwit地址，校验hrp、bech32/bech32m变体和program长度
+pub fn decode_segwit_address(address: &str, network: Network) -> Result<(u8, Vec<u8>)> {
+    let (hrp, data, variant) =
+        bech32::decode(address).map_err(|e| anyhow!("invalid bech32 address {address}: {e}"))?;
+    if hrp != network.hrp() {
+        bail!(
+            "address {address} hrp {hrp} does not match {network:?}, expect {}",
+            network.hrp()
+        );
+    }
+    let Some((version, program)) = data.split_first() else {
+        bail!("address {address} has no witness version");
+    };
+    let version = version.to_u8();
+    let program = Vec::<u8>::from_base32(program)?;
+    match (version, variant) {
+        (0, Variant::Bech32) | (1..=16, Variant::Bech32m) => {}
+        (0, Variant::Bech32m) => bail!("witness v0 address {address} must use bech32"),
+        (1..=16, Variant::Bech32) => {
+            bail!("witness v{version} address {address} must use bech32m")
+        }
+        _ => bail!("invalid witness version {version}"),
+    }
+    if !(2..=40).contains(&program.len()) || (version == 0 && ![20, 32].contains(&program.len())) {
+        bail!(
+            "invalid witness v{version} program length {}",
+            program.len()
+        );
+    }
+    Ok((version, program))
+}
+
+/// 解析base58check地址，返回版本字节和payload
+pub fn decode_base58_address(address: &str) -> Result<(u8, Vec<u8>)> {
+    let data = bs58::decode(address)
+        .into_vec()
+        .map_err(|e| anyhow!("invalid base58 address {address}: {e}"))?;
+    if data.len() != 25 {
+        bail!("invalid base58 address {address}: length {}", data.len());
+    }
+    let (payload, checksum) = data.split_at(21);
+    if double_sha256(payload)[..4] != *checksum {
+        bail!("invalid base58 address {address}: checksum mismatch");
+    }
+    Ok((payload[0], payload[1..].to_vec()))
+}
+
+/// 地址转为scriptPubKey，支持P2PKH、P2SH和segwit v0/v1地址，并校验所属网络
+pub fn address_to_script_pubkey(address: &str, network: Network) -> Result<Vec<u8>> {
+    let address = address.trim();
+    if address
+        .to_lowercase()
+        .starts_with(&format!("{}1", network.hrp()))
+    {
+        let (version, program) = decode_segwit_address(address, network)?;
+        return Ok(segwit_script(version, &program));
+    }
+    let (version, hash) = decode_base58_address(address)?;
+    match version {
+        v if v == network.p2pkh_prefix() => Ok(p2pkh_script(&hash)),
+        v if v == network.p2sh_prefix() => Ok(p2sh_script(&hash)),
+        v => bail!("address {address} version {v:#04x} does not belong to {network:?}"),
+    }
+}
+
+/// 输出脚本对应的地址，P2PK、多签和OP_RETURN等没有地址
+pub fn script_to_address(script: &[u8], network: Network) -> Option<String> {
+    match classify(script) {
+        ScriptTemplate::P2pkh => Some(base58check_encode(network.p2pkh_prefix(), &script[3..23])),
+        ScriptTemplate::P2sh => Some(base58check_encode(network.p2sh_prefix(), &script[2..22])),
+        ScriptTemplate::P2wpkh
+        | ScriptTemplate::P2wsh
+        | ScriptTemplate::P2tr
+        | ScriptTemplate::WitnessUnknown => {
+            let (version, program) = witness_program(script)?;
+            segwit_address(network, version, program).ok()
+        }
+        _ => None,
+    }
+}
+
+/// 解码后的脚本，用于交易和脚本的JSON输出
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedScript {
+    pub asm: String,
+    pub hex: String,
+    #[serde(rename = "type")]
+    pub template: ScriptTemplate,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+}
+
+impl DecodedScript {
+    pub fn new(script: &[u8], network: Network) -> Self {
+        DecodedScript {
+            asm: to_asm(script).unwrap_or_else(|e| format!("[error] {e}")),
+            hex: u8_array_convert_string(script),
+            template: classify(script),
+            address: script_to_address(script, network),
+        }
+    }
+}
+
+/// script decode 的输出，非P2SH和segwit的脚本同时给出作为redeem script和witness script时的地址
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScriptInfo {
+    #[serde(flatten)]
+    pub script: DecodedScript,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p2sh: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p2wsh: Option<String>,
+}
+
+impl ScriptInfo {
+    pub fn new(script: &[u8], network: Network) -> Self {
+        let decoded = DecodedScript::new(script, network);
+        let wrappable = !matches!(
+            decoded.template,
+            ScriptTemplate::P2sh
+                | ScriptTemplate::P2wpkh
+                | ScriptTemplate::P2wsh
+                | ScriptTemplate::P2tr
+                | ScriptTemplate::WitnessUnknown
+                | ScriptTemplate::OpReturn
+        );
+        let (p2sh, p2wsh) = if wrappable {
+            (
+                Some(base58check_encode(network.p2sh_prefix(), &hash160(script))),
+                segwit_address(network, 0, &Sha256::digest(script)).ok(),
+            )
+        } else {
+            (None, None)
+        };
+        ScriptInfo {
+            script: decoded,
+            p2sh,
+            p2wsh,
+        }
+    }
+}
+
+pub fn decode_script(
+    script: String,
+    network: String,
+    output: Option<String>,
+) -> Result<ScriptInfo> {
+    let network = Network::from_str(&network)?;
+    let script = parse_hex(&script)?;
+    // 提前检查脚本能否完整解析
+    instructions(&script)?;
+    let info = ScriptInfo::new(&script, network);
+    write_output(output, &serde_json::to_string_pretty(&info)?)?;
+    Ok(info)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EncodedScript {
+    pub script: String,
+}
+
+pub fn encode_script(asm: String) -> Result<EncodedScript> {
+    Ok(EncodedScript {
+        script: u8_array_convert_string(&from_asm(&asm)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::hex_string_2_array;
+
+    #[test]
+    fn test_classify() {
+        let cases = [
+            (
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+                ScriptTemplate::P2pkh,
+                Some("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"),
+            ),
+            (
+                "a914748284390f9e263a4b766a75d0633c50426eb87587",
+                ScriptTemplate::P2sh,
+                Some("3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V"),
+            ),
+            (
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+                ScriptTemplate::P2wpkh,
+                Some("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4"),
+            ),
+            (
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+                ScriptTemplate::P2wsh,
+                Some("bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3"),
+            ),
+            (
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+                ScriptTemplate::P2tr,
+                Some("bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"),
+            ),
+            (
+                "410411db93e1dcdb8a016b49840f8c53bc1eb68a382e97b1482ecad7b148a6909a5cb2e0eaddfb84ccf9744464f82e160bfa9b8b64f9d4c03f999b8643f656b412a3ac",
+                ScriptTemplate::P2pk,
+                None,
+            ),
+            ("6a0568656c6c6f", ScriptTemplate::OpReturn, None),
+            ("5152ae", ScriptTemplate::NonStandard, None),
+            ("4c", ScriptTemplate::NonStandard, None),
+        ];
+        for (script, template, address) in cases {
+            let script = hex_string_2_array(script);
+            assert_eq!(classify(&script), template);
+            assert_eq!(
+                script_to_address(&script, Network::Mainnet).as_deref(),
+                address
+            );
+        }
+        let multisig = format!(
+            "5121{}21{}52ae",
+            "0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798",
+            "02c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee5"
+        );
+        assert_eq!(
+            classify(&hex_string_2_array(&multisig)),
+            ScriptTemplate::Multisig
+        );
+    }
+
+    #[test]
+    fn test_to_asm() {
+        let script = hex_string_2_array("76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac");
+        assert_eq!(
+            to_asm(&script).unwrap(),
+            "OP_DUP OP_HASH160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 OP_EQUALVERIFY OP_CHECKSIG"
+        );
+        let script = hex_string_2_array("0014751e76e8199196d454941c45d1b3a323f1433bd6");
+        assert_eq!(
+            to_asm(&script).unwrap(),
+            "0 751e76e8199196d454941c45d1b3a323f1433bd6"
+        );
+        let script = hex_string_2_array("4f0051604c02abcdfe");
+        assert_eq!(to_asm(&script).unwrap(), "-1 0 1 16 0x4c02abcd 0xfe");
+        assert!(to_asm(&hex_string_2_array("4c05ab")).is_err());
+    }
+
+    #[test]
+    fn test_asm_round_trip() {
+        let scripts = [
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+            "a914748284390f9e263a4b766a75d0633c50426eb87587",
+            "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            "6a0568656c6c6f",
+            "52210279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f817982102c6047f9441ed7d6d3045406e95c07cd85c778e4b8cef3ca7abac09b95c709ee552ae",
+            // 非最短push、push 0x10、空push、未知操作码
+            "4c0201024c00011000fe",
+            "4f0051604c02abcdfe",
+            "",
+        ];
+        for script in scripts {
+            let bytes = hex_string_2_array(script);
+            let asm = to_asm(&bytes).unwrap();
+            assert_eq!(from_asm(&asm).unwrap(), bytes, "{asm}");
+        }
+        let data = vec![0xab; 300];
+        let script = push_bytes(&data);
+        assert_eq!(&script[..3], &[OP_PUSHDATA2, 0x2c, 0x01]);
+        assert_eq!(from_asm(&to_asm(&script).unwrap()).unwrap(), script);
+    }
+
+    #[test]
+    fn test_from_asm() {
+        assert_eq!(
+            u8_array_convert_string(
+                &from_asm("OP_DUP op_hash160 62e907b15cbf27d5425399ebf6f0fb50ebb88f18 EQUALVERIFY OP_CHECKSIG").unwrap()
+            ),
+            "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac"
+        );
+        assert_eq!(
+            from_asm("OP_FALSE OP_TRUE 16 -1 OP_CLTV").unwrap(),
+            vec![OP_0, OP_1, OP_16, OP_1NEGATE, 0xb1]
+        );
+        assert!(from_asm("OP_FOO").is_err());
+        assert!(from_asm("abc").is_err());
+        assert!(from_asm("OP_PUSHDATA1").is_err());
+        assert!(from_asm("0x4c05ab").is_err());
+        let info = ScriptInfo::new(&from_asm("1 0279be667ef9dcbbac55a06295ce870b07029bfcdb2dce28d959f2815b16f81798 1 OP_CHECKMULTISIG").unwrap(), Network::Mainnet);
+        assert_eq!(info.script.template, ScriptTemplate::Multisig);
+        assert!(info.p2sh.unwrap().starts_with('3'));
+        assert!(info.p2wsh.unwrap().starts_with("bc1q"));
+    }
+
+    #[test]
+    fn test_address_to_script_pubkey() {
+        let cases = [
+            (
+                "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa",
+                Network::Mainnet,
+                "76a91462e907b15cbf27d5425399ebf6f0fb50ebb88f1888ac",
+            ),
+            (
+                "3CK4fEwbMP7heJarmU4eqA3sMbVJyEnU3V",
+                Network::Mainnet,
+                "a914748284390f9e263a4b766a75d0633c50426eb87587",
+            ),
+            (
+                "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+                Network::Mainnet,
+                "0014751e76e8199196d454941c45d1b3a323f1433bd6",
+            ),
+            (
+                "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr",
+                Network::Mainnet,
+                "5120a60869f0dbcf1dc659c9cecbaf8050135ea9e8cdc487053f1dc6880949dc684c",
+            ),
+            (
+                "tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7",
+                Network::Testnet,
+                "00201863143c14c5166804bd19203356da136c985678cd4d27a1b8c6329604903262",
+            ),
+        ];
+        for (address, network, script) in cases {
+            let script_pubkey = address_to_script_pubkey(address, network).unwrap();
+            assert_eq!(u8_array_convert_string(&script_pubkey), script);
+            assert!(script_to_address(&script_pubkey, network)
+                .unwrap()
+                .eq_ignore_ascii_case(address));
+        }
+        // 网络不匹配、checksum错误、v1使用bech32
+        assert!(
+            address_to_script_pubkey("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", Network::Testnet)
+                .is_err()
+        );
+        assert!(
+            address_to_script_pubkey("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb", Network::Mainnet)
+                .is_err()
+        );
+        assert!(address_to_script_pubkey(
+            "bc1pw508d6qejxtdg4y5r3zarvary0c5xw7kw508d6qejxtdg4y5r3zarvary0c5xw7k7grplx",
+            Network::Mainnet
+        )
+        .is_err());
+        assert!(address_to_script_pubkey(
+            "tb1qw508d6qejxtdg4y5r3zarvary0c5xw7kxpjzsx",
+            Network::Mainnet
+        )
+        .is_err());
+    }
+}
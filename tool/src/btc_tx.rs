@@ -0,0 +1,602 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::utils::hex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+
+use crate::btc::{double_sha256, tagged_hash, Network};
+use crate::btc_script::{to_asm, DecodedScript};
+use crate::encrypt_decrypt::{read_input, write_output};
+use crate::util::u8_array_convert_string;
+
+const SEGWIT_MARKER: u8 = 0x00;
+const SEGWIT_FLAG: u8 = 0x01;
+const WITNESS_SCALE_FACTOR: usize = 4;
+const SATS_PER_BTC: u64 = 100_000_000;
+pub const SIGHASH_ALL: u32 = 0x01;
+/// taproot的SIGHASH_DEFAULT，签名后不追加sighash字节
+pub const SIGHASH_DEFAULT: u8 = 0x00;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutPoint {
+    /// 内部字节序，显示时需要反转
+    pub txid: [u8; 32],
+    pub vout: u32,
+}
+
+impl OutPoint {
+    pub fn is_null(&self) -> bool {
+        self.txid == [0u8; 32] && self.vout == u32::MAX
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxIn {
+    pub previous_output: OutPoint,
+    pub script_sig: Vec<u8>,
+    pub sequence: u32,
+    pub witness: Vec<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxOut {
+    pub value: u64,
+    pub script_pubkey: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub version: i32,
+    pub inputs: Vec<TxIn>,
+    pub outputs: Vec<TxOut>,
+    pub lock_time: u32,
+}
+
+/// 按字节顺序读取交易数据
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    /// 尚未读取的数据
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    pub fn read_bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| {
+                anyhow!(
+                    "transaction truncated, read {len} bytes at offset {}",
+                    self.position
+                )
+            })?;
+        let bytes = &self.data[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.read_bytes(4)?.try_into()?))
+    }
+
+    pub fn read_u64(&mut self) -> Result<u64> {
+        Ok(u64::from_le_bytes(self.read_bytes(8)?.try_into()?))
+    }
+
+    pub fn read_var_int(&mut self) -> Result<u64> {
+        match self.read_u8()? {
+            0xfd => Ok(u16::from_le_bytes(self.read_bytes(2)?.try_into()?) as u64),
+            0xfe => Ok(self.read_u32()? as u64),
+            0xff => self.read_u64(),
+            n => Ok(n as u64),
+        }
+    }
+
+    /// 长度不能超过剩余数据，避免恶意长度导致分配过大
+    pub fn read_len(&mut self) -> Result<usize> {
+        let len = self.read_var_int()?;
+        if len > (self.data.len() - self.position) as u64 {
+            bail!("invalid length {len} at offset {}", self.position);
+        }
+        Ok(len as usize)
+    }
+
+    pub fn read_var_bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.read_len()?;
+        Ok(self.read_bytes(len)?.to_vec())
+    }
+}
+
+/// Bitcoin的CompactSize编码
+pub fn write_var_int(buffer: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => buffer.push(n as u8),
+        0xfd..=0xffff => {
+            buffer.push(0xfd);
+            buffer.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x10000..=0xffff_ffff => {
+            buffer.push(0xfe);
+            buffer.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            buffer.push(0xff);
+            buffer.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+pub fn write_var_bytes(buffer: &mut Vec<u8>, data: &[u8]) {
+    write_var_int(buffer, data.len() as u64);
+    buffer.extend_from_slice(data);
+}
+
+/// double sha256后反转字节序，txid和区块hash的显示格式
+pub fn hash_to_hex(hash: &[u8]) -> String {
+    let mut hash = hash.to_vec();
+    hash.reverse();
+    u8_array_convert_string(&hash)
+}
+
+fn sha256d(data: &[u8]) -> [u8; 32] {
+    let mut hash = [0u8; 32];
+    hash.copy_from_slice(&double_sha256(data));
+    hash
+}
+
+pub fn parse_hex(data: &str) -> Result<Vec<u8>> {
+    hex::decode(data.trim().trim_start_matches("0x")).map_err(|e| anyhow!("invalid hex: {e}"))
+}
+
+impl TxOut {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let output = TxOut {
+            value: reader.read_u64()?,
+            script_pubkey: reader.read_var_bytes()?,
+        };
+        if !reader.is_empty() {
+            bail!("trailing bytes after transaction output");
+        }
+        Ok(output)
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buffer = self.value.to_le_bytes().to_vec();
+        write_var_bytes(&mut buffer, &self.script_pubkey);
+        buffer
+    }
+}
+
+impl Transaction {
+    /// 支持legacy和BIP-144 segwit格式，segwit在version后有marker 0x00和flag 0x01
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        let mut reader = Reader::new(data);
+        let version = reader.read_u32()? as i32;
+        let mut input_count = reader.read_len()?;
+        let segwit = input_count == SEGWIT_MARKER as usize;
+        if segwit {
+            let flag = reader.read_u8()?;
+            if flag != SEGWIT_FLAG {
+                bail!("invalid segwit flag {flag:#04x}");
+            }
+            input_count = reader.read_len()?;
+        }
+        let mut inputs = Vec::with_capacity(input_count);
+        for _ in 0..input_count {
+            let txid = reader.read_bytes(32)?.try_into()?;
+            let vout = reader.read_u32()?;
+            inputs.push(TxIn {
+                previous_output: OutPoint { txid, vout },
+                script_sig: reader.read_var_bytes()?,
+                sequence: reader.read_u32()?,
+                witness: vec![],
+            });
+        }
+        let output_count = reader.read_len()?;
+        let mut outputs = Vec::with_capacity(output_count);
+        for _ in 0..output_count {
+            outputs.push(TxOut {
+                value: reader.read_u64()?,
+                script_pubkey: reader.read_var_bytes()?,
+            });
+        }
+        if segwit {
+            for input in inputs.iter_mut() {
+                let count = reader.read_len()?;
+                for _ in 0..count {
+                    input.witness.push(reader.read_var_bytes()?);
+                }
+            }
+            if inputs.iter().all(|input| input.witness.is_empty()) {
+                bail!("segwit transaction without witness data");
+            }
+        }
+        let lock_time = reader.read_u32()?;
+        if !reader.is_empty() {
+            bail!(
+                "{} trailing bytes after transaction",
+                data.len() - reader.position
+            );
+        }
+        Ok(Transaction {
+            version,
+            inputs,
+            outputs,
+            lock_time,
+        })
+    }
+
+    pub fn has_witness(&self) -> bool {
+        self.inputs.iter().any(|input| !input.witness.is_empty())
+    }
+
+    fn serialize_inner(&self, with_witness: bool) -> Vec<u8> {
+        let with_witness = with_witness && self.has_witness();
+        let mut buffer = vec![];
+        buffer.extend_from_slice(&self.version.to_le_bytes());
+        if with_witness {
+            buffer.extend_from_slice(&[SEGWIT_MARKER, SEGWIT_FLAG]);
+        }
+        write_var_int(&mut buffer, self.inputs.len() as u64);
+        for input in &self.inputs {
+            buffer.extend_from_slice(&input.previous_output.txid);
+            buffer.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+            write_var_bytes(&mut buffer, &input.script_sig);
+            buffer.extend_from_slice(&input.sequence.to_le_bytes());
+        }
+        write_var_int(&mut buffer, self.outputs.len() as u64);
+        for output in &self.outputs {
+            buffer.extend_from_slice(&output.serialize());
+        }
+        if with_witness {
+            for input in &self.inputs {
+                write_var_int(&mut buffer, input.witness.len() as u64);
+                for item in &input.witness {
+                    write_var_bytes(&mut buffer, item);
+                }
+            }
+        }
+        buffer.extend_from_slice(&self.lock_time.to_le_bytes());
+        buffer
+    }
+
+    /// 完整序列化，有witness时使用segwit格式
+    pub fn serialize(&self) -> Vec<u8> {
+        self.serialize_inner(true)
+    }
+
+    /// 不含witness的序列化，用于计算txid
+    pub fn serialize_without_witness(&self) -> Vec<u8> {
+        self.serialize_inner(false)
+    }
+
+    pub fn txid(&self) -> String {
+        hash_to_hex(&double_sha256(&self.serialize_without_witness()))
+    }
+
+    /// 没有witness时与txid相同
+    pub fn wtxid(&self) -> String {
+        hash_to_hex(&double_sha256(&self.serialize()))
+    }
+
+    /// BIP-141: weight = base_size * 3 + total_size
+    pub fn weight(&self) -> usize {
+        self.serialize_without_witness().len() * (WITNESS_SCALE_FACTOR - 1) + self.serialize().len()
+    }
+
+    pub fn vsize(&self) -> usize {
+        self.weight().div_ceil(WITNESS_SCALE_FACTOR)
+    }
+
+    fn serialize_outpoints(&self) -> Vec<u8> {
+        let mut buffer = vec![];
+        for input in &self.inputs {
+            buffer.extend_from_slice(&input.previous_output.txid);
+            buffer.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+        }
+        buffer
+    }
+
+    fn serialize_sequences(&self) -> Vec<u8> {
+        self.inputs
+            .iter()
+            .flat_map(|input| input.sequence.to_le_bytes())
+            .collect()
+    }
+
+    fn serialize_outputs(&self) -> Vec<u8> {
+        self.outputs.iter().flat_map(TxOut::serialize).collect()
+    }
+
+    /// legacy签名hash，当前输入的scriptSig替换为script_code，其他输入置空，只支持SIGHASH_ALL
+    pub fn legacy_sighash(&self, index: usize, script_code: &[u8]) -> [u8; 32] {
+        let mut tx = self.clone();
+        for (i, input) in tx.inputs.iter_mut().enumerate() {
+            input.script_sig = if i == index {
+                script_code.to_vec()
+            } else {
+                vec![]
+            };
+        }
+        let mut data = tx.serialize_without_witness();
+        data.extend_from_slice(&SIGHASH_ALL.to_le_bytes());
+        sha256d(&data)
+    }
+
+    /// BIP-143 segwit v0签名hash，P2WPKH的script_code为对应的P2PKH脚本，只支持SIGHASH_ALL
+    pub fn segwit_v0_sighash(&self, index: usize, script_code: &[u8], amount: u64) -> [u8; 32] {
+        let input = &self.inputs[index];
+        let mut data = vec![];
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(&sha256d(&self.serialize_outpoints()));
+        data.extend_from_slice(&sha256d(&self.serialize_sequences()));
+        data.extend_from_slice(&input.previous_output.txid);
+        data.extend_from_slice(&input.previous_output.vout.to_le_bytes());
+        write_var_bytes(&mut data, script_code);
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&input.sequence.to_le_bytes());
+        data.extend_from_slice(&sha256d(&self.serialize_outputs()));
+        data.extend_from_slice(&self.lock_time.to_le_bytes());
+        data.extend_from_slice(&SIGHASH_ALL.to_le_bytes());
+        sha256d(&data)
+    }
+
+    /// BIP-341 key path签名hash，需要所有输入花费的输出，只支持SIGHASH_DEFAULT
+    pub fn taproot_key_spend_sighash(&self, index: usize, prevouts: &[TxOut]) -> Result<[u8; 32]> {
+        if prevouts.len() != self.inputs.len() {
+            bail!(
+                "taproot sighash needs {} prevouts, got {}",
+                self.inputs.len(),
+                prevouts.len()
+            );
+        }
+        let mut script_pubkeys = vec![];
+        for prevout in prevouts {
+            write_var_bytes(&mut script_pubkeys, &prevout.script_pubkey);
+        }
+        let amounts: Vec<u8> = prevouts
+            .iter()
+            .flat_map(|prevout| prevout.value.to_le_bytes())
+            .collect();
+        // epoch + hash_type
+        let mut data = vec![0x00, SIGHASH_DEFAULT];
+        data.extend_from_slice(&self.version.to_le_bytes());
+        data.extend_from_slice(&self.lock_time.to_le_bytes());
+        data.extend_from_slice(&Sha256::digest(self.serialize_outpoints()));
+        data.extend_from_slice(&Sha256::digest(amounts));
+        data.extend_from_slice(&Sha256::digest(script_pubkeys));
+        data.extend_from_slice(&Sha256::digest(self.serialize_sequences()));
+        data.extend_from_slice(&Sha256::digest(self.serialize_outputs()));
+        // spend_type: 没有annex的key path
+        data.push(0x00);
+        data.extend_from_slice(&(index as u32).to_le_bytes());
+        Ok(tagged_hash("TapSighash", &data))
+    }
+}
+
+pub fn format_btc(sats: u64) -> String {
+    format!("{}.{:08}", sats / SATS_PER_BTC, sats % SATS_PER_BTC)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScriptSig {
+    pub asm: String,
+    pub hex: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedInput {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub coinbase: bool,
+    pub script_sig: ScriptSig,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub witness: Vec<String>,
+    pub sequence: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedOutput {
+    pub n: usize,
+    pub value: String,
+    pub value_sat: u64,
+    pub script_pubkey: DecodedScript,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecodedTransaction {
+    pub txid: String,
+    pub wtxid: String,
+    pub version: i32,
+    pub size: usize,
+    pub vsize: usize,
+    pub weight: usize,
+    pub locktime: u32,
+    pub vin: Vec<DecodedInput>,
+    pub vout: Vec<DecodedOutput>,
+    pub total_output: String,
+}
+
+impl DecodedTransaction {
+    pub fn new(tx: &Transaction, network: Network) -> Self {
+        let vin = tx
+            .inputs
+            .iter()
+            .map(|input| DecodedInput {
+                txid: hash_to_hex(&input.previous_output.txid),
+                vout: input.previous_output.vout,
+                coinbase: input.previous_output.is_null(),
+                // coinbase的scriptSig可以是任意数据，不一定能按脚本解析
+                script_sig: ScriptSig {
+                    asm: to_asm(&input.script_sig).unwrap_or_default(),
+                    hex: u8_array_convert_string(&input.script_sig),
+                },
+                witness: input
+                    .witness
+                    .iter()
+                    .map(|item| u8_array_convert_string(item))
+                    .collect(),
+                sequence: input.sequence,
+            })
+            .collect();
+        let vout = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(n, output)| DecodedOutput {
+                n,
+                value: format_btc(output.value),
+                value_sat: output.value,
+                script_pubkey: DecodedScript::new(&output.script_pubkey, network),
+            })
+            .collect();
+        DecodedTransaction {
+            txid: tx.txid(),
+            wtxid: tx.wtxid(),
+            version: tx.version,
+            size: tx.serialize().len(),
+            vsize: tx.vsize(),
+            weight: tx.weight(),
+            locktime: tx.lock_time,
+            vin,
+            vout,
+            total_output: format_btc(tx.outputs.iter().map(|output| output.value).sum()),
+        }
+    }
+}
+
+/// 文件内容可以是raw交易hex，也可以是build-tx、psbt extract输出的json
+fn read_raw_transaction(input: &str) -> Result<String> {
+    #[derive(Deserialize)]
+    struct RawTransaction {
+        raw_transaction: String,
+    }
+
+    let content = read_input(input)?;
+    if !content.trim_start().starts_with('{') {
+        return Ok(content);
+    }
+    let raw: RawTransaction = serde_json::from_str(&content)
+        .map_err(|e| anyhow!("invalid transaction json {input}: {e}"))?;
+    Ok(raw.raw_transaction)
+}
+
+pub fn decode_transaction(
+    raw: Option<String>,
+    input: Option<String>,
+    network: String,
+    output: Option<String>,
+) -> Result<DecodedTransaction> {
+    let raw = match (raw, input) {
+        (Some(raw), _) => raw,
+        (None, Some(input)) => read_raw_transaction(&input)?,
+        (None, None) => bail!("params error: raw or input must have one."),
+    };
+    let network = Network::from_str(&network)?;
+    let tx = Transaction::parse(&parse_hex(&raw)?)?;
+    let decoded = DecodedTransaction::new(&tx, network);
+    write_output(output, &serde_json::to_string_pretty(&decoded)?)?;
+    Ok(decoded)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::btc_script::ScriptTemplate;
+
+    // 创世区块的coinbase交易
+    const GENESIS_TX: &str = "01000000010000000000000000000000000000000000000000000000000000000000000000ffffffff4d04ffff001d0104455468652054696d65732030332f4a616e2f32303039204368616e63656c6c6f72206f6e206272696e6b206f66207365636f6e64206261696c6f757420666f722062616e6b73ffffffff0100f2052a01000000434104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac00000000";
+
+    // BIP-143中native P2WPKH示例的已签名交易
+    const SEGWIT_TX: &str = "01000000000102fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f00000000494830450221008b9d1dc26ba6a9cb62127b02742fa9d754cd3bebf337f7a55d114c8e5cdd30be022040529b194ba3f9281a99f2b1c0a19c0489bc22ede944ccf4ecbab4cc618ef3ed01eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac000247304402203609e17b84f6a7d30c80bfa610b5b4542f32a8a0d5447a12fb1366d7f01cc44a0220573a954c4518331561406f90300e8f3358f51928d43c212a8caed02de67eebee0121025476c2e83188368da1ff3e292e7acafcdb3566bb0ad253f62fc70f07aeee635711000000";
+
+    #[test]
+    fn test_decode_legacy() {
+        let tx = Transaction::parse(&parse_hex(GENESIS_TX).unwrap()).unwrap();
+        assert_eq!(
+            tx.txid(),
+            "4a5e1e4baab89f3a32518a88c31bc87f618f76673e2cc77ab2127b7afdeda33b"
+        );
+        assert_eq!(tx.wtxid(), tx.txid());
+        assert_eq!(u8_array_convert_string(&tx.serialize()), GENESIS_TX);
+        let decoded = DecodedTransaction::new(&tx, Network::Mainnet);
+        assert!(decoded.vin[0].coinbase);
+        assert_eq!(decoded.size, 204);
+        assert_eq!(decoded.weight, 816);
+        assert_eq!(decoded.vsize, 204);
+        assert_eq!(decoded.vout[0].value, "50.00000000");
+        assert_eq!(decoded.vout[0].script_pubkey.template, ScriptTemplate::P2pk);
+    }
+
+    #[test]
+    fn test_decode_segwit() {
+        let data = parse_hex(SEGWIT_TX).unwrap();
+        let tx = Transaction::parse(&data).unwrap();
+        assert_eq!(tx.serialize(), data);
+        assert_eq!(tx.inputs.len(), 2);
+        assert!(tx.inputs[0].witness.is_empty());
+        assert_eq!(tx.inputs[1].witness.len(), 2);
+        assert_eq!(tx.lock_time, 17);
+        let base_size = tx.serialize_without_witness().len();
+        assert_eq!(tx.weight(), base_size * 3 + data.len());
+        assert_eq!(tx.vsize(), (base_size * 3 + data.len()).div_ceil(4));
+        assert_ne!(tx.txid(), tx.wtxid());
+        assert_eq!(
+            tx.txid(),
+            hash_to_hex(&double_sha256(&tx.serialize_without_witness()))
+        );
+        let decoded = DecodedTransaction::new(&tx, Network::Testnet);
+        assert_eq!(decoded.vout[0].value_sat, 112340000);
+        assert_eq!(
+            decoded.vout[0].script_pubkey.address.as_deref(),
+            Some("msQzKJatdWdw4rpy8sbv8puHoncseekYCf")
+        );
+        assert_eq!(
+            decoded.vin[0].txid,
+            "9f96ade4b41d5433f4eda31e1738ec2b36f6e7d1420d94a6af99801a88f7f7ff"
+        );
+    }
+
+    #[test]
+    fn test_segwit_v0_sighash() {
+        // BIP-143 native P2WPKH示例，第二个输入为P2WPKH
+        let unsigned = "0100000002fff7f7881a8099afa6940d42d1e7f6362bec38171ea3edf433541db4e4ad969f0000000000eeffffffef51e1b804cc89d182d279655c3aa89e815b1b309fe287d9b2b55d57b90ec68a0100000000ffffffff02202cb206000000001976a9148280b37df378db99f66f85c95a783a76ac7a6d5988ac9093510d000000001976a9143bde42dbee7e4dbe6a21b2d50ce2f0167faa815988ac11000000";
+        let tx = Transaction::parse(&parse_hex(unsigned).unwrap()).unwrap();
+        let script_code = parse_hex("76a9141d0f172a0ecb48aee1be1f2687d2963ae33f71a188ac").unwrap();
+        assert_eq!(
+            u8_array_convert_string(&tx.segwit_v0_sighash(1, &script_code, 600000000)),
+            "c37af31116d1b27caf68aae9e3ac82f1477929014d5b917657d0eb49478cb670"
+        );
+        assert!(tx.taproot_key_spend_sighash(0, &[]).is_err());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(Transaction::parse(&parse_hex("0100000001").unwrap()).is_err());
+        let mut data = parse_hex(GENESIS_TX).unwrap();
+        data.push(0);
+        assert!(Transaction::parse(&data).is_err());
+        // 超大的varint长度
+        assert!(Transaction::parse(&parse_hex("01000000ffffffffffffffffff").unwrap()).is_err());
+        assert!(parse_hex("0x123").is_err());
+    }
+}
@@ -2,6 +2,21 @@ use clap::builder::Str;
 use clap::{Args, Parser, Subcommand};
 use ethers::types::U128;
 
+use crate::kdf::{DEFAULT_PBKDF2_C, DEFAULT_SCRYPT_N, DEFAULT_SCRYPT_P, DEFAULT_SCRYPT_R};
+use crate::output::OutputFormat;
+
+#[derive(Args, Debug)]
+pub struct EthArgs {
+    /// 配置文件中的网络，如 mainnet、sepolia、bsc，未指定时使用default_network
+    #[arg(long, global = true)]
+    pub network: Option<String>,
+    /// 默认为 ~/.config/tool/config.toml
+    #[arg(long, global = true)]
+    pub config: Option<String>,
+    #[command(subcommand)]
+    pub command: EthSubCommands,
+}
+
 #[derive(Subcommand, Debug)]
 pub enum EthSubCommands {
     Sec2Address {
@@ -17,10 +32,11 @@ pub enum EthSubCommands {
     ChainInfo {
         #[arg(short = 's', long, default_value = "address")]
         address: String,
-        #[arg(short = 'k', long, default_value = "api_key")]
-        api_key: String,
+        /// 默认使用网络配置中的explorer_api_key
+        #[arg(short = 'k', long)]
+        api_key: Option<String>,
         #[arg(short = 'i', long)]
-        chain_id: u64,
+        chain_id: Option<u64>,
     },
     Bip32 {
         #[arg(short = 's', long, default_value = "x_private_key")]
@@ -44,37 +60,92 @@ pub enum EthSubCommands {
         #[arg(short = 't', long, default_value = "path")]
         path: String,
     },
+    KeystoreCreate {
+        #[arg(short = 'k', long, default_value = "scrypt")]
+        kdf: String,
+        #[arg(short = 'r', long)]
+        random: bool,
+        #[arg(short = 'p', long)]
+        password_file: Option<String>,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    KeystoreDecrypt {
+        #[arg(short = 'k', long, default_value = "keystore.json")]
+        keystore: String,
+        #[arg(short = 'p', long)]
+        password_file: Option<String>,
+    },
     Transfer {
-        #[arg(short = 's', long, default_value = "private_key")]
-        private_key: String,
-        #[arg(short = 'r', long, default_value = "rpc host")]
-        rpc_url: String,
+        #[arg(short = 's', long, conflicts_with = "keystore")]
+        private_key: Option<String>,
+        #[arg(short = 'k', long)]
+        keystore: Option<String>,
+        #[arg(long)]
+        password_file: Option<String>,
+        #[arg(short = 'r', long)]
+        rpc_url: Option<String>,
         #[arg(short = 't', long, default_value = "destination")]
         to: String,
         #[arg(short = 'v', long)]
         value: u128,
         #[arg(short = 'i', long)]
-        chain_id: u32,
+        chain_id: Option<u64>,
         #[arg(short = 'b', long)]
         is_broadcast: bool,
         #[arg(short = 'c', long)]
         contract_address: Option<String>,
+        #[arg(short = 'd', long)]
+        data: Option<String>,
+        #[arg(short = 'a', long)]
+        abi: Option<String>,
+        #[arg(short = 'f', long)]
+        function: Option<String>,
+        #[arg(long, num_args = 1.., allow_negative_numbers = true)]
+        args: Vec<String>,
         #[arg(short = 'p', long)]
-        gas_price: Option<u128>,
+        gas_price: Option<String>,
+        #[arg(long)]
+        max_fee_per_gas: Option<String>,
+        #[arg(long)]
+        max_priority_fee_per_gas: Option<String>,
         #[arg(short = 'l', long)]
         gas_limit: Option<u128>,
         #[arg(short = 'n', long)]
         nonce: Option<u128>,
     },
+    TxBuild(TxBuildArgs),
+    TxSign {
+        #[arg(short = 'f', long, default_value = "-")]
+        input: String,
+        #[arg(short = 's', long, conflicts_with = "keystore")]
+        private_key: Option<String>,
+        #[arg(short = 'k', long)]
+        keystore: Option<String>,
+        #[arg(long)]
+        password_file: Option<String>,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    TxBroadcast {
+        #[arg(short = 'r', long)]
+        rpc_url: Option<String>,
+        #[arg(short = 'x', long)]
+        raw: Option<String>,
+        #[arg(short = 'f', long)]
+        input: Option<String>,
+    },
     Amount {
-        #[arg(short = 'r', long, default_value = "rpc host")]
-        rpc_url: String,
+        #[arg(short = 'r', long)]
+        rpc_url: Option<String>,
         #[arg(short = 'a', long, default_value = "destination")]
         address: String,
-        #[arg(short = 'p', long, default_value = "gas_price/GWei")]
-        gas_price: String,
-        #[arg(short = 'l', long, default_value = "destination")]
-        gas_limit: String,
+        /// 单位GWei，默认使用网络配置中的gas_price或max_fee_per_gas
+        #[arg(short = 'p', long)]
+        gas_price: Option<String>,
+        /// 默认使用网络配置中的gas_limit，都没有时为21000
+        #[arg(short = 'l', long)]
+        gas_limit: Option<u128>,
         #[arg(short = 'b', long)]
         block_id: Option<u64>,
     },
@@ -94,6 +165,159 @@ pub enum EthSubCommands {
         #[arg(short = 'n', long)]
         func_name: Option<String>,
     },
+    Call(CallArgs),
+    Logs(LogsArgs),
+    Balances(BalancesArgs),
+    EncodeCall {
+        #[arg(short = 's', long, default_value = "transfer(address,uint256)")]
+        sig: String,
+        #[arg(short = 'a', long, num_args = 1.., allow_negative_numbers = true)]
+        args: Vec<String>,
+    },
+    SignMessage {
+        #[arg(short = 's', long, conflicts_with = "keystore")]
+        private_key: Option<String>,
+        #[arg(short = 'k', long)]
+        keystore: Option<String>,
+        #[arg(long)]
+        password_file: Option<String>,
+        #[arg(short = 'm', long)]
+        message: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    SignTypedData {
+        #[arg(short = 's', long, conflicts_with = "keystore")]
+        private_key: Option<String>,
+        #[arg(short = 'k', long)]
+        keystore: Option<String>,
+        #[arg(long)]
+        password_file: Option<String>,
+        #[arg(short = 'f', long, default_value = "-")]
+        input: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    Recover {
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+        #[arg(short = 'f', long)]
+        typed_data: Option<String>,
+        #[arg(long)]
+        hash: Option<String>,
+        #[arg(short = 'g', long)]
+        signature: String,
+    },
+    Verify {
+        #[arg(short = 'm', long)]
+        message: Option<String>,
+        #[arg(short = 'f', long)]
+        typed_data: Option<String>,
+        #[arg(long)]
+        hash: Option<String>,
+        #[arg(short = 'g', long)]
+        signature: String,
+        #[arg(short = 'a', long)]
+        address: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct TxBuildArgs {
+    #[arg(short = 'y', long, default_value = "eip1559")]
+    pub tx_type: String,
+    #[arg(short = 'f', long)]
+    pub from: Option<String>,
+    #[arg(short = 't', long, default_value = "destination")]
+    pub to: String,
+    #[arg(short = 'v', long, default_value_t = 0)]
+    pub value: u128,
+    #[arg(short = 'd', long)]
+    pub data: Option<String>,
+    #[arg(short = 'i', long)]
+    pub chain_id: Option<u64>,
+    #[arg(short = 'n', long)]
+    pub nonce: Option<u64>,
+    #[arg(short = 'l', long)]
+    pub gas_limit: Option<u128>,
+    #[arg(short = 'p', long)]
+    pub gas_price: Option<String>,
+    #[arg(long)]
+    pub max_fee_per_gas: Option<String>,
+    #[arg(long)]
+    pub max_priority_fee_per_gas: Option<String>,
+    #[arg(short = 'a', long)]
+    pub access_list: Option<String>,
+    #[arg(short = 'r', long)]
+    pub rpc_url: Option<String>,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct CallArgs {
+    #[arg(short = 'r', long)]
+    pub rpc_url: Option<String>,
+    #[arg(short = 't', long)]
+    pub to: String,
+    #[arg(short = 'f', long, default_value = "balanceOf(address)(uint256)")]
+    pub function: String,
+    #[arg(short = 'a', long)]
+    pub abi: Option<String>,
+    #[arg(long, num_args = 1.., allow_negative_numbers = true)]
+    pub args: Vec<String>,
+    #[arg(short = 'b', long, default_value = "latest")]
+    pub block: String,
+    #[arg(long)]
+    pub from: Option<String>,
+    #[arg(short = 'F', long, default_value = "json")]
+    pub format: String,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct LogsArgs {
+    #[arg(short = 'r', long)]
+    pub rpc_url: Option<String>,
+    #[arg(short = 'a', long)]
+    pub address: Option<String>,
+    #[arg(long)]
+    pub abi: Option<String>,
+    #[arg(short = 'e', long)]
+    pub event: Option<String>,
+    #[arg(long)]
+    pub topic1: Option<String>,
+    #[arg(long)]
+    pub topic2: Option<String>,
+    #[arg(long)]
+    pub topic3: Option<String>,
+    #[arg(short = 'f', long, default_value_t = 0)]
+    pub from_block: u64,
+    #[arg(short = 't', long)]
+    pub to_block: Option<u64>,
+    #[arg(short = 'p', long, default_value_t = 2000)]
+    pub page_size: u64,
+    #[arg(short = 'F', long, default_value = "json")]
+    pub format: String,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct BalancesArgs {
+    #[arg(short = 'r', long)]
+    pub rpc_url: Option<String>,
+    #[arg(short = 'a', long, default_value = "-")]
+    pub addresses: String,
+    #[arg(short = 't', long, value_delimiter = ',')]
+    pub tokens: Vec<String>,
+    #[arg(short = 'c', long, default_value_t = 8)]
+    pub concurrency: usize,
+    #[arg(short = 'b', long, default_value = "latest")]
+    pub block: String,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -120,6 +344,38 @@ pub enum BtcSubCommands {
         #[arg(short = 'a', long, default_value = "raw_tx")]
         raw_tx: String,
     },
+    #[command(subcommand)]
+    Script(ScriptSubCommands),
+    /// 由UTXO列表构建并签名交易，支持P2PKH、P2SH-P2WPKH、P2WPKH和P2TR输入
+    BuildTx(BuildTxArgs),
+    /// 由输出描述符派生地址，支持pkh、wpkh、sh(wpkh)、tr、sh/wsh/sh(wsh)包装的multi和sortedmulti
+    Descriptor {
+        #[arg(short = 'd', long)]
+        descriptor: String,
+        /// 描述符以 /* 结尾时的起始index
+        #[arg(short = 's', long, default_value_t = 0)]
+        start: u32,
+        #[arg(short = 'c', long, default_value_t = 10)]
+        count: u32,
+        #[arg(short = 'n', long, default_value = "mainnet")]
+        network: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// base64格式的PSBT文件，解析、签名、合并、finalize和提取交易
+    #[command(subcommand)]
+    Psbt(PsbtSubCommands),
+    /// 解析legacy和segwit交易，输出JSON
+    DecodeTx {
+        #[arg(short = 'x', long)]
+        raw: Option<String>,
+        #[arg(short = 'f', long)]
+        input: Option<String>,
+        #[arg(short = 'n', long, default_value = "mainnet")]
+        network: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
     Bip32 {
         #[arg(short = 's', long, default_value = "x_private_key")]
         x_private_key: Option<String>,
@@ -134,6 +390,238 @@ pub enum BtcSubCommands {
         #[arg(short = 'p', long, default_value = "passphrase")]
         passphrase: String,
     },
+    /// 在SLIP-132的xpub/ypub/zpub/tpub/upub/vpub及多签Y/Z/U/V前缀之间转换扩展密钥
+    XkeyConvert {
+        #[arg(short = 'k', long)]
+        key: String,
+        #[arg(short = 't', long, default_value = "xpub")]
+        target: String,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct BuildTxArgs {
+    /// UTXO的JSON文件，[{"txid", "vout", "amount"(sat), "path"}]，`-` 表示从stdin读取
+    #[arg(short = 'u', long, default_value = "-")]
+    pub utxos: String,
+    /// 根扩展私钥，UTXO的path从该节点派生
+    #[arg(short = 'x', long)]
+    pub x_prv: String,
+    /// 目标地址和金额(sat)，格式为 address:amount，可以指定多个
+    #[arg(short = 't', long, required = true)]
+    pub to: Vec<String>,
+    /// sat/vB，不能超过10000
+    #[arg(short = 'f', long)]
+    pub fee_rate: f64,
+    #[arg(long)]
+    pub change_address: Option<String>,
+    #[arg(long)]
+    pub change_path: Option<String>,
+    #[arg(short = 'n', long, default_value = "mainnet")]
+    pub network: String,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ScriptSubCommands {
+    /// 脚本hex解析为ASM，并识别P2PKH、P2WPKH、多签、OP_RETURN等模板
+    Decode {
+        script: String,
+        #[arg(short = 'n', long, default_value = "mainnet")]
+        network: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// 由ASM生成脚本hex，如 "OP_DUP OP_HASH160 <hash> OP_EQUALVERIFY OP_CHECKSIG"
+    Encode { asm: String },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PsbtSubCommands {
+    /// 输出输入输出、手续费和每个输入的签名状态
+    Decode {
+        #[arg(short = 'i', long, default_value = "-")]
+        input: String,
+        #[arg(short = 'n', long, default_value = "mainnet")]
+        network: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// 用根扩展私钥签名，按BIP-32派生信息中的指纹和路径匹配公钥
+    Sign {
+        #[arg(short = 'i', long, default_value = "-")]
+        input: String,
+        #[arg(short = 'x', long)]
+        x_prv: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// 合并多个签名后的PSBT，未签名交易必须相同
+    Combine {
+        #[arg(short = 'i', long, required = true)]
+        inputs: Vec<String>,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    Finalize {
+        #[arg(short = 'i', long, default_value = "-")]
+        input: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// 所有输入finalize后提取可广播的交易
+    Extract {
+        #[arg(short = 'i', long, default_value = "-")]
+        input: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MnemonicSubCommands {
+    /// 默认使用系统随机数生成24个单词，也可以由hex熵或骰子投掷结果生成
+    Generate {
+        /// 12/15/18/21/24，指定hex熵时由熵的长度决定
+        #[arg(short = 'w', long)]
+        words: Option<usize>,
+        /// english, chinese-simplified, chinese-traditional, czech, french, italian, japanese, korean, spanish
+        #[arg(short = 'l', long, default_value = "english")]
+        language: String,
+        #[arg(long, conflicts_with = "dice")]
+        hex: Option<String>,
+        /// 1-6的投掷结果，如 "3615224..."
+        #[arg(long)]
+        dice: Option<String>,
+        #[arg(short = 'p', long, default_value = "")]
+        passphrase: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    /// 校验单词和checksum，对拼错的单词给出建议
+    Validate {
+        #[arg(short = 'm', long)]
+        mnemonic: String,
+        /// 默认按单词自动识别
+        #[arg(short = 'l', long)]
+        language: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AddressSubCommands {
+    /// 识别ETH和BTC地址，校验checksum并输出hash/witness program和scriptPubKey
+    Inspect {
+        address: String,
+        /// 只对BTC地址有效，指定时校验地址是否属于该网络
+        #[arg(short = 'n', long)]
+        network: Option<String>,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum SeedSubCommands {
+    /// 拆分为n份，任意k份可以恢复，每份编码为BIP-39英文单词
+    Split {
+        #[arg(short = 'm', long)]
+        mnemonic: String,
+        #[arg(short = 't', long)]
+        threshold: u8,
+        #[arg(short = 'n', long)]
+        shares: u8,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+    Combine {
+        /// 可以指定多个
+        #[arg(short = 's', long)]
+        shares: Vec<String>,
+        /// 每行一个份额，`-` 表示从stdin读取
+        #[arg(short = 'i', long)]
+        input: Option<String>,
+        /// 恢复的助记词使用的语言
+        #[arg(short = 'l', long, default_value = "english")]
+        language: String,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(Args, Debug)]
+pub struct VanityArgs {
+    /// eth 或 btc
+    #[arg(short = 'c', long, default_value = "eth")]
+    pub chain: String,
+    /// BTC的前缀需要包含地址固定的开头，如 1、3、bc1q
+    #[arg(short = 'p', long, default_value = "")]
+    pub prefix: String,
+    #[arg(short = 's', long, default_value = "")]
+    pub suffix: String,
+    /// ETH按EIP-55校验和地址匹配大小写
+    #[arg(long)]
+    pub case_sensitive: bool,
+    /// btc的地址类型：p2pkh, p2sh-p2wpkh, p2wpkh, p2tr
+    #[arg(short = 'a', long, default_value = "p2pkh")]
+    pub address_type: String,
+    #[arg(short = 'n', long, default_value = "mainnet")]
+    pub network: String,
+    /// 0表示使用所有CPU核心
+    #[arg(short = 't', long, default_value_t = 0)]
+    pub threads: usize,
+    /// 指定时从助记词路径下按index派生，而不是随机生成私钥
+    #[arg(short = 'm', long)]
+    pub mnemonic: Option<String>,
+    #[arg(long, default_value = "")]
+    pub passphrase: String,
+    /// 默认 m/44'/60'/0'/0，btc按地址类型的purpose
+    #[arg(long)]
+    pub path: Option<String>,
+    #[arg(long, default_value_t = 0)]
+    pub start: u32,
+    #[arg(long, default_value_t = 1_000_000)]
+    pub count: u32,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct DeriveArgs {
+    #[arg(short = 'c', long, default_value = "btc")]
+    pub chain: String,
+    #[arg(short = 'm', long)]
+    pub mnemonic: Option<String>,
+    #[arg(short = 'p', long, default_value = "")]
+    pub passphrase: String,
+    #[arg(short = 'x', long)]
+    pub x_pub: Option<String>,
+    /// 默认由扩展公钥前缀推断，助记词派生时为84
+    #[arg(long)]
+    pub purpose: Option<u32>,
+    /// 扩展公钥已经是账户层级，不能再指定account和coin type
+    #[arg(short = 'a', long, default_value_t = 0, conflicts_with = "x_pub")]
+    pub account: u32,
+    #[arg(long, conflicts_with = "x_pub")]
+    pub coin_type: Option<u32>,
+    /// 默认由扩展公钥前缀推断，助记词派生时为mainnet
+    #[arg(short = 'n', long)]
+    pub network: Option<String>,
+    #[arg(long, default_value = "both")]
+    pub change: String,
+    #[arg(short = 's', long, default_value_t = 0)]
+    pub start: u32,
+    #[arg(short = 'l', long, default_value_t = 20)]
+    pub count: u32,
+    #[arg(short = 'u', long)]
+    pub used: Option<String>,
+    #[arg(short = 'g', long, default_value_t = 20)]
+    pub gap_limit: u32,
+    #[arg(short = 'F', long, default_value = "json")]
+    pub format: String,
+    #[arg(short = 'o', long)]
+    pub output: Option<String>,
 }
 
 #[derive(Subcommand, Debug)]
@@ -143,18 +631,26 @@ pub(crate) enum SubCommands {
         plaintext: String,
         #[arg(short = 'p', long, default_value = "password")]
         password: String,
+        #[arg(short = 'c', long, default_value = "aes-256-gcm")]
+        cipher: String,
+        #[arg(short = 'k', long, default_value = "scrypt")]
+        kdf: String,
+        #[arg(long, default_value_t = DEFAULT_SCRYPT_N)]
+        scrypt_n: u64,
+        #[arg(long, default_value_t = DEFAULT_SCRYPT_R)]
+        scrypt_r: u32,
+        #[arg(long, default_value_t = DEFAULT_SCRYPT_P)]
+        scrypt_p: u32,
+        #[arg(long, default_value_t = DEFAULT_PBKDF2_C)]
+        pbkdf2_c: u32,
+        #[arg(short = 'o', long)]
+        output: Option<String>,
     },
     Decrypt {
-        #[arg(short = 'c', long, default_value = "cipher")]
-        cipher: String,
+        #[arg(short = 'i', long, default_value = "-")]
+        input: String,
         #[arg(short = 'p', long, default_value = "password")]
         password: String,
-        #[arg(short = 'i', long, default_value = "iv")]
-        iv: String,
-        #[arg(short = 't', long, default_value = "tag")]
-        tag: String,
-        #[arg(short = 'a', long, default_value = "aad")]
-        aad: String,
     },
     Random {
         #[arg(short = 'i', long)]
@@ -182,15 +678,40 @@ pub(crate) enum SubCommands {
         #[arg(short = 'k', long, default_value = "BSC")]
         key_word: String,
     },
+    Derive(DeriveArgs),
+    /// BIP-39助记词生成和校验
+    #[command(subcommand)]
+    Mnemonic(MnemonicSubCommands),
+    /// 用GF(256) Shamir方案拆分和恢复助记词
     #[command(subcommand)]
-    Eth(EthSubCommands),
+    Seed(SeedSubCommands),
+    /// 多线程搜索匹配前缀/后缀的ETH或BTC地址
+    Vanity(VanityArgs),
+    #[command(subcommand)]
+    Address(AddressSubCommands),
+    Eth(Box<EthArgs>),
     #[command(subcommand)]
     Btc(BtcSubCommands),
+    /// 交互模式，复用子命令的参数，`set` 保存的密钥只在会话内存中，历史记录不保存密钥参数
+    Shell {
+        /// 会话默认的eth网络
+        #[arg(long)]
+        network: Option<String>,
+        /// 默认为 ~/.config/tool/config.toml
+        #[arg(long)]
+        config: Option<String>,
+        /// 默认为 ~/.config/tool/history
+        #[arg(long)]
+        history: Option<String>,
+    },
 }
 
 #[derive(Parser, Debug)]
 #[command(version)]
 pub struct Cli {
+    /// 结果输出到stdout的格式，日志输出到stderr。text格式下psbt、交易、envelope等原样输出便于管道，json格式下包装为对象
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    pub output_format: OutputFormat,
     #[command(subcommand)]
     pub(crate) command: SubCommands,
 }
@@ -0,0 +1,318 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tracing::debug;
+
+use ethers::types::U256;
+use ethers::utils::parse_units;
+
+use crate::eth_tx::FeeOptions;
+
+/// 默认配置文件相对于home目录的路径
+const DEFAULT_CONFIG_PATH: &str = ".config/tool/config.toml";
+
+/// 配置文件，如
+/// ```toml
+/// default_network = "sepolia"
+///
+/// [networks.sepolia]
+/// rpc_url = "https://ethereum-sepolia.publicnode.com"
+/// chain_id = 11155111
+/// explorer_api_key = "..."
+/// max_fee_per_gas = "30 gwei"
+/// max_priority_fee_per_gas = "1 gwei"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub default_network: Option<String>,
+    #[serde(default)]
+    pub networks: BTreeMap<String, NetworkProfile>,
+}
+
+/// 命名网络的默认参数，命令行参数优先于配置
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetworkProfile {
+    #[serde(skip)]
+    pub name: Option<String>,
+    pub rpc_url: Option<String>,
+    pub chain_id: Option<u64>,
+    pub explorer_api_key: Option<String>,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    /// toml不支持u128，使用时再转换
+    pub gas_limit: Option<u64>,
+}
+
+impl Config {
+    pub fn parse(content: &str) -> Result<Self> {
+        toml::from_str(content).map_err(|e| anyhow!("invalid config: {e}"))
+    }
+
+    /// 未指定路径时读取 ~/.config/tool/config.toml，默认配置文件不存在时使用空配置
+    pub fn load(path: Option<&str>) -> Result<Self> {
+        let path = match path {
+            Some(path) => PathBuf::from(path),
+            None => match dirs::home_dir().map(|home| home.join(DEFAULT_CONFIG_PATH)) {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            },
+        };
+        debug!("load config from {}", path.display());
+        let content = fs::read_to_string(&path)
+            .map_err(|e| anyhow!("read config {} failed: {e}", path.display()))?;
+        Self::parse(&content).map_err(|e| anyhow!("{}: {e}", path.display()))
+    }
+
+    /// 按名称选择网络，未指定时使用default_network，配置中的字段覆盖内置的chain id
+    pub fn profile(&self, network: Option<&str>) -> Result<NetworkProfile> {
+        let Some(name) = network.or(self.default_network.as_deref()) else {
+            return Ok(NetworkProfile::default());
+        };
+        let name = name.to_lowercase();
+        let builtin = NetworkProfile::builtin(&name);
+        let profile = match (self.networks.get(&name), builtin) {
+            (Some(profile), builtin) => NetworkProfile {
+                chain_id: profile
+                    .chain_id
+                    .or(builtin.and_then(|builtin| builtin.chain_id)),
+                ..profile.clone()
+            },
+            (None, Some(builtin)) => builtin,
+            (None, None) => bail!("network {name} not found in config."),
+        };
+        Ok(NetworkProfile {
+            name: Some(name),
+            ..profile
+        })
+    }
+}
+
+impl NetworkProfile {
+    /// 常用网络内置chain id，rpc地址等仍需在配置或命令行中指定
+    pub fn builtin(name: &str) -> Option<Self> {
+        let chain_id = match name {
+            "mainnet" | "ethereum" => 1,
+            "sepolia" => 11155111,
+            "holesky" => 17000,
+            "bsc" => 56,
+            "bsc-testnet" => 97,
+            _ => return None,
+        };
+        Some(NetworkProfile {
+            chain_id: Some(chain_id),
+            ..Default::default()
+        })
+    }
+
+    fn missing(&self, field: &str) -> anyhow::Error {
+        match &self.name {
+            Some(name) => {
+                anyhow!("{field} is required: pass it as a flag or set it in network {name}.")
+            }
+            None => anyhow!(
+                "{field} is required: pass it as a flag or select a network with --network."
+            ),
+        }
+    }
+
+    pub fn rpc_url(&self, flag: Option<String>) -> Result<String> {
+        flag.or_else(|| self.rpc_url.clone())
+            .ok_or_else(|| self.missing("rpc_url"))
+    }
+
+    pub fn chain_id(&self, flag: Option<u64>) -> Result<u64> {
+        flag.or(self.chain_id)
+            .ok_or_else(|| self.missing("chain_id"))
+    }
+
+    pub fn explorer_api_key(&self, flag: Option<String>) -> Result<String> {
+        flag.or_else(|| self.explorer_api_key.clone())
+            .ok_or_else(|| self.missing("api_key"))
+    }
+
+    pub fn gas_limit(&self, flag: Option<u128>) -> Option<u128> {
+        flag.or(self.gas_limit.map(u128::from))
+    }
+
+    /// 每单位gas最多支付的费用，命令行的值单位为GWei，配置中优先取gas_price，其次为max_fee_per_gas
+    pub fn fee_cap(&self, gas_price_gwei: Option<String>) -> Result<U256> {
+        if let Some(gas_price) = gas_price_gwei {
+            return Ok(parse_units(gas_price.trim(), "gwei")?.into());
+        }
+        let fees = self.fees(None, None, None)?;
+        fees.gas_price
+            .or(fees.max_fee_per_gas)
+            .ok_or_else(|| self.missing("gas_price"))
+    }
+
+    /// 命令行指定了任意费用参数时只使用命令行的费用，避免legacy和eip1559费用混用
+    pub fn fees(
+        &self,
+        gas_price: Option<String>,
+        max_fee_per_gas: Option<String>,
+        max_priority_fee_per_gas: Option<String>,
+    ) -> Result<FeeOptions> {
+        if gas_price.is_some() || max_fee_per_gas.is_some() || max_priority_fee_per_gas.is_some() {
+            return FeeOptions::parse(gas_price, max_fee_per_gas, max_priority_fee_per_gas);
+        }
+        FeeOptions::parse(
+            self.gas_price.clone(),
+            self.max_fee_per_gas.clone(),
+            self.max_priority_fee_per_gas.clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const CONFIG: &str = r#"
+default_network = "sepolia"
+
+[networks.sepolia]
+rpc_url = "https://sepolia.example"
+explorer_api_key = "key"
+max_fee_per_gas = "30 gwei"
+max_priority_fee_per_gas = "1 gwei"
+gas_limit = 21000
+
+[networks.local]
+rpc_url = "http://127.0.0.1:8545"
+chain_id = 31337
+gas_price = "1 gwei"
+"#;
+
+    #[test]
+    fn test_profile() {
+        let config = Config::parse(CONFIG).unwrap();
+        // 默认网络，chain id来自内置配置
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name.as_deref(), Some("sepolia"));
+        assert_eq!(profile.chain_id(None).unwrap(), 11155111);
+        assert_eq!(profile.rpc_url(None).unwrap(), "https://sepolia.example");
+        assert_eq!(profile.explorer_api_key(None).unwrap(), "key");
+        assert_eq!(profile.gas_limit(None), Some(21000));
+
+        let profile = config.profile(Some("local")).unwrap();
+        assert_eq!(profile.chain_id(None).unwrap(), 31337);
+        // 内置网络不需要配置
+        let profile = config.profile(Some("BSC")).unwrap();
+        assert_eq!(profile.chain_id(None).unwrap(), 56);
+        assert!(profile.rpc_url(None).is_err());
+        assert!(config.profile(Some("unknown")).is_err());
+
+        assert!(Config::parse("[networks.sepolia]\nrpc = \"x\"").is_err());
+        assert!(Config::default()
+            .profile(None)
+            .unwrap()
+            .chain_id(None)
+            .is_err());
+    }
+
+    #[test]
+    fn test_flag_override() {
+        let config = Config::parse(CONFIG).unwrap();
+        let profile = config.profile(Some("sepolia")).unwrap();
+        assert_eq!(
+            profile.rpc_url(Some("http://other".to_string())).unwrap(),
+            "http://other"
+        );
+        assert_eq!(profile.chain_id(Some(1)).unwrap(), 1);
+        assert_eq!(profile.gas_limit(Some(50000)), Some(50000));
+
+        let fees = profile.fees(None, None, None).unwrap();
+        assert_eq!(fees.max_fee_per_gas, Some(U256::from(30_000_000_000u64)));
+        assert_eq!(
+            fees.max_priority_fee_per_gas,
+            Some(U256::from(1_000_000_000u64))
+        );
+        // 命令行的legacy费用不与配置中的eip1559费用混用
+        let fees = profile
+            .fees(Some("2 gwei".to_string()), None, None)
+            .unwrap();
+        assert_eq!(fees.gas_price, Some(U256::from(2_000_000_000u64)));
+        assert_eq!(fees.max_fee_per_gas, None);
+
+        assert_eq!(
+            profile.fee_cap(None).unwrap(),
+            U256::from(30_000_000_000u64)
+        );
+        assert_eq!(
+            profile.fee_cap(Some("5".to_string())).unwrap(),
+            U256::from(5_000_000_000u64)
+        );
+    }
+
+    #[test]
+    fn test_network_flag() {
+        use crate::cli::{Cli, SubCommands};
+        use clap::Parser;
+
+        // --network 和 --config 可以放在eth的子命令之后
+        let cli = Cli::try_parse_from([
+            "tool",
+            "eth",
+            "chain-info",
+            "-s",
+            "0x0",
+            "--network",
+            "bsc",
+            "--config",
+            "tool.toml",
+        ])
+        .unwrap();
+        let SubCommands::Eth(args) = cli.command else {
+            panic!("expect eth command");
+        };
+        assert_eq!(args.network.as_deref(), Some("bsc"));
+        assert_eq!(args.config.as_deref(), Some("tool.toml"));
+    }
+
+    #[test]
+    fn test_load_lazily() {
+        use crate::output::{pipe, run_cli};
+
+        // 离线命令不读取配置文件
+        let missing = "/nonexistent/tool/config.toml";
+        let private_key = "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+        assert!(run_cli(&[
+            "eth",
+            "--config",
+            missing,
+            "sec2-address",
+            "-s",
+            private_key
+        ])
+        .is_ok());
+        assert!(run_cli(&["eth", "--config", missing, "tx-build", "-t", "0x0"]).is_err());
+
+        // 默认网络的rpc地址不会让tx-build连接节点，显式指定--network时才会
+        let config = pipe(
+            "config.toml",
+            "default_network = \"local\"\n[networks.local]\nrpc_url = \"http://127.0.0.1:1\"\nchain_id = 31337\n",
+        );
+        let args = [
+            "--output-format",
+            "json",
+            "eth",
+            "--config",
+            &config,
+            "tx-build",
+            "-t",
+            "0x0000000000000000000000000000000000000001",
+            "-f",
+            "0x0000000000000000000000000000000000000002",
+        ];
+        let unsigned: serde_json::Value = serde_json::from_str(&run_cli(&args).unwrap()).unwrap();
+        let unsigned = &unsigned["unsigned_transaction"];
+        assert_eq!(unsigned["chainId"], 31337);
+        assert!(unsigned["transaction"]["nonce"].is_null());
+        assert!(run_cli(&[&args[..], &["--network", "local"]].concat()).is_err());
+    }
+}
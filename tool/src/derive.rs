@@ -0,0 +1,452 @@
+use anyhow::{anyhow, bail, Result};
+use bip32::{PublicKey as Bip32PubKey, XPub};
+use bip39::Mnemonic;
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+use secp256k1::PublicKey;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::bip32::{derive_x_prv, derive_x_pub_child, mnemonic_to_x_prv, parse_x_pub, Slip132};
+use crate::btc::{public_key_to_address, AddressType, Network};
+use crate::cli::DeriveArgs;
+use crate::encrypt_decrypt::read_input;
+use crate::file_handle::{output_records, RecordFormat};
+use crate::util::u8_array_convert_string;
+
+const ETH_COIN_TYPE: u32 = 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chain {
+    Btc,
+    Eth,
+}
+
+impl FromStr for Chain {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "btc" => Ok(Chain::Btc),
+            "eth" => Ok(Chain::Eth),
+            _ => Err(anyhow!("chain {s} not supported.")),
+        }
+    }
+}
+
+/// 地址的编码方式，BTC按purpose对应的地址类型，ETH为checksum地址
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressFormat {
+    Btc(AddressType, Network),
+    Eth,
+}
+
+impl AddressFormat {
+    pub fn encode(&self, public_key: &PublicKey) -> Result<String> {
+        match self {
+            AddressFormat::Btc(address_type, network) => {
+                public_key_to_address(public_key, *address_type, *network)
+            }
+            AddressFormat::Eth => {
+                let address = Address::from_str(&crate::eth::pub_key_to_address(*public_key))?;
+                Ok(to_checksum(&address, None))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DerivedAddress {
+    pub path: String,
+    pub address: String,
+    pub public_key: String,
+    pub used: bool,
+}
+
+/// 账户级别的扩展公钥及其路径，如 m/84'/0'/0'
+pub struct AccountKey {
+    pub path: String,
+    pub x_pub: XPub,
+}
+
+impl AccountKey {
+    pub fn from_mnemonic(
+        mnemonic: &str,
+        passphrase: &str,
+        purpose: u32,
+        coin_type: u32,
+        account: u32,
+    ) -> Result<Self> {
+        Mnemonic::from_str(mnemonic).map_err(|e| anyhow!("invalid mnemonic: {e}"))?;
+        let root = mnemonic_to_x_prv(mnemonic.to_string(), passphrase.to_string());
+        let path = format!("m/{purpose}'/{coin_type}'/{account}'");
+        let x_prv = derive_x_prv(&root, &path)?;
+        Ok(AccountKey {
+            path,
+            x_pub: x_prv.public_key(),
+        })
+    }
+
+    /// 扩展公钥应为账户级别(depth 3)，无法得知完整路径时只记录相对路径，同时返回前缀对应的SLIP-132版本
+    pub fn from_x_pub(x_pub: &str) -> Result<(Self, Slip132)> {
+        let (x_pub, version) = parse_x_pub(x_pub)?;
+        if x_pub.attrs().depth != 3 {
+            info!(
+                "extended public key depth is {}, expect an account level key.",
+                x_pub.attrs().depth
+            );
+        }
+        Ok((
+            AccountKey {
+                path: "M".to_string(),
+                x_pub,
+            },
+            version,
+        ))
+    }
+
+    pub fn derive(&self, change: u32, index: u32, format: AddressFormat) -> Result<DerivedAddress> {
+        let child = derive_x_pub_child(&derive_x_pub_child(&self.x_pub, change)?, index)?;
+        let public_key = PublicKey::from_slice(&child.public_key().to_bytes())?;
+        Ok(DerivedAddress {
+            path: format!("{}/{change}/{index}", self.path),
+            address: format.encode(&public_key)?,
+            public_key: u8_array_convert_string(&public_key.serialize()),
+            used: false,
+        })
+    }
+}
+
+/// 已使用的地址列表，用于gap limit扫描
+pub struct UsedAddresses {
+    pub addresses: HashSet<String>,
+    pub gap_limit: u32,
+}
+
+impl UsedAddresses {
+    /// 每行一个地址，忽略空行和#开头的注释，csv格式时取第一列
+    pub fn parse(content: &str, gap_limit: u32) -> Self {
+        let addresses = content
+            .lines()
+            .map(|line| line.split(',').next().unwrap_or_default().trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        UsedAddresses {
+            addresses,
+            gap_limit,
+        }
+    }
+
+    fn contains(&self, address: &str) -> bool {
+        self.addresses.contains(&address.to_lowercase())
+    }
+}
+
+/// 派生一条链(receive为0，change为1)上的地址，提供已使用的地址时连续gap_limit个未使用后停止，否则派生count个
+pub fn derive_chain(
+    account: &AccountKey,
+    format: AddressFormat,
+    change: u32,
+    start: u32,
+    count: u32,
+    used: Option<&UsedAddresses>,
+) -> Result<Vec<DerivedAddress>> {
+    let mut addresses = vec![];
+    let mut gap = 0;
+    for index in start.. {
+        match used {
+            Some(used) if gap >= used.gap_limit => break,
+            None if index >= start.saturating_add(count) => break,
+            _ => {}
+        }
+        let mut address = account.derive(change, index, format)?;
+        if let Some(used) = used {
+            address.used = used.contains(&address.address);
+            gap = if address.used { 0 } else { gap + 1 };
+        }
+        addresses.push(address);
+    }
+    Ok(addresses)
+}
+
+/// 未指定时由扩展公钥前缀推断地址类型和网络，xpub/tpub 常被各类钱包通用，不限制purpose
+pub fn resolve_btc_format(
+    purpose: Option<u32>,
+    network: Option<&str>,
+    version: Option<Slip132>,
+) -> Result<(AddressType, Network)> {
+    let implied = match version {
+        Some(version) => Some(version.script_type.address_type().ok_or_else(|| {
+            anyhow!(
+                "{} is a multisig extended key, can not derive single key addresses.",
+                version.public.as_str()
+            )
+        })?),
+        None => None,
+    };
+    let address_type = match (purpose, implied) {
+        (Some(purpose), Some(implied))
+            if implied != AddressType::P2pkh && implied.purpose() != purpose =>
+        {
+            bail!(
+                "purpose {purpose} conflicts with extended key prefix, expect {}.",
+                implied.purpose()
+            )
+        }
+        (Some(purpose), _) => AddressType::from_purpose(purpose)?,
+        (None, Some(implied)) => implied,
+        (None, None) => AddressType::P2wpkh,
+    };
+    let network = match (network, version) {
+        (Some(network), Some(version)) => {
+            let network = Network::from_str(network)?;
+            if (network == Network::Mainnet) != (version.network == Network::Mainnet) {
+                bail!(
+                    "network {network:?} conflicts with extended key prefix {}.",
+                    version.public.as_str()
+                );
+            }
+            network
+        }
+        (Some(network), None) => Network::from_str(network)?,
+        (None, Some(version)) => version.network,
+        (None, None) => Network::Mainnet,
+    };
+    Ok((address_type, network))
+}
+
+pub fn derive(args: DeriveArgs) -> Result<Vec<DerivedAddress>> {
+    let chain = Chain::from_str(&args.chain)?;
+    let record_format = RecordFormat::from_str(&args.format)?;
+    let x_pub = match args.x_pub {
+        Some(x_pub) => Some(AccountKey::from_x_pub(&x_pub)?),
+        None => None,
+    };
+    let (format, coin_type) = match chain {
+        Chain::Btc => {
+            let (address_type, network) = resolve_btc_format(
+                args.purpose,
+                args.network.as_deref(),
+                x_pub.as_ref().map(|(_, version)| *version),
+            )?;
+            (
+                AddressFormat::Btc(address_type, network),
+                network.coin_type(),
+            )
+        }
+        Chain::Eth if args.purpose.unwrap_or(44) == 44 => (AddressFormat::Eth, ETH_COIN_TYPE),
+        Chain::Eth => bail!("eth only supports purpose 44."),
+    };
+    let purpose = match format {
+        AddressFormat::Btc(address_type, _) => address_type.purpose(),
+        AddressFormat::Eth => 44,
+    };
+    let coin_type = args.coin_type.unwrap_or(coin_type);
+    let account = match (args.mnemonic, x_pub) {
+        (Some(mnemonic), None) => AccountKey::from_mnemonic(
+            &mnemonic,
+            &args.passphrase,
+            purpose,
+            coin_type,
+            args.account,
+        )?,
+        (None, Some((account, _))) => account,
+        _ => bail!("params error: one of mnemonic or x_pub is required."),
+    };
+    let used = match args.used {
+        Some(used) => Some(UsedAddresses::parse(&read_input(&used)?, args.gap_limit)),
+        None => None,
+    };
+    let changes: &[u32] = match args.change.to_lowercase().as_str() {
+        "receive" => &[0],
+        "change" => &[1],
+        "both" => &[0, 1],
+        _ => bail!("change must be receive, change or both."),
+    };
+    let mut addresses = vec![];
+    for change in changes {
+        addresses.extend(derive_chain(
+            &account,
+            format,
+            *change,
+            args.start,
+            args.count,
+            used.as_ref(),
+        )?);
+    }
+    info!(
+        "derived {} addresses from {}.",
+        addresses.len(),
+        account.path
+    );
+    output_records(&addresses, record_format, args.output)?;
+    Ok(addresses)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn first_address(
+        purpose: u32,
+        coin_type: u32,
+        format: AddressFormat,
+        change: u32,
+        index: u32,
+    ) -> String {
+        let account = AccountKey::from_mnemonic(MNEMONIC, "", purpose, coin_type, 0).unwrap();
+        account.derive(change, index, format).unwrap().address
+    }
+
+    #[test]
+    fn test_bip_vectors() {
+        let btc = |address_type| AddressFormat::Btc(address_type, Network::Mainnet);
+        assert_eq!(
+            first_address(44, 0, btc(AddressType::P2pkh), 0, 0),
+            "1LqBGSKuX5yYUonjxT5qGfpUsXKYYWeabA"
+        );
+        assert_eq!(
+            first_address(49, 0, btc(AddressType::P2shP2wpkh), 0, 0),
+            "37VucYSaXLCAsxYyAPfbSi9eh4iEcbShgf"
+        );
+        assert_eq!(
+            first_address(
+                49,
+                1,
+                AddressFormat::Btc(AddressType::P2shP2wpkh, Network::Testnet),
+                0,
+                0
+            ),
+            "2Mww8dCYPUpKHofjgcXcBCEGmniw9CoaiD2"
+        );
+        assert_eq!(
+            first_address(84, 0, btc(AddressType::P2wpkh), 0, 0),
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(
+            first_address(84, 0, btc(AddressType::P2wpkh), 1, 0),
+            "bc1q8c6fshw2dlwun7ekn9qwf37cu2rn755upcp6el"
+        );
+        assert_eq!(
+            first_address(86, 0, btc(AddressType::P2tr), 0, 0),
+            "bc1p5cyxnuxmeuwuvkwfem96lqzszd02n6xdcjrs20cac6yqjjwudpxqkedrcr"
+        );
+        assert_eq!(
+            first_address(86, 0, btc(AddressType::P2tr), 0, 1),
+            "bc1p4qhjn9zdvkux4e44uhx8tc55attvtyu358kutcqkudyccelu0was9fqzwh"
+        );
+        assert_eq!(
+            first_address(86, 0, btc(AddressType::P2tr), 1, 0),
+            "bc1p3qkhfews2uk44qtvauqyr2ttdsw7svhkl9nkm9s9c3x4ax5h60wqwruhk7"
+        );
+        assert_eq!(
+            first_address(44, ETH_COIN_TYPE, AddressFormat::Eth, 0, 0),
+            "0x9858EfFD232B4033E47d90003D41EC34EcaEda94"
+        );
+    }
+
+    #[test]
+    fn test_derive_from_x_pub() {
+        use crate::output::run_cli;
+
+        // BIP-84 测试向量中的账户扩展公钥
+        let (account, _) = AccountKey::from_x_pub("xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V").unwrap();
+        let address = account
+            .derive(
+                0,
+                0,
+                AddressFormat::Btc(AddressType::P2wpkh, Network::Mainnet),
+            )
+            .unwrap();
+        assert_eq!(
+            address.address,
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert_eq!(address.path, "M/0/0");
+
+        // 扩展公钥不能与account、coin type同时使用
+        let x_pub = "xpub6CatWdiZiodmUeTDp8LT5or8nmbKNcuyvz7WyksVFkKB4RHwCD3XyuvPEbvqAQY3rAPshWcMLoP2fMFMKHPJ4ZeZXYVUhLv1VMrjPC7PW6V";
+        let args = ["derive", "-x", x_pub, "--purpose", "84", "-l", "1"];
+        assert!(run_cli(&args)
+            .unwrap()
+            .contains("bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"));
+        for extra in [["-a", "1"], ["--coin-type", "1"]] {
+            let err = run_cli(&[&args[..], &extra].concat()).unwrap_err();
+            assert!(err.to_string().contains("cannot be used with"));
+        }
+    }
+
+    #[test]
+    fn test_resolve_btc_format() {
+        let zpub = "zpub6rFR7y4Q2AijBEqTUquhVz398htDFrtymD9xYYfG1m4wAcvPhXNfE3EfH1r1ADqtfSdVCToUG868RvUUkgDKf31mGDtKsAYz2oz2AGutZYs";
+        let (account, version) = AccountKey::from_x_pub(zpub).unwrap();
+        let (address_type, network) = resolve_btc_format(None, None, Some(version)).unwrap();
+        assert_eq!(
+            (address_type, network),
+            (AddressType::P2wpkh, Network::Mainnet)
+        );
+        let address = account
+            .derive(0, 0, AddressFormat::Btc(address_type, network))
+            .unwrap();
+        assert_eq!(
+            address.address,
+            "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"
+        );
+        assert!(resolve_btc_format(Some(44), None, Some(version)).is_err());
+        assert!(resolve_btc_format(None, Some("testnet"), Some(version)).is_err());
+        // xpub 不限制purpose
+        let xpub = Slip132::from_prefix("xpub").unwrap();
+        assert_eq!(
+            resolve_btc_format(Some(86), None, Some(xpub)).unwrap(),
+            (AddressType::P2tr, Network::Mainnet)
+        );
+        let upub = Slip132::from_prefix("upub").unwrap();
+        assert_eq!(
+            resolve_btc_format(None, Some("regtest"), Some(upub)).unwrap(),
+            (AddressType::P2shP2wpkh, Network::Regtest)
+        );
+        assert!(
+            resolve_btc_format(None, None, Some(Slip132::from_prefix("Zpub").unwrap())).is_err()
+        );
+        assert_eq!(
+            resolve_btc_format(None, None, None).unwrap(),
+            (AddressType::P2wpkh, Network::Mainnet)
+        );
+    }
+
+    #[test]
+    fn test_gap_limit() {
+        let account = AccountKey::from_mnemonic(MNEMONIC, "", 84, 0, 0).unwrap();
+        let format = AddressFormat::Btc(AddressType::P2wpkh, Network::Mainnet);
+        let addresses = derive_chain(&account, format, 0, 0, 5, None).unwrap();
+        assert_eq!(addresses.len(), 5);
+        // 第0和第3个地址已使用，gap limit为2时派生到第5个地址停止
+        let used = UsedAddresses::parse(
+            &format!(
+                "# used\n{}\n{},1000\n",
+                addresses[0].address,
+                addresses[3].address.to_uppercase()
+            ),
+            2,
+        );
+        let scanned = derive_chain(&account, format, 0, 0, 5, Some(&used)).unwrap();
+        let flags: Vec<bool> = scanned.iter().map(|a| a.used).collect();
+        assert_eq!(flags, vec![true, false, false]);
+        let used = UsedAddresses::parse(&addresses[2].address, 2);
+        let scanned = derive_chain(&account, format, 0, 0, 5, Some(&used)).unwrap();
+        let flags: Vec<bool> = scanned.iter().map(|a| a.used).collect();
+        assert_eq!(flags, vec![false, false]);
+        let used = UsedAddresses::parse(
+            &format!("{}\n{}", addresses[1].address, addresses[3].address),
+            2,
+        );
+        let scanned = derive_chain(&account, format, 0, 0, 5, Some(&used)).unwrap();
+        assert_eq!(scanned.len(), 6);
+        assert_eq!(scanned[5].path, "m/84'/0'/0'/0/5");
+    }
+}
@@ -1,97 +1,241 @@
-use aes_gcm::aead::{AeadMut, Buffer};
-use aes_gcm::{
-    aead::{Aead, AeadCore, OsRng},
-    Aes256Gcm, AesGcm, Key, KeyInit, Nonce,
-};
+use aes_gcm::aead::generic_array::typenum::Unsigned;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::Aes256Gcm;
+use anyhow::{anyhow, bail};
+use chacha20poly1305::ChaCha20Poly1305;
+use ethers::utils::hex;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
-use std::iter::repeat;
-use std::{fmt::Write, string::String};
+use std::fs;
+use std::io::Read;
+use std::str::FromStr;
 use tracing::info;
 
-use crate::util::{hex_string_2_array, u8_array_convert_string};
+use crate::kdf::{pbkdf2_derive, scrypt_derive, Pbkdf2Params, ScryptParams, DEFAULT_DKLEN};
+use crate::output::Output;
+use crate::util::u8_array_convert_string;
 
-/// 使用hash算法将输入的密码转化为固定字节的key用于对数据进行加解密
-/// 目前暂定hash256和AES-256 GCM
-///
-///
+/// 加密结果统一保存为带版本号的JSON envelope，包含解密所需的全部参数：
+/// kdf名称和参数、salt、nonce、aad、密文和tag，方便脚本保存和还原
+pub const ENVELOPE_VERSION: u32 = 1;
+const SALT_LEN: usize = 16;
+const AAD_LEN: usize = 16;
+const TAG_LEN: usize = 16;
 
-fn generate_key(password: String) -> Vec<u8> {
-    let mut sh = Keccak256::new();
-    sh.update(&password.as_bytes());
-    let out = sh.finalize().to_vec();
-    out
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CipherAlgorithm {
+    #[serde(rename = "aes-256-gcm")]
+    Aes256Gcm,
+    #[serde(rename = "chacha20-poly1305")]
+    ChaCha20Poly1305,
 }
 
-pub fn encrypt(plaintext: String, password: String) -> anyhow::Result<()> {
-    info!("plaintext: {plaintext}");
-    let key = generate_key(password);
+impl FromStr for CipherAlgorithm {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "aes-256-gcm" => Ok(CipherAlgorithm::Aes256Gcm),
+            "chacha20-poly1305" => Ok(CipherAlgorithm::ChaCha20Poly1305),
+            _ => Err(anyhow!("cipher {s} not supported.")),
+        }
+    }
+}
+
+/// 由密码派生对称密钥的算法，参数和salt一起记录在envelope中，解密时按记录的参数还原key
+/// Keccak256为早期单次hash的方式，只保留用于解密旧的envelope
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "name", content = "params", rename_all = "lowercase")]
+pub enum Kdf {
+    Keccak256,
+    Scrypt(ScryptParams),
+    Pbkdf2(Pbkdf2Params),
+}
+
+impl Kdf {
+    pub fn from_args(
+        name: &str,
+        scrypt_n: u64,
+        scrypt_r: u32,
+        scrypt_p: u32,
+        pbkdf2_c: u32,
+    ) -> anyhow::Result<Kdf> {
+        match name.to_lowercase().as_str() {
+            "scrypt" => Ok(Kdf::Scrypt(ScryptParams {
+                n: scrypt_n,
+                r: scrypt_r,
+                p: scrypt_p,
+                dklen: DEFAULT_DKLEN,
+            })),
+            "pbkdf2" => Ok(Kdf::Pbkdf2(Pbkdf2Params {
+                c: pbkdf2_c,
+                ..Default::default()
+            })),
+            _ => Err(anyhow!("kdf {name} not supported.")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub version: u32,
+    pub kdf: Kdf,
+    pub salt: String,
+    pub cipher: CipherAlgorithm,
+    pub nonce: String,
+    pub aad: String,
+    pub ciphertext: String,
+    pub tag: String,
+}
+
+/// 使用kdf将输入的密码和salt转化为固定字节的key用于对数据进行加解密
+fn generate_key(kdf: &Kdf, password: &str, salt: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let key = match kdf {
+        Kdf::Keccak256 => {
+            let mut sh = Keccak256::new();
+            sh.update(salt);
+            sh.update(password.as_bytes());
+            sh.finalize().to_vec()
+        }
+        Kdf::Scrypt(params) => scrypt_derive(password.as_bytes(), salt, params)?,
+        Kdf::Pbkdf2(params) => pbkdf2_derive(password.as_bytes(), salt, params)?,
+    };
+    Ok(key)
+}
+
+fn aead_seal<C: Aead + AeadCore + KeyInit>(
+    key: &[u8],
+    msg: &[u8],
+    aad: &[u8],
+) -> anyhow::Result<(Vec<u8>, Vec<u8>)> {
+    let cipher = C::new_from_slice(key).map_err(|e| anyhow!("invalid key length: {e}"))?;
+    let nonce = C::generate_nonce(OsRng);
+    let out = cipher
+        .encrypt(&nonce, Payload { msg, aad })
+        .map_err(|e| anyhow!("encrypt failed: {e}"))?;
+    Ok((nonce.to_vec(), out))
+}
+
+fn aead_open<C: Aead + AeadCore + KeyInit>(
+    key: &[u8],
+    nonce: &[u8],
+    msg: &[u8],
+    aad: &[u8],
+) -> anyhow::Result<Vec<u8>> {
+    let cipher = C::new_from_slice(key).map_err(|e| anyhow!("invalid key length: {e}"))?;
+    if nonce.len() != C::NonceSize::to_usize() {
+        bail!("invalid nonce length: {}", nonce.len());
+    }
+    cipher
+        .decrypt(nonce.into(), Payload { msg, aad })
+        .map_err(|_| anyhow!("decrypt failed: wrong password or corrupted envelope."))
+}
+
+/// 使用密码加密数据并生成envelope
+pub fn seal(
+    plaintext: &[u8],
+    password: &str,
+    cipher: CipherAlgorithm,
+    kdf: Kdf,
+) -> anyhow::Result<Envelope> {
     let mut rng = rand::thread_rng();
-    let mut aad = [0u8; 16];
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut aad = [0u8; AAD_LEN];
     rng.fill_bytes(&mut aad);
-    let aes_key = Key::<Aes256Gcm>::from_slice(key.as_slice());
-    let mut aes_gcm = Aes256Gcm::new(&aes_key);
-    let plaintext_array = plaintext.as_bytes();
-    let nonce = Aes256Gcm::generate_nonce(OsRng);
-    let mut out = aes_gcm
-        .encrypt(
-            &nonce,
-            aes_gcm::aead::Payload {
-                msg: plaintext_array,
-                aad: aad.as_slice(),
-            },
-        )
-        .unwrap();
-    println!("out: {:?}", out);
-    let index = out.len() - 16;
-    let out_str: String = u8_array_convert_string(&out[..index]);
-    let iv_str = u8_array_convert_string(&nonce.as_slice());
-    let tag_str = u8_array_convert_string(&out[index..]);
-    let aad_str = u8_array_convert_string(&aad);
-    info!(
-        "cipher: {:?}\n{:?}, \niv: {:?}\n tag:{:?}, aav: {:?}",
-        out, out_str, iv_str, tag_str, aad_str
-    );
+    let key = generate_key(&kdf, password, &salt)?;
+    let (nonce, out) = match cipher {
+        CipherAlgorithm::Aes256Gcm => aead_seal::<Aes256Gcm>(&key, plaintext, &aad)?,
+        CipherAlgorithm::ChaCha20Poly1305 => aead_seal::<ChaCha20Poly1305>(&key, plaintext, &aad)?,
+    };
+    // 输出的末尾16字节为tag
+    let index = out.len() - TAG_LEN;
+    Ok(Envelope {
+        version: ENVELOPE_VERSION,
+        kdf,
+        salt: u8_array_convert_string(&salt),
+        cipher,
+        nonce: u8_array_convert_string(&nonce),
+        aad: u8_array_convert_string(&aad),
+        ciphertext: u8_array_convert_string(&out[..index]),
+        tag: u8_array_convert_string(&out[index..]),
+    })
+}
+
+/// 按envelope中记录的参数解密数据
+pub fn open(envelope: &Envelope, password: &str) -> anyhow::Result<Vec<u8>> {
+    if envelope.version != ENVELOPE_VERSION {
+        bail!("envelope version {} not supported.", envelope.version);
+    }
+    let decode = |name: &str, value: &str| {
+        hex::decode(value).map_err(|e| anyhow!("invalid envelope {name}: {e}"))
+    };
+    let salt = decode("salt", &envelope.salt)?;
+    let nonce = decode("nonce", &envelope.nonce)?;
+    let aad = decode("aad", &envelope.aad)?;
+    let mut msg = decode("ciphertext", &envelope.ciphertext)?;
+    let tag = decode("tag", &envelope.tag)?;
+    if tag.len() != TAG_LEN {
+        bail!("invalid tag length: {}", tag.len());
+    }
+    msg.extend_from_slice(&tag);
+    let key = generate_key(&envelope.kdf, password, &salt)?;
+    match envelope.cipher {
+        CipherAlgorithm::Aes256Gcm => aead_open::<Aes256Gcm>(&key, &nonce, &msg, &aad),
+        CipherAlgorithm::ChaCha20Poly1305 => {
+            aead_open::<ChaCha20Poly1305>(&key, &nonce, &msg, &aad)
+        }
+    }
+}
+
+/// 读取文件内容，`-` 表示从stdin读取
+pub fn read_input(input: &str) -> anyhow::Result<String> {
+    if input == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content)?;
+        Ok(content)
+    } else {
+        Ok(fs::read_to_string(input)?)
+    }
+}
+
+/// 指定文件时写入文件，命令结果统一由调用方输出到stdout
+pub fn write_output(output: Option<String>, content: &str) -> anyhow::Result<()> {
+    if let Some(path) = output {
+        fs::write(&path, content)?;
+        info!("write to {path} success.");
+    }
     Ok(())
 }
 
-pub fn decrypt(
-    cipher: String,
+pub fn encrypt(
+    plaintext: String,
     password: String,
-    iv: String,
-    tag: String,
-    aad: String,
-) -> anyhow::Result<()> {
-    info!("cipher: {cipher}");
-    let key = generate_key(password);
-    let mut cipher_arr = hex_string_2_array(&cipher);
-    let mut nonce_arr = hex_string_2_array(&iv);
-    let tag = hex_string_2_array(&tag);
-    let aad = hex_string_2_array(&aad);
-    let aes_key = Key::<Aes256Gcm>::from_slice(key.as_slice());
-    let mut aes_gcm = Aes256Gcm::new(&aes_key);
-    cipher_arr.extend_from_slice(&tag);
-    let result = aes_gcm
-        .decrypt(
-            &Nonce::from_slice(nonce_arr.as_slice()),
-            aes_gcm::aead::Payload {
-                msg: cipher_arr.as_slice(),
-                aad: &aad,
-            },
-        )
-        .unwrap();
-    //let result = aes_gcm.decrypt(&cipher_arr, &mut out, &tag);
-    let plaintext = String::from_utf8(result.clone()).unwrap();
-    println!(
-        "decrypt success, result: {:?}, plaintext: {:?}",
-        result, plaintext
-    );
-    Ok(())
+    cipher: String,
+    kdf: Kdf,
+    output: Option<String>,
+) -> anyhow::Result<Output> {
+    let cipher = CipherAlgorithm::from_str(&cipher)?;
+    let envelope = seal(plaintext.as_bytes(), &password, cipher, kdf)?;
+    Output::artifact(output, "envelope", &envelope)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Decrypted {
+    pub plaintext: String,
+}
+
+pub fn decrypt(input: String, password: String) -> anyhow::Result<Decrypted> {
+    let envelope: Envelope = serde_json::from_str(&read_input(&input)?)?;
+    let plaintext = String::from_utf8(open(&envelope, &password)?)?;
+    Ok(Decrypted { plaintext })
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::util::hex_string_2_array;
 
     #[test]
     fn string_u8_array() {
@@ -102,4 +246,115 @@ mod test {
         assert_eq!(arr.to_vec(), arr_result);
         assert_eq!(str_result, s.to_string());
     }
+
+    fn test_kdf() -> Kdf {
+        Kdf::Scrypt(ScryptParams {
+            n: 1024,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_envelope_round_trip() {
+        for cipher in [
+            CipherAlgorithm::Aes256Gcm,
+            CipherAlgorithm::ChaCha20Poly1305,
+        ] {
+            let envelope = seal(b"hello world", "password", cipher, test_kdf()).unwrap();
+            let json = serde_json::to_string(&envelope).unwrap();
+            let envelope: Envelope = serde_json::from_str(&json).unwrap();
+            assert_eq!(envelope.cipher, cipher);
+            assert_eq!(
+                open(&envelope, "password").unwrap(),
+                b"hello world".to_vec()
+            );
+            assert!(open(&envelope, "wrong password").is_err());
+        }
+    }
+
+    #[test]
+    fn test_envelope_format() {
+        let envelope = seal(b"hello", "password", CipherAlgorithm::Aes256Gcm, test_kdf()).unwrap();
+        let value = serde_json::to_value(&envelope).unwrap();
+        assert_eq!(value["version"], 1);
+        assert_eq!(value["kdf"]["name"], "scrypt");
+        assert_eq!(value["kdf"]["params"]["n"], 1024);
+        assert_eq!(value["cipher"], "aes-256-gcm");
+        assert_eq!(envelope.nonce.len(), 24);
+        assert_eq!(envelope.tag.len(), TAG_LEN * 2);
+    }
+
+    #[test]
+    fn test_kdf_params_honoured() {
+        let kdf = Kdf::from_args("pbkdf2", 0, 0, 0, 1000).unwrap();
+        let envelope = seal(b"hello", "password", CipherAlgorithm::Aes256Gcm, kdf).unwrap();
+        assert_eq!(open(&envelope, "password").unwrap(), b"hello".to_vec());
+        // 篡改记录的参数后派生出的key不同，无法解密
+        let mut tampered = envelope.clone();
+        tampered.kdf = Kdf::from_args("pbkdf2", 0, 0, 0, 1001).unwrap();
+        assert!(open(&tampered, "password").is_err());
+        // 不同文件的salt随机生成
+        let other = seal(
+            b"hello",
+            "password",
+            CipherAlgorithm::Aes256Gcm,
+            envelope.kdf.clone(),
+        )
+        .unwrap();
+        assert_ne!(envelope.salt, other.salt);
+        assert!(Kdf::from_args("argon2", 0, 0, 0, 0).is_err());
+    }
+
+    #[test]
+    fn test_malformed_envelope() {
+        let envelope = seal(b"hello", "password", CipherAlgorithm::Aes256Gcm, test_kdf()).unwrap();
+        let fields: [fn(&mut Envelope) -> &mut String; 5] = [
+            |e| &mut e.salt,
+            |e| &mut e.nonce,
+            |e| &mut e.aad,
+            |e| &mut e.ciphertext,
+            |e| &mut e.tag,
+        ];
+        for field in fields {
+            for bad in ["abc", "zz", "000g"] {
+                let mut tampered = envelope.clone();
+                *field(&mut tampered) = bad.to_string();
+                assert!(open(&tampered, "password").is_err());
+            }
+        }
+        // 不可信envelope中过大的kdf参数
+        for kdf in [
+            Kdf::Scrypt(ScryptParams {
+                n: 1 << 40,
+                ..Default::default()
+            }),
+            Kdf::Scrypt(ScryptParams {
+                n: 1024,
+                dklen: 1 << 40,
+                ..Default::default()
+            }),
+        ] {
+            let mut tampered = envelope.clone();
+            tampered.kdf = kdf;
+            assert!(open(&tampered, "password").is_err());
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        use crate::output::{pipe, run_cli};
+
+        // text格式下encrypt输出的envelope可以直接作为decrypt的输入，json格式下包装在envelope字段中
+        let args = ["encrypt", "-t", "hello", "-p", "pw", "--scrypt-n", "1024"];
+        let envelope = run_cli(&args).unwrap();
+        let input = pipe("envelope-text.json", &envelope);
+        let decrypted = run_cli(&["decrypt", "-i", &input, "-p", "pw"]).unwrap();
+        assert_eq!(decrypted, "plaintext: hello");
+
+        let wrapped = run_cli(&[&["--output-format", "json"], &args[..]].concat()).unwrap();
+        let wrapped: serde_json::Value = serde_json::from_str(&wrapped).unwrap();
+        let input = pipe("envelope-json.json", &wrapped["envelope"].to_string());
+        let decrypted = run_cli(&["decrypt", "-i", &input, "-p", "pw"]).unwrap();
+        assert_eq!(decrypted, "plaintext: hello");
+    }
 }
@@ -1,14 +1,23 @@
+use crate::abi::{
+    decode_call, decode_call_by_selector, encode_call, load_abi, parse_signature, DecodedCall,
+};
 use crate::bip32::{derive_private_by_path, derive_public_by_path, mnemonic_to_x_prv};
+use crate::btc::Bip39Key;
+use crate::eth_tx::{
+    apply_fees, broadcast_raw_transaction, parse_hex_data, sign_typed_transaction, FeeOptions,
+    SignedTransaction,
+};
 use crate::http_request::fetch_url;
-use crate::util::{hex_string_2_array, u8_array_convert_string};
-use anyhow::Result;
+use crate::util::u8_array_convert_string;
+use anyhow::{anyhow, bail, Result};
 use bip32::secp256k1::elliptic_curve::weierstrass::add;
 use bip32::{Prefix, PublicKey as Bip32PubKey};
-use bytes::Buf;
 use clap::builder::Str;
-use ethers::abi::{parse_abi_str, AbiEncode};
+use ethers::abi::AbiEncode;
 use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::utils::hex;
 use ethers::utils::hex::ToHex;
+use ethers::utils::parse_units;
 use ethers::{
     core::types::{Address, TransactionRequest},
     etherscan::Client,
@@ -17,82 +26,96 @@ use ethers::{
 };
 use num_bigint::BigInt;
 use num_bigint::Sign::{NoSign, Plus};
-use num_traits::Num;
 use regex::Regex;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::Serialize;
 use serde_json::json;
 use sha3::Digest;
 use std::collections::HashMap;
 use std::f64;
-use std::io::{Cursor, Read};
 use std::ops::{Div, Mul, Sub};
 use std::str::FromStr;
 use std::sync::Arc;
 use tracing::info;
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CreatedTransaction {
+    pub gas_limit: Option<U256>,
+    #[serde(flatten)]
+    pub signed: SignedTransaction,
+    pub broadcast: bool,
+}
+
 pub async fn create_transaction(
     private_key: String,
     rpc_url: String,
     to: String,
     value: u128,
-    chain_id: u32,
+    chain_id: u64,
     is_broadcast: bool,
     contract: Option<String>,
-    gas_price: Option<u128>,
+    data: Option<String>,
+    fees: FeeOptions,
     gas_limit: Option<u128>,
     nonce: Option<u128>,
-) -> Result<()> {
-    let wallet = private_key.as_str().parse::<LocalWallet>().unwrap();
-    let provider = Provider::<Http>::try_from(rpc_url.as_str()).unwrap();
+) -> Result<CreatedTransaction> {
+    let wallet = private_key
+        .as_str()
+        .parse::<LocalWallet>()
+        .map_err(|e| anyhow!("invalid private key: {e}"))?;
+    let provider = Provider::<Http>::try_from(rpc_url.as_str())
+        .map_err(|e| anyhow!("invalid rpc url: {e}"))?;
     let client = Arc::new(SignerMiddleware::new(provider, wallet));
-    let mut tx_request: TypedTransaction = if contract.is_none() {
-        TransactionRequest::new()
+    let tx_request: TypedTransaction = match (contract, data) {
+        (Some(_), Some(_)) => bail!("contract_address and data can not be used together."),
+        // 任意合约调用，data可由encode-call生成或通过abi编码
+        (None, Some(data)) => TransactionRequest::new()
             .to(to.as_str())
             .value(value)
+            .data(parse_hex_data(&data)?)
             .chain_id(chain_id)
-            .into()
-    } else {
-        abigen!(
-            ERC20Contract,
-            r#"[
-            function balanceOf(address account) external view returns (uint256)
-            function decimals() external view returns (uint8)
-            function symbol() external view returns (string memory)
-            function transfer(address to, uint256 amount) external returns (bool)
-            event Transfer(address indexed from, address indexed to, uint256 value)
-        ]"#,
-        );
-        let address = contract.unwrap().as_str().parse::<Address>().unwrap();
-        let contract = ERC20Contract::new(address, client.clone());
-        (*contract
-            .transfer(to.as_str().parse::<Address>().unwrap(), value.into())
-            .tx
-            .set_chain_id(chain_id)
-            .set_value(0))
-        .clone()
-        .into()
+            .into(),
+        // ERC20转账，value为token数量
+        (Some(contract), None) => {
+            let function = parse_signature("transfer(address to, uint256 amount)")?;
+            let data = encode_call(&function, &[to, value.to_string()])?;
+            TransactionRequest::new()
+                .to(contract.as_str())
+                .value(0)
+                .data(data)
+                .chain_id(chain_id)
+                .into()
+        }
+        (None, None) => TransactionRequest::new()
+            .to(to.as_str())
+            .value(value)
+            .chain_id(chain_id)
+            .into(),
     };
-    if let Some(gas_price_val) = gas_price {
-        tx_request.set_gas_price(gas_price_val);
-    }
+    let mut tx_request = apply_fees(client.provider(), tx_request, &fees).await?;
     if let Some(gas_limit_val) = gas_limit {
         tx_request.set_gas(gas_limit_val);
     }
     if let Some(nonce) = nonce {
         tx_request.set_nonce(nonce);
     }
+    // 未指定gas_limit时由节点估算，合约调用会revert时在这里报错
     client
         .fill_transaction(&mut tx_request, None)
         .await
-        .unwrap();
-    let sig = client.signer().sign_transaction(&tx_request).await.unwrap();
-    let tx = tx_request.rlp_signed(&sig);
-    info!("tx: {:?}", tx);
+        .map_err(|e| anyhow!("fill transaction failed: {e}"))?;
+    info!("gas limit: {:?}", tx_request.gas());
+    let signed = sign_typed_transaction(client.signer(), &tx_request)?;
+    info!("tx: {:?}", signed.raw_transaction);
     if is_broadcast {
-        let pending_tx = client.provider().send_raw_transaction(tx).await.unwrap();
-        info!("txHash: {:?}", pending_tx.tx_hash());
+        let tx_hash = broadcast_raw_transaction(client.provider(), &signed.raw_transaction).await?;
+        info!("txHash: {:?}", tx_hash);
     }
-    Ok(())
+    Ok(CreatedTransaction {
+        gas_limit: tx_request.gas().copied(),
+        signed,
+        broadcast: is_broadcast,
+    })
 }
 
 fn parse_param(data: &[u8; 32]) -> String {
@@ -107,7 +130,7 @@ fn parse_param(data: &[u8; 32]) -> String {
         // 先默认为value
         let value = BigInt::from_bytes_be(Plus, ignore_prefix_0.as_slice());
         let value_str = value.to_string();
-        if let Some(u) = (value_str.clone() + "Wei").parse_uint_str() {
+        if let Ok(u) = (value_str.clone() + "Wei").parse_uint_str() {
             let result = u.get_all();
             return format!("{:?}", result);
         } else {
@@ -116,55 +139,102 @@ fn parse_param(data: &[u8; 32]) -> String {
     }
 }
 
+/// 使用abi文件中的函数编码合约调用，function可以是函数名或者签名，如 "safeTransferFrom(address,address,uint256)"
+pub fn encode_abi_call(abi: &str, function: &str, args: &[String]) -> Result<String> {
+    let abi = load_abi(abi)?;
+    let function = abi.function_for_call(function, args.len())?;
+    let data = encode_call(function, args)?;
+    Ok(format!("0x{}", u8_array_convert_string(&data)))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CallData {
+    pub data: String,
+}
+
+/// 按函数签名编码调用参数，输出的calldata可以作为Transfer的data
+pub fn encode_call_data(signature: String, args: Vec<String>) -> Result<CallData> {
+    let function = parse_signature(&signature)?;
+    let data = encode_call(&function, &args)?;
+    Ok(CallData {
+        data: format!("0x{}", u8_array_convert_string(&data)),
+    })
+}
+
+/// 没有abi且不在内置的选择器列表中时，参数是按32字节逐个猜测的结果
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum DecodedCallData {
+    Known(DecodedCall),
+    Unknown {
+        selector: String,
+        params: Vec<String>,
+    },
+}
+
 pub fn decode_call_data(
     data_field: String,
     abi_str: Option<String>,
     func_name: Option<String>,
-) -> Result<()> {
-    let data_field_str = data_field.as_str().trim_start_matches("0x");
-    match abi_str {
-        Some(abi_str) => {
-            let parse_abi = parse_abi_str(&abi_str).expect("parse abi failed.");
-            let func = parse_abi
-                .function(func_name.expect("func name should exist").as_str())
-                .unwrap();
-            // 去除函数选择器数据字段否则解析不正确
-            let decoded = func
-                .decode_input(&hex_string_2_array(&data_field_str)[4..])
-                .expect("parse data failed.");
-            println!("decode result: {:?}", decoded);
-        }
+) -> Result<DecodedCallData> {
+    let data_field_str = data_field.trim().trim_start_matches("0x");
+    let data_bytes =
+        hex::decode(data_field_str).map_err(|e| anyhow!("invalid call data hex: {e}"))?;
+    let decoded = match abi_str {
+        Some(abi_str) => Some(decode_call(
+            &load_abi(&abi_str)?,
+            &data_bytes,
+            func_name.as_deref(),
+        )?),
+        None => decode_call_by_selector(&data_bytes)?,
+    };
+    match decoded {
+        Some(call) => Ok(DecodedCallData::Known(call)),
         None => {
-            let data_bytes = hex_string_2_array(data_field_str);
-            let function_selector = &data_bytes[..4];
-            let mut params = vec![];
-            let mut cursor = Cursor::new(&data_bytes[4..]);
-            let mut read_32_bytes = [0; 32];
-            while cursor.has_remaining() {
-                cursor
-                    .read_exact(&mut read_32_bytes)
-                    .expect("read param must be 32 bytes.");
-                params.push(parse_param(&read_32_bytes));
+            // 没有abi且不在内置的选择器列表中，只能按32字节逐个猜测参数
+            if data_bytes.len() < 4 {
+                bail!("call data must have a 4 bytes function selector");
+            }
+            let (function_selector, params_bytes) = data_bytes.split_at(4);
+            if params_bytes.len() % 32 != 0 {
+                bail!(
+                    "call data params must be a multiple of 32 bytes, got {} bytes",
+                    params_bytes.len()
+                );
             }
-            println!(
-                "function_selector: {:?}\n, params: {:?}",
-                u8_array_convert_string(function_selector),
-                params
-            );
+            let params = params_bytes
+                .chunks_exact(32)
+                .map(|word| parse_param(word.try_into().expect("chunk is 32 bytes")))
+                .collect();
+            Ok(DecodedCallData::Unknown {
+                selector: format!("0x{}", u8_array_convert_string(function_selector)),
+                params,
+            })
         }
     }
-    Ok(())
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransferableBalance {
+    pub address: String,
+    pub balance: U256,
+    pub fee: U256,
+    /// 余额不足以支付手续费时为空
+    pub transfer_balance: Option<U256>,
+    pub chain_gas_price_gwei: U256,
+}
+
+/// gas_price单位为wei
 pub async fn calculate_balance(
     rpc_url: String,
     address: String,
-    gas_price: String,
-    gas_limit: String,
+    gas_price: U256,
+    gas_limit: u128,
     block_id: Option<u64>,
-) -> Result<()> {
+) -> Result<TransferableBalance> {
     // 获取地址余额
-    let provider = Provider::try_from(rpc_url.as_str()).unwrap();
+    let provider = Provider::<Http>::try_from(rpc_url.as_str())
+        .map_err(|e| anyhow!("invalid rpc url: {e}"))?;
     let block_id = match block_id {
         Some(id) => Some(BlockId::from(id)),
         None => None,
@@ -175,17 +245,15 @@ pub async fn calculate_balance(
         .expect("query address balance error");
     let gas_price_on_chain = provider.get_gas_price().await.unwrap();
     let g_wei: U256 = 1_000_000_000.into();
-    let gas_price = U256::from_str_radix(gas_price.as_str(), 10)
-        .unwrap()
-        .mul(g_wei);
-    let fee = gas_price.mul(U256::from_str_radix(gas_limit.as_str(), 10).unwrap());
-    if balance.gt(&fee) {
-        let transfer_balance = balance.sub(fee);
-        println!("address: {:?}, transfer balance: {:?}(gas_price: {gas_price}, gas_limit: {gas_limit}), chain_gas_price = {:?}GWei", address, transfer_balance, gas_price_on_chain.div(g_wei));
-    } else {
-        println!("address balance: {balance}, fee: {fee}");
-    }
-    Ok(())
+    let fee = gas_price.mul(U256::from(gas_limit));
+    let transfer_balance = balance.gt(&fee).then(|| balance.sub(fee));
+    Ok(TransferableBalance {
+        address,
+        balance,
+        fee,
+        transfer_balance,
+        chain_gas_price_gwei: gas_price_on_chain.div(g_wei),
+    })
 }
 
 #[derive(Debug)]
@@ -196,6 +264,16 @@ pub enum Unit {
 }
 
 impl Unit {
+    /// 转换为精确的wei数值，f64按十进制字符串解析避免浮点乘法带来的误差
+    pub fn to_wei(&self) -> Result<U256> {
+        let wei = match self {
+            Unit::Wei(val) => *val,
+            Unit::GWei(val) => parse_units(val.to_string(), "gwei")?.into(),
+            Unit::Eth(val) => parse_units(val.to_string(), "ether")?.into(),
+        };
+        Ok(wei)
+    }
+
     pub fn get_all(&self) -> Vec<Unit> {
         let wei: U256 = 1_000_000_000_000_000_000i64.into();
         let g_wei = U256::from(1_000_000_000);
@@ -225,11 +303,11 @@ impl Unit {
 }
 
 pub trait EthersUnit {
-    fn parse_uint_str(&self) -> Option<Unit>;
+    fn parse_uint_str(&self) -> Result<Unit>;
 }
 
 fn split_number_and_uint(input: &str) -> Option<(String, String)> {
-    let reg = r"(\d+(\.\d+)?)\s*(\w+)";
+    let reg = r"^\s*(\d+(\.\d+)?)\s*([a-zA-Z]+)\s*$";
     let re = Regex::new(reg).unwrap();
     if let Some(captures) = re.captures(input) {
         Some((
@@ -242,29 +320,54 @@ fn split_number_and_uint(input: &str) -> Option<(String, String)> {
 }
 
 impl EthersUnit for String {
-    fn parse_uint_str(&self) -> Option<Unit> {
-        if let Some((amount, unit)) = split_number_and_uint(&self) {
-            let result = match unit.to_lowercase().as_str() {
-                "wei" => Unit::Wei(U256::from_str_radix(&amount, 10).unwrap()),
-                "gwei" => Unit::GWei(f64::from_str_radix(&amount, 10).unwrap()),
-                "eth" => Unit::Eth(f64::from_str_radix(&amount, 10).unwrap()),
-                _ => {
-                    panic!("unit not supported.")
-                }
-            };
-            Some(result)
-        } else {
-            None
-        }
+    /// 数值和单位无法解析时返回错误，wei只能是整数
+    fn parse_uint_str(&self) -> Result<Unit> {
+        let (amount, unit) =
+            split_number_and_uint(self).ok_or_else(|| anyhow!("invalid amount: {self}"))?;
+        let result = match unit.to_lowercase().as_str() {
+            "wei" => Unit::Wei(
+                U256::from_dec_str(&amount)
+                    .map_err(|e| anyhow!("invalid wei amount {amount}: {e}"))?,
+            ),
+            "gwei" => Unit::GWei(amount.parse()?),
+            "eth" => Unit::Eth(amount.parse()?),
+            _ => bail!("unit {unit} not supported, expect wei, gwei or eth."),
+        };
+        Ok(result)
     }
 }
 
-pub fn eth_convert(amount: String) -> Result<()> {
-    if let Some(amount_unit) = amount.parse_uint_str() {
-        let result = amount_unit.get_all();
-        println!("{amount} equal: {:?}", result);
+/// 解析带单位的数值为wei，不带单位的整数默认为wei，如 "30 GWei"、"0.1ETH"、"21000"
+pub fn parse_wei(value: &str) -> Result<U256> {
+    let value = value.trim();
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(U256::from_dec_str(value)?);
     }
-    Ok(())
+    value.to_string().parse_uint_str()?.to_wei()
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ConvertedAmount {
+    pub wei: U256,
+    pub gwei: f64,
+    pub eth: f64,
+}
+
+pub fn eth_convert(amount: String) -> Result<ConvertedAmount> {
+    let amount_unit = amount.parse_uint_str()?;
+    let mut converted = ConvertedAmount {
+        wei: U256::zero(),
+        gwei: 0.0,
+        eth: 0.0,
+    };
+    for unit in amount_unit.get_all() {
+        match unit {
+            Unit::Wei(val) => converted.wei = val,
+            Unit::GWei(val) => converted.gwei = val,
+            Unit::Eth(val) => converted.eth = val,
+        }
+    }
+    Ok(converted)
 }
 
 pub fn get_public_key(private_key: &str) -> PublicKey {
@@ -273,7 +376,7 @@ pub fn get_public_key(private_key: &str) -> PublicKey {
     PublicKey::from_secret_key(&secp, &sec_key)
 }
 
-fn pub_key_to_address(public_key: PublicKey) -> String {
+pub(crate) fn pub_key_to_address(public_key: PublicKey) -> String {
     let mut hasher = sha3::Keccak256::new();
     hasher.update(&public_key.serialize_uncompressed()[1..]);
     let out = hasher.finalize().to_vec();
@@ -284,25 +387,33 @@ fn pub_key_to_address(public_key: PublicKey) -> String {
     address
 }
 
-pub fn pub_key_str_to_address(public_key: String) -> Result<()> {
-    let public_key = PublicKey::from_str(&public_key).expect("PublicKey from str failed.");
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct EthAddress {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    pub address: String,
+}
+
+pub fn pub_key_str_to_address(public_key: String) -> Result<EthAddress> {
+    let public_key = PublicKey::from_str(&public_key)
+        .map_err(|e| anyhow!("invalid public key {public_key}: {e}"))?;
     let address = pub_key_to_address(public_key);
-    info!("address: {:?}", address);
-    Ok(())
+    Ok(EthAddress {
+        public_key: None,
+        address,
+    })
 }
 
-pub fn private_key_to_address(private_key: String) -> Result<()> {
+pub fn private_key_to_address(private_key: String) -> Result<EthAddress> {
     let public_key = get_public_key(&private_key);
     let address = pub_key_to_address(public_key);
-    info!(
-        "public_key: {:?}, \naddress: {:?}",
-        public_key.to_string(),
-        address
-    );
-    Ok(())
+    Ok(EthAddress {
+        public_key: Some(public_key.to_string()),
+        address,
+    })
 }
 
-pub fn bip32_to_address(xkey: String, path: String) -> Result<()> {
+pub fn bip32_to_address(xkey: String, path: String) -> Result<EthAddress> {
     let x_pub_key = if xkey.starts_with("xprv") {
         let x_priv_key = derive_private_by_path(path, xkey);
         x_priv_key.public_key()
@@ -312,39 +423,51 @@ pub fn bip32_to_address(xkey: String, path: String) -> Result<()> {
     let address = pub_key_to_address(
         PublicKey::from_slice(x_pub_key.public_key().to_bytes().to_vec().as_slice()).unwrap(),
     );
-    info!("address: {:?}", address);
-    Ok(())
+    Ok(EthAddress {
+        public_key: None,
+        address,
+    })
 }
 
-pub fn bip39_to_key(mnemonic: String, passphrase: String) -> Result<()> {
+pub fn bip39_to_key(mnemonic: String, passphrase: String) -> Result<Bip39Key> {
     let key = mnemonic_to_x_prv(mnemonic, passphrase);
     let xkey_str = &*key.to_string(Prefix::XPRV).to_string();
-    let extend_key = derive_private_by_path("m/44'/60'/0'".to_string(), xkey_str.to_string());
-    info!("xPrivKey: {:?}", xkey_str);
-    info!(
-        "extend key for m/44'/60'/0': {:?}\n xPubKey: {:?}",
-        extend_key.to_string(Prefix::XPRV),
-        extend_key.public_key().to_string(Prefix::XPUB)
-    );
-    Ok(())
+    let path = "m/44'/60'/0'".to_string();
+    let extend_key = derive_private_by_path(path.clone(), xkey_str.to_string());
+    Ok(Bip39Key {
+        x_prv: xkey_str.to_string(),
+        path,
+        account_x_prv: extend_key.to_string(Prefix::XPRV).to_string(),
+        account_x_pub: extend_key.public_key().to_string(Prefix::XPUB),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyPair {
+    pub path: String,
+    pub private_key: String,
+    pub address: String,
 }
 
 pub fn mnemonic_to_key_pair_by_path(
     mnemonic: String,
     passphrase: String,
     path: String,
-) -> Result<()> {
+) -> Result<KeyPair> {
     let key = mnemonic_to_x_prv(mnemonic, passphrase);
     let xkey_str = &*key.to_string(Prefix::XPRV).to_string();
     let mut path_complete = "m/44'/60'/0'".to_string();
     path_complete.push_str(&path[1..]);
-    let extend_key = derive_private_by_path(path_complete, xkey_str.to_string());
+    let extend_key = derive_private_by_path(path_complete.clone(), xkey_str.to_string());
     let address = pub_key_to_address(
         PublicKey::from_slice(extend_key.public_key().to_bytes().to_vec().as_slice()).unwrap(),
     );
     let private_key = u8_array_convert_string(extend_key.to_bytes().to_vec().as_slice());
-    info!("private key: {:?}, address: {:?}", private_key, address);
-    Ok(())
+    Ok(KeyPair {
+        path: path_complete,
+        private_key,
+        address,
+    })
 }
 
 pub async fn query_chain_info_by_address(
@@ -369,26 +492,30 @@ pub async fn query_chain_info_by_address(
     Ok(())
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EtherscanAccount {
+    pub address: Address,
+    pub balance: String,
+    pub eth_usd: f64,
+}
+
 pub async fn query_account_by_etherscan(
     address: String,
     api_key: String,
     chain: u64,
-) -> Result<()> {
-    let client = Client::new(
-        Chain::try_from(chain).expect("chain try from {chain} failed"),
-        api_key,
-    )
-    .expect("client init failed.");
+) -> Result<EtherscanAccount> {
+    let chain = Chain::try_from(chain).map_err(|e| anyhow!("chain {chain} not supported: {e}"))?;
+    let client = Client::new(chain, api_key)?;
     let address = address.as_str().parse()?;
     // 获取链的原生币种余额
     let native_balance = client.get_ether_balance_single(&address, None).await?;
     // 查询指定token的价格
     let price = client.eth_price().await?;
-    info!(
-        "address: {:?}, native token: {:?}, price: {:?}",
-        native_balance.account, native_balance.balance, price.ethusd
-    );
-    Ok(())
+    Ok(EtherscanAccount {
+        address: native_balance.account,
+        balance: native_balance.balance,
+        eth_usd: price.ethusd,
+    })
 }
 
 #[cfg(test)]
@@ -409,6 +536,7 @@ mod test {
             false,
             None,
             None,
+            FeeOptions::default(),
             None,
             None,
         ));
@@ -427,11 +555,38 @@ mod test {
             false,
             Some("0xBA62BCfcAaFc6622853cca2BE6Ac7d845BC0f2Dc".to_string()),
             None,
+            FeeOptions::default(),
             None,
             None,
         ));
     }
 
+    #[test]
+    fn test_create_tx_invalid_args() {
+        let rt = Runtime::new().unwrap();
+        let create = |private_key: &str, rpc_url: &str| {
+            rt.block_on(create_transaction(
+                private_key.to_string(),
+                rpc_url.to_string(),
+                "0x9BF5a8AF3333e2bF300FB00A0B7B8aDddc90dd43".to_string(),
+                1,
+                0x05,
+                false,
+                None,
+                None,
+                FeeOptions::default(),
+                None,
+                None,
+            ))
+        };
+        let private_key = "1cb90607624a78a065b51ded6fc701c381aa6b0aef37ed278f15774dd5b85758";
+        // 私钥或rpc地址非法时返回错误而不是panic
+        let err = create("not a key", "http://127.0.0.1:1").unwrap_err();
+        assert!(err.to_string().contains("invalid private key"), "{err}");
+        let err = create(private_key, "not a url").unwrap_err();
+        assert!(err.to_string().contains("invalid rpc url"), "{err}");
+    }
+
     #[test]
     fn test_split_number_uint() {
         let str1 = "1326 ETH".to_string();
@@ -461,6 +616,26 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_wei() {
+        assert_eq!(parse_wei("21000").unwrap(), U256::from(21000));
+        assert_eq!(parse_wei("30 GWei").unwrap(), U256::from(30_000_000_000u64));
+        assert_eq!(parse_wei("1.5gwei").unwrap(), U256::from(1_500_000_000u64));
+        assert_eq!(
+            parse_wei("1.03 ETH").unwrap(),
+            U256::from(1_030_000_000_000_000_000u64)
+        );
+        assert_eq!(parse_wei("100 wei").unwrap(), U256::from(100));
+        assert!(parse_wei("").is_err());
+        assert!(parse_wei("abc").is_err());
+        // 以下输入不再panic
+        assert!(parse_wei("1.5 wei").is_err());
+        assert!(parse_wei("10 usdc").is_err());
+        assert!(parse_wei("1e18").is_err());
+        assert!(parse_wei("0x10 gwei").is_err());
+        assert!(eth_convert("1.03 BTC".to_string()).is_err());
+    }
+
     #[ignore]
     #[test]
     fn test_unit_convert() {
@@ -493,4 +668,23 @@ mod test {
             Some("transfer".to_string()),
         );
     }
+
+    #[test]
+    fn test_decode_call_data_malformed() {
+        // 非法的十六进制字符
+        assert!(decode_call_data("0xzz".to_string(), None, None).is_err());
+        // 不足4字节的选择器
+        assert!(decode_call_data("0x1234".to_string(), None, None).is_err());
+        // 选择器之后的参数不是32字节的整数倍
+        assert!(decode_call_data("0x12345678aabb".to_string(), None, None).is_err());
+
+        let data = format!("0x12345678{:0>64}", "2a");
+        match decode_call_data(data, None, None).unwrap() {
+            DecodedCallData::Unknown { selector, params } => {
+                assert_eq!(selector, "0x12345678");
+                assert_eq!(params.len(), 1);
+            }
+            other => panic!("unexpected decoded call data: {other:?}"),
+        }
+    }
 }
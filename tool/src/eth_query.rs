@@ -0,0 +1,879 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::abi::{Event, Function, RawLog, Token};
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::{
+    Address, BlockId, BlockNumber, Bytes, Filter, Log, Topic, TransactionRequest, H256, U256,
+};
+use ethers::utils::{format_units, to_checksum};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::abi::{
+    common_events, decode_params, encode_call, event_signature, format_token, load_abi,
+    parse_event, parse_signature, DecodedParam, LoadedAbi, ParamNames,
+};
+use crate::cli::{BalancesArgs, CallArgs, LogsArgs};
+use crate::config::NetworkProfile;
+use crate::encrypt_decrypt::read_input;
+use crate::file_handle::{output_records, output_table, RecordFormat};
+use crate::util::u8_array_convert_string;
+
+/// 区块参数，支持 latest/earliest/pending/safe/finalized、区块号(十进制或0x)和区块hash
+pub fn parse_block_id(block: &str) -> Result<BlockId> {
+    let block = block.trim();
+    let number = match block.to_lowercase().as_str() {
+        "latest" => BlockNumber::Latest,
+        "earliest" => BlockNumber::Earliest,
+        "pending" => BlockNumber::Pending,
+        "safe" => BlockNumber::Safe,
+        "finalized" => BlockNumber::Finalized,
+        _ if block.len() == 66 && block.starts_with("0x") => {
+            return Ok(BlockId::Hash(H256::from_str(block)?));
+        }
+        _ => {
+            let number = match block.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => block.parse::<u64>(),
+            };
+            BlockNumber::Number(number.map_err(|_| anyhow!("invalid block {block}"))?.into())
+        }
+    };
+    Ok(BlockId::Number(number))
+}
+
+/// 日志的topic，地址会左侧补0为32字节
+pub fn parse_topic(topic: &str) -> Result<H256> {
+    let hex = topic.trim().trim_start_matches("0x");
+    match hex.len() {
+        40 => Ok(H256::from(Address::from_str(hex)?)),
+        64 => Ok(H256::from_str(hex)?),
+        _ => bail!("invalid topic {topic}, expect an address or 32 bytes hex."),
+    }
+}
+
+async fn call_raw(
+    provider: &Provider<Http>,
+    to: Address,
+    function: &Function,
+    args: &[String],
+    block: BlockId,
+    from: Option<Address>,
+) -> Result<Bytes> {
+    let mut tx = TransactionRequest::new()
+        .to(to)
+        .data(encode_call(function, args)?);
+    if let Some(from) = from {
+        tx = tx.from(from);
+    }
+    let tx: TypedTransaction = tx.into();
+    provider
+        .call(&tx, Some(block))
+        .await
+        .map_err(|e| anyhow!("eth_call {} failed: {e}", function.name))
+}
+
+/// 执行只读调用并返回解码后的token
+pub async fn call_tokens(
+    provider: &Provider<Http>,
+    to: Address,
+    function: &Function,
+    args: &[String],
+    block: BlockId,
+) -> Result<Vec<Token>> {
+    let result = call_raw(provider, to, function, args, block, None).await?;
+    function
+        .decode_output(&result)
+        .map_err(|e| anyhow!("decode {} output failed: {e}", function.name))
+}
+
+/// 执行只读调用并按函数的返回值类型解码，没有返回值定义时输出原始数据
+pub async fn call_function(
+    provider: &Provider<Http>,
+    to: Address,
+    function: &Function,
+    output_names: &[ParamNames],
+    args: &[String],
+    block: BlockId,
+    from: Option<Address>,
+) -> Result<Vec<DecodedParam>> {
+    let result = call_raw(provider, to, function, args, block, from).await?;
+    if function.outputs.is_empty() {
+        return Ok(vec![DecodedParam {
+            name: "result".to_string(),
+            kind: "bytes".to_string(),
+            value: format!("0x{}", u8_array_convert_string(&result)),
+        }]);
+    }
+    let tokens = function
+        .decode_output(&result)
+        .map_err(|e| anyhow!("decode {} output failed: {e}", function.name))?;
+    Ok(decode_params(&function.outputs, output_names, &tokens))
+}
+
+/// 按区块范围分页查询日志，节点拒绝查询时将范围减半后重试
+pub async fn fetch_logs(
+    provider: &Provider<Http>,
+    filter: &Filter,
+    from_block: u64,
+    to_block: u64,
+    page_size: u64,
+) -> Result<Vec<Log>> {
+    let mut logs = vec![];
+    let mut page_size = page_size.max(1);
+    let mut start = from_block;
+    while start <= to_block {
+        let end = to_block.min(start.saturating_add(page_size - 1));
+        let page_filter = filter.clone().from_block(start).to_block(end);
+        match provider.get_logs(&page_filter).await {
+            Ok(page) => {
+                info!("blocks {start}-{end}: {} logs", page.len());
+                logs.extend(page);
+                start = end + 1;
+            }
+            // 节点通常限制单次查询的区块范围或返回数量
+            Err(e) if page_size > 1 => {
+                page_size /= 2;
+                info!("get logs {start}-{end} failed: {e}, retry with page size {page_size}");
+            }
+            Err(e) => bail!("get logs {start}-{end} failed: {e}"),
+        }
+    }
+    Ok(logs)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogRecord {
+    pub block_number: u64,
+    pub transaction_hash: String,
+    pub log_index: u64,
+    pub address: String,
+    pub event: String,
+    pub params: String,
+}
+
+/// 按topic0匹配事件并解码，同名事件按indexed参数个数区分，无法解码时输出原始topics和data
+pub fn decode_log(log: &Log, events: &[Event]) -> LogRecord {
+    let raw = RawLog {
+        topics: log.topics.clone(),
+        data: log.data.to_vec(),
+    };
+    let decoded = log.topics.first().and_then(|topic0| {
+        events
+            .iter()
+            .filter(|event| event.signature() == *topic0)
+            .find_map(|event| event.parse_log(raw.clone()).ok().map(|log| (event, log)))
+    });
+    let (event, params) = match decoded {
+        Some((event, decoded)) => {
+            let params: Vec<String> = decoded
+                .params
+                .iter()
+                .map(|p| format!("{}={}", p.name, format_token(&p.value, None)))
+                .collect();
+            (event_signature(event), params.join("; "))
+        }
+        None => {
+            let topics: Vec<String> = log.topics.iter().map(|t| format!("{t:?}")).collect();
+            (
+                String::new(),
+                format!(
+                    "topics=[{}]; data=0x{}",
+                    topics.join(", "),
+                    u8_array_convert_string(&log.data)
+                ),
+            )
+        }
+    };
+    LogRecord {
+        block_number: log.block_number.unwrap_or_default().as_u64(),
+        transaction_hash: log
+            .transaction_hash
+            .map(|hash| format!("{hash:?}"))
+            .unwrap_or_default(),
+        log_index: log.log_index.unwrap_or_default().as_u64(),
+        address: to_checksum(&log.address, None),
+        event,
+        params,
+    }
+}
+
+pub async fn call(args: CallArgs, profile: &NetworkProfile) -> Result<Vec<DecodedParam>> {
+    let format = RecordFormat::from_str(&args.format)?;
+    let provider = Provider::<Http>::try_from(profile.rpc_url(args.rpc_url)?.as_str())?;
+    let to = Address::from_str(&args.to)?;
+    let from = args.from.as_deref().map(Address::from_str).transpose()?;
+    let block = parse_block_id(&args.block)?;
+    // 提供abi时function可以是函数名，否则需要带返回值的签名，如 "balanceOf(address)(uint256)"
+    let (function, output_names) = match args.abi {
+        Some(abi) => {
+            let abi = load_abi(&abi)?;
+            let function = abi
+                .function_for_call(&args.function, args.args.len())?
+                .clone();
+            let names = abi.output_names(&function);
+            (function, names)
+        }
+        None => {
+            let function = parse_signature(&args.function)?;
+            let names = function
+                .outputs
+                .iter()
+                .map(ParamNames::from_param)
+                .collect();
+            (function, names)
+        }
+    };
+    let result = call_function(
+        &provider,
+        to,
+        &function,
+        &output_names,
+        &args.args,
+        block,
+        from,
+    )
+    .await?;
+    output_records(&result, format, args.output)?;
+    Ok(result)
+}
+
+/// 要解码的事件：event为签名时直接解析，为名称时从abi中查找
+fn resolve_events(abi: Option<&LoadedAbi>, event: Option<&str>) -> Result<(Vec<Event>, Vec<H256>)> {
+    let mut events = match abi {
+        Some(abi) => abi.abi.events().cloned().collect(),
+        None => common_events()?,
+    };
+    let selected = match event {
+        Some(event) if event.contains('(') => {
+            let event = parse_event(event)?;
+            events.insert(0, event.clone());
+            vec![event]
+        }
+        Some(name) => {
+            let selected: Vec<Event> = events.iter().filter(|e| e.name == name).cloned().collect();
+            if selected.is_empty() {
+                bail!("event {name} not found in abi.");
+            }
+            selected
+        }
+        None => vec![],
+    };
+    let mut topics: Vec<H256> = selected.iter().map(Event::signature).collect();
+    topics.dedup();
+    Ok((events, topics))
+}
+
+pub async fn logs(args: LogsArgs, profile: &NetworkProfile) -> Result<Vec<LogRecord>> {
+    let format = RecordFormat::from_str(&args.format)?;
+    let provider = Provider::<Http>::try_from(profile.rpc_url(args.rpc_url)?.as_str())?;
+    let abi = args.abi.as_deref().map(load_abi).transpose()?;
+    let (events, event_topics) = resolve_events(abi.as_ref(), args.event.as_deref())?;
+    let mut filter = Filter::new();
+    if let Some(address) = &args.address {
+        filter = filter.address(Address::from_str(address)?);
+    }
+    if !event_topics.is_empty() {
+        filter = filter.topic0(Topic::from(event_topics));
+    }
+    if let Some(topic) = &args.topic1 {
+        filter = filter.topic1(parse_topic(topic)?);
+    }
+    if let Some(topic) = &args.topic2 {
+        filter = filter.topic2(parse_topic(topic)?);
+    }
+    if let Some(topic) = &args.topic3 {
+        filter = filter.topic3(parse_topic(topic)?);
+    }
+    let to_block = match args.to_block {
+        Some(to_block) => to_block,
+        None => provider.get_block_number().await?.as_u64(),
+    };
+    let logs = fetch_logs(
+        &provider,
+        &filter,
+        args.from_block,
+        to_block,
+        args.page_size,
+    )
+    .await?;
+    let records: Vec<LogRecord> = logs.iter().map(|log| decode_log(log, &events)).collect();
+    info!("{} logs found.", records.len());
+    output_records(&records, format, args.output)?;
+    Ok(records)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInfo {
+    pub address: Address,
+    pub symbol: String,
+    pub decimals: u8,
+}
+
+/// U256最多有78位十进制数，decimals超过77时无法格式化
+const MAX_DECIMALS: u32 = 77;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressBalance {
+    pub address: Address,
+    pub native_token: U256,
+    pub erc20_tokens: Vec<U256>,
+}
+
+fn first_uint(tokens: Vec<Token>) -> Result<U256> {
+    tokens
+        .into_iter()
+        .next()
+        .and_then(Token::into_uint)
+        .ok_or_else(|| anyhow!("expect an uint return value."))
+}
+
+/// 查询token的symbol和decimals，部分早期token(如MKR)的symbol返回bytes32
+pub async fn token_info(
+    provider: &Provider<Http>,
+    token: Address,
+    block: BlockId,
+) -> Result<TokenInfo> {
+    let decimals = parse_signature("decimals()(uint8)")?;
+    let decimals = first_uint(call_tokens(provider, token, &decimals, &[], block).await?)?;
+    if decimals > U256::from(MAX_DECIMALS) {
+        bail!("token {token:?} decimals {decimals} exceeds {MAX_DECIMALS}.");
+    }
+    let symbol = parse_signature("symbol()(string)")?;
+    let symbol = match call_tokens(provider, token, &symbol, &[], block).await {
+        Ok(tokens) => tokens.into_iter().next().and_then(Token::into_string),
+        Err(_) => {
+            let symbol = parse_signature("symbol()(bytes32)")?;
+            call_tokens(provider, token, &symbol, &[], block)
+                .await?
+                .into_iter()
+                .next()
+                .and_then(Token::into_fixed_bytes)
+                .map(|bytes| {
+                    String::from_utf8_lossy(&bytes)
+                        .trim_end_matches('\0')
+                        .to_string()
+                })
+        }
+    };
+    Ok(TokenInfo {
+        address: token,
+        symbol: symbol.unwrap_or_else(|| to_checksum(&token, None)),
+        decimals: decimals.low_u32() as u8,
+    })
+}
+
+pub async fn address_balance(
+    provider: &Provider<Http>,
+    address: Address,
+    tokens: &[Address],
+    block: BlockId,
+) -> Result<AddressBalance> {
+    let native_token = provider
+        .get_balance(address, Some(block))
+        .await
+        .map_err(|e| anyhow!("query {address:?} balance failed: {e}"))?;
+    let balance_of = parse_signature("balanceOf(address)(uint256)")?;
+    let mut erc20_tokens = vec![];
+    for token in tokens {
+        let tokens = call_tokens(
+            provider,
+            *token,
+            &balance_of,
+            &[format!("{address:?}")],
+            block,
+        )
+        .await
+        .map_err(|e| anyhow!("query {address:?} balance of {token:?} failed: {e}"))?;
+        erc20_tokens.push(first_uint(tokens)?);
+    }
+    Ok(AddressBalance {
+        address,
+        native_token,
+        erc20_tokens,
+    })
+}
+
+/// 并发查询地址的余额，同时进行的查询不超过concurrency个，结果按输入的地址顺序返回，
+/// 单个地址查询失败时记录错误，不影响其他地址
+pub async fn scan_balances(
+    provider: &Provider<Http>,
+    addresses: &[Address],
+    tokens: &[Address],
+    block: BlockId,
+    concurrency: usize,
+) -> Result<Vec<Result<AddressBalance>>> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for (index, address) in addresses.iter().enumerate() {
+        let provider = provider.clone();
+        let tokens = tokens.to_vec();
+        let semaphore = semaphore.clone();
+        let address = *address;
+        tasks.spawn(async move {
+            let balance = match semaphore.acquire_owned().await {
+                Ok(_permit) => address_balance(&provider, address, &tokens, block).await,
+                Err(e) => Err(e.into()),
+            };
+            (index, balance)
+        });
+    }
+    let mut balances: Vec<_> = addresses.iter().map(|_| None).collect();
+    while let Some(result) = tasks.join_next().await {
+        let (index, balance) = result?;
+        balances[index] = Some(balance);
+    }
+    Ok(balances.into_iter().flatten().collect())
+}
+
+/// 地址文件每行一个地址，忽略空行和#开头的注释，csv格式时取第一列
+pub fn parse_address_list(content: &str) -> Result<Vec<Address>> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index, line.split(',').next().unwrap_or_default().trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#') && *line != "address")
+        .map(|(index, line)| {
+            Address::from_str(line)
+                .map_err(|e| anyhow!("invalid address {line} at line {}: {e}", index + 1))
+        })
+        .collect()
+}
+
+/// 每个地址的每个token一行，列与写入文件的表格一致。
+/// 前三列与Log2Csv输出的Account格式一致，余额为最小单位；之后是token地址、symbol，
+/// 以及按decimals格式化的余额。native_token在同一地址的每一行重复，未指定token时每个地址一行
+pub async fn balances(
+    args: BalancesArgs,
+    profile: &NetworkProfile,
+) -> Result<Vec<Map<String, Value>>> {
+    let provider = Provider::<Http>::try_from(profile.rpc_url(args.rpc_url)?.as_str())?;
+    let block = parse_block_id(&args.block)?;
+    let addresses = parse_address_list(&read_input(&args.addresses)?)?;
+    let tokens = args
+        .tokens
+        .iter()
+        .map(|token| Address::from_str(token.trim()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut infos = vec![];
+    for token in &tokens {
+        let info = token_info(&provider, *token, block).await?;
+        info!(
+            "token {:?}: {} decimals {}",
+            token, info.symbol, info.decimals
+        );
+        infos.push(info);
+    }
+    info!(
+        "query {} addresses with concurrency {}.",
+        addresses.len(),
+        args.concurrency
+    );
+    let balances = scan_balances(&provider, &addresses, &tokens, block, args.concurrency).await?;
+    let mut header: Vec<String> = [
+        "address",
+        "native_token",
+        "erc20_token",
+        "token",
+        "symbol",
+        "native_balance",
+        "erc20_balance",
+    ]
+    .map(String::from)
+    .to_vec();
+    // 有地址查询失败时增加error列，失败地址的余额留空
+    let failed = balances.iter().filter(|balance| balance.is_err()).count();
+    if failed > 0 {
+        warn!("query {failed} of {} addresses failed.", addresses.len());
+        header.push("error".to_string());
+    }
+    let row_tokens: Vec<Option<(usize, &TokenInfo)>> = if infos.is_empty() {
+        vec![None]
+    } else {
+        infos.iter().enumerate().map(Some).collect()
+    };
+    let mut rows: Vec<Vec<String>> = vec![];
+    for (address, balance) in addresses.iter().zip(&balances) {
+        for token in &row_tokens {
+            let mut row = vec![format!("{address:?}")];
+            let (token_address, symbol) = token
+                .map(|(_, info)| (format!("{:?}", info.address), info.symbol.clone()))
+                .unwrap_or_default();
+            match balance {
+                Ok(balance) => {
+                    let erc20 = token
+                        .map(|(index, info)| (balance.erc20_tokens[index], info.decimals as u32));
+                    row.push(balance.native_token.to_string());
+                    row.push(
+                        erc20
+                            .map(|(value, _)| value.to_string())
+                            .unwrap_or_default(),
+                    );
+                    row.extend([token_address, symbol]);
+                    row.push(format_units(balance.native_token, 18)?);
+                    row.push(
+                        erc20
+                            .map(|(value, decimals)| format_units(value, decimals))
+                            .transpose()?
+                            .unwrap_or_default(),
+                    );
+                    if failed > 0 {
+                        row.push(String::new());
+                    }
+                }
+                Err(e) => {
+                    row.extend([String::new(), String::new(), token_address, symbol]);
+                    row.extend([String::new(), String::new(), e.to_string()]);
+                }
+            }
+            rows.push(row);
+        }
+    }
+    output_table(&header, &rows, args.output)?;
+    let records = rows
+        .into_iter()
+        .map(|row| {
+            header
+                .iter()
+                .cloned()
+                .zip(row.into_iter().map(Value::String))
+                .collect()
+        })
+        .collect();
+    let balances: Vec<&AddressBalance> = balances.iter().flatten().collect();
+    let native_total = balances
+        .iter()
+        .fold(U256::zero(), |total, b| total + b.native_token);
+    info!("native token total: {}", format_units(native_total, 18)?);
+    for (index, info) in infos.iter().enumerate() {
+        let total = balances
+            .iter()
+            .fold(U256::zero(), |total, b| total + b.erc20_tokens[index]);
+        info!(
+            "{} total: {}",
+            info.symbol,
+            format_units(total, info.decimals as u32)?
+        );
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::mock_rpc::mock_rpc_server;
+    use serde_json::{json, Value};
+
+    const HOLDER: &str = "0x0ca0e077a7d81c8ba0aeb710d2cfe2aa5dd3d955";
+    const TRANSFER_TOPIC: &str =
+        "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef";
+
+    fn hex_u64(value: &Value) -> u64 {
+        u64::from_str_radix(value.as_str().unwrap().trim_start_matches("0x"), 16).unwrap()
+    }
+
+    fn handle_call(method: &str, params: &Value) -> Result<Value, String> {
+        match method {
+            "eth_call" => {
+                let tx = &params[0];
+                let data = tx["data"].as_str().or(tx["input"].as_str()).unwrap();
+                assert_eq!(
+                    data,
+                    format!("0x70a08231000000000000000000000000{}", &HOLDER[2..])
+                );
+                assert_eq!(params[1], "0x10");
+                Ok(json!(format!("0x{:064x}", 1000)))
+            }
+            _ => Err(format!("method {method} not supported")),
+        }
+    }
+
+    #[test]
+    fn test_call_function() {
+        let url = mock_rpc_server(handle_call);
+        let provider = Provider::<Http>::try_from(url.as_str()).unwrap();
+        let function = parse_signature("balanceOf(address)(uint256 balance)").unwrap();
+        let names: Vec<ParamNames> = function
+            .outputs
+            .iter()
+            .map(ParamNames::from_param)
+            .collect();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let result = rt
+            .block_on(call_function(
+                &provider,
+                Address::from_low_u64_be(1),
+                &function,
+                &names,
+                &[HOLDER.to_string()],
+                parse_block_id("16").unwrap(),
+                None,
+            ))
+            .unwrap();
+        assert_eq!(
+            result,
+            vec![DecodedParam {
+                name: "balance".to_string(),
+                kind: "uint256".to_string(),
+                value: "1000".to_string(),
+            }]
+        );
+    }
+
+    /// 每个区块一条日志，第9块为未知事件，单次最多查询4个区块
+    fn handle_logs(method: &str, params: &Value) -> Result<Value, String> {
+        assert_eq!(method, "eth_getLogs");
+        let from = hex_u64(&params[0]["fromBlock"]);
+        let to = hex_u64(&params[0]["toBlock"]);
+        if to - from + 1 > 4 {
+            return Err("block range is too wide".to_string());
+        }
+        let logs: Vec<Value> = (from..=to)
+            .filter(|block| block % 3 == 0)
+            .map(|block| {
+                let topic0 = if block == 9 {
+                    format!("0x{:064x}", 9)
+                } else {
+                    TRANSFER_TOPIC.to_string()
+                };
+                json!({
+                    "address": "0x0000000000000000000000000000000000000001",
+                    "topics": [topic0, format!("0x{:064x}", 2), format!("0x{:064x}", 3)],
+                    "data": format!("0x{:064x}", block * 100),
+                    "blockNumber": format!("0x{block:x}"),
+                    "transactionHash": format!("0x{:064x}", block),
+                    "logIndex": "0x0",
+                    "removed": false
+                })
+            })
+            .collect();
+        Ok(json!(logs))
+    }
+
+    #[test]
+    fn test_fetch_logs_paginated() {
+        let url = mock_rpc_server(handle_logs);
+        let provider = Provider::<Http>::try_from(url.as_str()).unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let logs = rt
+            .block_on(fetch_logs(&provider, &Filter::new(), 0, 20, 10))
+            .unwrap();
+        let blocks: Vec<u64> = logs
+            .iter()
+            .map(|log| log.block_number.unwrap().as_u64())
+            .collect();
+        assert_eq!(blocks, vec![0, 3, 6, 9, 12, 15, 18]);
+
+        let (events, topics) = resolve_events(None, Some("Transfer")).unwrap();
+        assert_eq!(topics, vec![H256::from_str(TRANSFER_TOPIC).unwrap()]);
+        let records: Vec<LogRecord> = logs.iter().map(|log| decode_log(log, &events)).collect();
+        assert_eq!(records[1].event, "Transfer(address,address,uint256)");
+        assert_eq!(
+            records[1].params,
+            "from=0x0000000000000000000000000000000000000002; to=0x0000000000000000000000000000000000000003; value=300"
+        );
+        assert_eq!(records[1].block_number, 3);
+        assert!(records[3].event.is_empty());
+        assert!(records[3].params.ends_with(&format!("data=0x{:064x}", 900)));
+    }
+
+    #[test]
+    fn test_decode_erc721_transfer() {
+        let log = Log {
+            topics: vec![
+                H256::from_str(TRANSFER_TOPIC).unwrap(),
+                parse_topic(HOLDER).unwrap(),
+                H256::from_low_u64_be(2),
+                H256::from_low_u64_be(42),
+            ],
+            ..Default::default()
+        };
+        let record = decode_log(&log, &common_events().unwrap());
+        assert!(record.params.ends_with("tokenId=42"));
+    }
+
+    #[test]
+    fn test_parse_block_id() {
+        assert_eq!(
+            parse_block_id("latest").unwrap(),
+            BlockId::Number(BlockNumber::Latest)
+        );
+        assert_eq!(
+            parse_block_id("0x10").unwrap(),
+            parse_block_id("16").unwrap()
+        );
+        assert!(matches!(
+            parse_block_id(TRANSFER_TOPIC).unwrap(),
+            BlockId::Hash(_)
+        ));
+        assert!(parse_block_id("yesterday").is_err());
+        assert!(parse_topic("0x1234").is_err());
+    }
+
+    fn padded_address(data: &str) -> String {
+        data[data.len() - 40..].to_string()
+    }
+
+    /// 地址的最后一个字节作为余额，token地址的最后一个字节作为倍数
+    fn handle_balances(method: &str, params: &Value) -> Result<Value, String> {
+        match method {
+            "eth_getBalance" => {
+                let address = Address::from_str(params[0].as_str().unwrap()).unwrap();
+                Ok(json!(format!("0x{:x}", address.as_bytes()[19])))
+            }
+            "eth_call" => {
+                let tx = &params[0];
+                let token = Address::from_str(tx["to"].as_str().unwrap()).unwrap();
+                let data = tx["data"].as_str().or(tx["input"].as_str()).unwrap();
+                let multiple = token.as_bytes()[19] as u64;
+                match &data[..10] {
+                    "0x313ce567" if multiple == 0xff => Ok(json!(format!("0x{:064x}", 78))),
+                    "0x313ce567" => Ok(json!(format!("0x{:064x}", 6))),
+                    // symbol为string和bytes32两种返回值
+                    "0x95d89b41" if multiple == 1 => Ok(json!(format!(
+                        "0x{}",
+                        u8_array_convert_string(&ethers::abi::encode(&[Token::String(
+                            "USDT".to_string()
+                        )]))
+                    ))),
+                    "0x95d89b41" => Ok(json!(
+                        "0x4d4b520000000000000000000000000000000000000000000000000000000000"
+                    )),
+                    "0x70a08231" => {
+                        let holder = Address::from_str(&padded_address(data)).unwrap();
+                        if holder.as_bytes()[19] == 0x0b {
+                            return Err("execution reverted".to_string());
+                        }
+                        Ok(json!(format!(
+                            "0x{:064x}",
+                            holder.as_bytes()[19] as u64 * multiple
+                        )))
+                    }
+                    _ => Err("unknown selector".to_string()),
+                }
+            }
+            _ => Err(format!("method {method} not supported")),
+        }
+    }
+
+    #[test]
+    fn test_scan_balances() {
+        let url = mock_rpc_server(handle_balances);
+        let provider = Provider::<Http>::try_from(url.as_str()).unwrap();
+        let addresses = parse_address_list(
+            "# holders\naddress,native_token\n0x0000000000000000000000000000000000000005,0\n\n0x0000000000000000000000000000000000000007\n0x0000000000000000000000000000000000000009",
+        )
+        .unwrap();
+        assert_eq!(addresses.len(), 3);
+        assert!(parse_address_list("0x1234").is_err());
+        let tokens = vec![Address::from_low_u64_be(1), Address::from_low_u64_be(10)];
+        let block = parse_block_id("latest").unwrap();
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let usdt = rt
+            .block_on(token_info(&provider, tokens[0], block))
+            .unwrap();
+        assert_eq!(usdt.symbol, "USDT");
+        assert_eq!(usdt.decimals, 6);
+        let mkr = rt
+            .block_on(token_info(&provider, tokens[1], block))
+            .unwrap();
+        assert_eq!(mkr.symbol, "MKR");
+        assert!(rt
+            .block_on(token_info(&provider, Address::from_low_u64_be(0xff), block))
+            .is_err());
+        let balances = rt
+            .block_on(scan_balances(&provider, &addresses, &tokens, block, 2))
+            .unwrap();
+        let values: Vec<(u64, u64, Vec<u64>)> = balances
+            .iter()
+            .map(|b| {
+                let b = b.as_ref().unwrap();
+                (
+                    b.address.to_low_u64_be(),
+                    b.native_token.as_u64(),
+                    b.erc20_tokens.iter().map(U256::as_u64).collect(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                (5, 5, vec![5, 50]),
+                (7, 7, vec![7, 70]),
+                (9, 9, vec![9, 90])
+            ]
+        );
+    }
+
+    #[test]
+    fn test_balances() {
+        use crate::output::{pipe, run_cli};
+
+        let url = mock_rpc_server(handle_balances);
+        let addresses = pipe(
+            "balances-addresses",
+            "0x0000000000000000000000000000000000000005\n0x000000000000000000000000000000000000000b\n0x0000000000000000000000000000000000000007",
+        );
+        let output = pipe("balances.csv", "");
+        // 每个地址的每个token一行，两个token的symbol都是MKR，按token地址区分
+        let records = run_cli(&[
+            "--output-format",
+            "json",
+            "eth",
+            "balances",
+            "-r",
+            &url,
+            "-a",
+            &addresses,
+            "-t",
+            "0x0000000000000000000000000000000000000002,0x0000000000000000000000000000000000000003",
+            "-o",
+            &output,
+        ])
+        .unwrap();
+        let records: Vec<Map<String, Value>> = serde_json::from_str(&records).unwrap();
+        assert_eq!(records.len(), 6);
+        let token2 = "0x0000000000000000000000000000000000000002";
+        let token3 = "0x0000000000000000000000000000000000000003";
+        assert_eq!(records[0]["token"], token2);
+        assert_eq!(records[0]["symbol"], "MKR");
+        assert_eq!(records[0]["native_token"], "5");
+        assert_eq!(records[0]["erc20_token"], "10");
+        assert_eq!(records[0]["native_balance"], "0.000000000000000005");
+        assert_eq!(records[0]["erc20_balance"], "0.000010");
+        assert_eq!(records[0]["error"], "");
+        assert_eq!(records[1]["token"], token3);
+        assert_eq!(records[1]["native_token"], "5");
+        assert_eq!(records[1]["erc20_token"], "15");
+        // 查询失败的地址记录错误，不影响其他地址
+        for record in &records[2..4] {
+            assert_eq!(record["erc20_token"], "");
+            assert_eq!(record["erc20_balance"], "");
+            assert!(record["error"]
+                .as_str()
+                .unwrap()
+                .contains("execution reverted"));
+        }
+        assert_eq!(records[5]["native_token"], "7");
+        assert_eq!(records[5]["token"], token3);
+        assert_eq!(records[5]["erc20_token"], "21");
+        assert_eq!(records[5]["erc20_balance"], "0.000021");
+
+        // 写入的csv前三列与Account格式一致
+        let csv = std::fs::read_to_string(&output).unwrap();
+        let mut lines = csv.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            "address,native_token,erc20_token,token,symbol,native_balance,erc20_balance,error"
+        );
+        assert!(lines
+            .next()
+            .unwrap()
+            .starts_with("0x0000000000000000000000000000000000000005,5,10,"));
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::types::transaction::eip712::{Eip712, TypedData};
+use ethers::utils::hex;
+use secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+use secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::str::FromStr;
+use tracing::info;
+
+use crate::encrypt_decrypt::{read_input, write_output};
+use crate::eth::pub_key_to_address;
+use crate::util::{hex_string_2_array, u8_array_convert_string};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedMessage {
+    pub address: String,
+    pub hash: String,
+    pub signature: String,
+}
+
+/// 0x开头的合法hex按原始字节处理，其他按utf8文本处理，与cast wallet sign保持一致
+fn message_bytes(message: &str) -> Vec<u8> {
+    match message.strip_prefix("0x") {
+        Some(hex) if hex.len() % 2 == 0 && hex.chars().all(|c| c.is_ascii_hexdigit()) => {
+            hex_string_2_array(hex)
+        }
+        _ => message.as_bytes().to_vec(),
+    }
+}
+
+/// EIP-191 personal_sign: keccak256("\x19Ethereum Signed Message:\n" + len(message) + message)
+pub fn hash_message(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()).as_bytes());
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// EIP-712: keccak256("\x19\x01" + domainSeparator + hashStruct(message))
+pub fn hash_typed_data(typed_data: &str) -> Result<[u8; 32]> {
+    let typed_data: TypedData =
+        serde_json::from_str(typed_data).map_err(|e| anyhow!("invalid typed data: {e}"))?;
+    typed_data
+        .encode_eip712()
+        .map_err(|e| anyhow!("encode typed data failed: {e}"))
+}
+
+/// 签名结果为65字节 r + s + v，v为27或28
+pub fn sign_hash(private_key: &str, hash: &[u8; 32]) -> Result<Vec<u8>> {
+    let secret_key = SecretKey::from_str(private_key.trim_start_matches("0x"))?;
+    let message = Message::from_slice(hash)?;
+    let (recovery_id, signature) = Secp256k1::new()
+        .sign_ecdsa_recoverable(&message, &secret_key)
+        .serialize_compact();
+    let mut result = signature.to_vec();
+    result.push(27 + recovery_id.to_i32() as u8);
+    Ok(result)
+}
+
+/// 由签名恢复签名者地址，v兼容0/1和27/28两种写法
+pub fn recover_address(hash: &[u8; 32], signature: &[u8]) -> Result<String> {
+    if signature.len() != 65 {
+        bail!("signature must be 65 bytes, got {}", signature.len());
+    }
+    let v = match signature[64] {
+        v @ 0..=1 => v,
+        v @ 27..=28 => v - 27,
+        v => bail!("invalid signature v: {v}"),
+    };
+    let recovery_id = RecoveryId::from_i32(v as i32)?;
+    let signature = RecoverableSignature::from_compact(&signature[..64], recovery_id)?;
+    let public_key: PublicKey =
+        Secp256k1::new().recover_ecdsa(&Message::from_slice(hash)?, &signature)?;
+    Ok(pub_key_to_address(public_key))
+}
+
+fn sign(private_key: &str, hash: [u8; 32], output: Option<String>) -> Result<SignedMessage> {
+    let signature = sign_hash(private_key, &hash)?;
+    let signed = SignedMessage {
+        address: recover_address(&hash, &signature)?,
+        hash: format!("0x{}", u8_array_convert_string(&hash)),
+        signature: format!("0x{}", u8_array_convert_string(&signature)),
+    };
+    info!("signer: {}", signed.address);
+    write_output(output, &serde_json::to_string_pretty(&signed)?)?;
+    Ok(signed)
+}
+
+pub fn sign_message(
+    private_key: String,
+    message: String,
+    output: Option<String>,
+) -> Result<SignedMessage> {
+    sign(&private_key, hash_message(&message_bytes(&message)), output)
+}
+
+pub fn sign_typed_data(
+    private_key: String,
+    input: String,
+    output: Option<String>,
+) -> Result<SignedMessage> {
+    sign(&private_key, hash_typed_data(&read_input(&input)?)?, output)
+}
+
+/// 待验证的hash：EIP-191消息、EIP-712 typed data文件或者已经计算好的32字节hash，三者只能有一个
+pub fn resolve_hash(
+    message: Option<String>,
+    typed_data: Option<String>,
+    hash: Option<String>,
+) -> Result<[u8; 32]> {
+    match (message, typed_data, hash) {
+        (Some(message), None, None) => Ok(hash_message(&message_bytes(&message))),
+        (None, Some(typed_data), None) => hash_typed_data(&read_input(&typed_data)?),
+        (None, None, Some(hash)) => {
+            let hash = hash.trim_start_matches("0x");
+            if hash.len() != 64 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                bail!("hash must be 32 bytes hex.");
+            }
+            let mut result = [0u8; 32];
+            result.copy_from_slice(&hex_string_2_array(hash));
+            Ok(result)
+        }
+        _ => bail!("params error: one of message, typed_data or hash is required."),
+    }
+}
+
+fn parse_signature_hex(signature: &str) -> Result<Vec<u8>> {
+    hex::decode(signature.trim().trim_start_matches("0x"))
+        .map_err(|e| anyhow!("invalid signature hex: {e}"))
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RecoveredSigner {
+    pub hash: String,
+    pub address: String,
+}
+
+pub fn recover(hash: [u8; 32], signature: String) -> Result<RecoveredSigner> {
+    let address = recover_address(&hash, &parse_signature_hex(&signature)?)?;
+    Ok(RecoveredSigner {
+        hash: format!("0x{}", u8_array_convert_string(&hash)),
+        address,
+    })
+}
+
+/// 签名者与地址不一致时返回错误
+pub fn verify(hash: [u8; 32], signature: String, address: String) -> Result<RecoveredSigner> {
+    let signer = recover(hash, signature)?;
+    if !signer.address.eq_ignore_ascii_case(&address) {
+        bail!(
+            "signature mismatch: signed by {}, expect {address}",
+            signer.address
+        );
+    }
+    Ok(signer)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_personal_sign() {
+        let private_key = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let hash = hash_message(&message_bytes("Some data"));
+        assert_eq!(
+            u8_array_convert_string(&hash),
+            "1da44b586eb0729ff70a73c326926f6ed5a25f5b056e7f47fbc6e58d86871655"
+        );
+        let signature = sign_hash(private_key, &hash).unwrap();
+        assert_eq!(u8_array_convert_string(&signature), "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c");
+        assert_eq!(
+            recover_address(&hash, &signature).unwrap(),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+        // v为0/1的写法
+        let mut signature = signature;
+        signature[64] -= 27;
+        assert_eq!(
+            recover_address(&hash, &signature).unwrap(),
+            "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23"
+        );
+        assert!(recover_address(&hash, &signature[..64]).is_err());
+        assert_eq!(message_bytes("0x1234"), vec![0x12, 0x34]);
+        assert_eq!(message_bytes("0x123"), b"0x123".to_vec());
+    }
+
+    #[test]
+    fn test_sign_typed_data() {
+        // EIP-712规范中的Mail示例，私钥为keccak256("cow")
+        let typed_data = r#"{
+            "types": {
+                "EIP712Domain": [
+                    {"name": "name", "type": "string"},
+                    {"name": "version", "type": "string"},
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "Person": [
+                    {"name": "name", "type": "string"},
+                    {"name": "wallet", "type": "address"}
+                ],
+                "Mail": [
+                    {"name": "from", "type": "Person"},
+                    {"name": "to", "type": "Person"},
+                    {"name": "contents", "type": "string"}
+                ]
+            },
+            "primaryType": "Mail",
+            "domain": {
+                "name": "Ether Mail",
+                "version": "1",
+                "chainId": 1,
+                "verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+            },
+            "message": {
+                "from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+                "to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+                "contents": "Hello, Bob!"
+            }
+        }"#;
+        let private_key = "c85ef7d79691fe79573b1a7064c19c1a9819ebdbd1faaab1a8ec92344438aaf4";
+        let hash = hash_typed_data(typed_data).unwrap();
+        assert_eq!(
+            u8_array_convert_string(&hash),
+            "be609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2"
+        );
+        let signature = sign_hash(private_key, &hash).unwrap();
+        assert_eq!(u8_array_convert_string(&signature), "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c");
+        assert!(verify(
+            hash,
+            u8_array_convert_string(&signature),
+            "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826".to_string()
+        )
+        .is_ok());
+        assert!(verify(
+            hash,
+            u8_array_convert_string(&signature),
+            "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB".to_string()
+        )
+        .is_err());
+    }
+}
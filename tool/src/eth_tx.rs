@@ -0,0 +1,657 @@
+use anyhow::{anyhow, bail, Result};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers::types::transaction::eip2930::AccessList;
+use ethers::{
+    prelude::*,
+    signers::LocalWallet,
+    types::{Eip1559TransactionRequest, Eip2930TransactionRequest, TransactionRequest},
+    utils::keccak256,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::str::FromStr;
+use tracing::info;
+
+use crate::cli::TxBuildArgs;
+use crate::config::NetworkProfile;
+use crate::encrypt_decrypt::read_input;
+use crate::eth::parse_wei;
+use crate::output::Output;
+use crate::util::u8_array_convert_string;
+
+/// 离线签名的流程拆分为三步：
+/// 1. 在联网机器上构建未签名交易(可选从rpc补全nonce、gas等字段)并保存为json
+/// 2. 在离线机器上读取json并签名，整个过程不访问网络
+/// 3. 将签名后的raw交易在联网机器上广播
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxType {
+    Legacy,
+    Eip2930,
+    Eip1559,
+}
+
+impl FromStr for TxType {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "legacy" | "0" => Ok(TxType::Legacy),
+            "eip2930" | "2930" | "1" => Ok(TxType::Eip2930),
+            "eip1559" | "1559" | "2" => Ok(TxType::Eip1559),
+            _ => Err(anyhow!("transaction type {s} not supported.")),
+        }
+    }
+}
+
+/// 未签名交易的文件格式，legacy交易序列化时会丢弃chainId，因此单独记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedTransaction {
+    pub chain_id: u64,
+    pub transaction: TypedTransaction,
+}
+
+impl UnsignedTransaction {
+    pub fn new(transaction: TypedTransaction) -> Result<Self> {
+        let chain_id = transaction
+            .chain_id()
+            .ok_or_else(|| anyhow!("transaction missing fields: chain_id"))?;
+        Ok(UnsignedTransaction {
+            chain_id: chain_id.as_u64(),
+            transaction,
+        })
+    }
+
+    pub fn into_transaction(self) -> TypedTransaction {
+        let mut tx = self.transaction;
+        tx.set_chain_id(self.chain_id);
+        tx
+    }
+}
+
+/// 交易的gas费用选项，单位均为wei
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FeeOptions {
+    pub gas_price: Option<U256>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+}
+
+impl FeeOptions {
+    /// 费用参数支持带单位的输入，如 "30 GWei"
+    pub fn parse(
+        gas_price: Option<String>,
+        max_fee_per_gas: Option<String>,
+        max_priority_fee_per_gas: Option<String>,
+    ) -> Result<Self> {
+        let parse = |value: Option<String>| value.map(|v| parse_wei(&v)).transpose();
+        let fees = FeeOptions {
+            gas_price: parse(gas_price)?,
+            max_fee_per_gas: parse(max_fee_per_gas)?,
+            max_priority_fee_per_gas: parse(max_priority_fee_per_gas)?,
+        };
+        if fees.gas_price.is_some()
+            && (fees.max_fee_per_gas.is_some() || fees.max_priority_fee_per_gas.is_some())
+        {
+            bail!("gas_price can not be used together with eip1559 fees.");
+        }
+        Ok(fees)
+    }
+}
+
+/// 签名后的交易，raw_transaction可直接用于eth_sendRawTransaction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub raw_transaction: String,
+    pub hash: String,
+}
+
+pub fn parse_hex_data(data: &str) -> Result<Bytes> {
+    Bytes::from_str(data).map_err(|e| anyhow!("invalid hex data {data}: {e}"))
+}
+
+pub fn new_typed_transaction(tx_type: TxType, access_list: Option<AccessList>) -> TypedTransaction {
+    let access_list = access_list.unwrap_or_default();
+    match tx_type {
+        TxType::Legacy => TransactionRequest::new().into(),
+        TxType::Eip2930 => {
+            Eip2930TransactionRequest::new(TransactionRequest::new(), access_list).into()
+        }
+        TxType::Eip1559 => Eip1559TransactionRequest::new()
+            .access_list(access_list)
+            .into(),
+    }
+}
+
+/// 将交易转换为指定的类型，保留from、to、value、data、nonce、gas与chain_id
+pub fn convert_tx_type(tx: &TypedTransaction, tx_type: TxType) -> TypedTransaction {
+    let mut converted = new_typed_transaction(tx_type, tx.access_list().cloned());
+    if let Some(from) = tx.from() {
+        converted.set_from(*from);
+    }
+    if let Some(to) = tx.to() {
+        converted.set_to(to.clone());
+    }
+    if let Some(value) = tx.value() {
+        converted.set_value(*value);
+    }
+    if let Some(data) = tx.data() {
+        converted.set_data(data.clone());
+    }
+    if let Some(nonce) = tx.nonce() {
+        converted.set_nonce(*nonce);
+    }
+    if let Some(gas) = tx.gas() {
+        converted.set_gas(*gas);
+    }
+    if let Some(chain_id) = tx.chain_id() {
+        converted.set_chain_id(chain_id);
+    }
+    converted
+}
+
+/// 指定gas_price时使用legacy交易，否则构建eip1559交易，
+/// 未指定的费用通过eth_feeHistory估算，链不支持eip1559时回退为legacy交易
+pub async fn apply_fees(
+    provider: &Provider<Http>,
+    tx: TypedTransaction,
+    fees: &FeeOptions,
+) -> Result<TypedTransaction> {
+    if fees.gas_price.is_some() {
+        let mut tx = convert_tx_type(&tx, TxType::Legacy);
+        set_fees(&mut tx, fees.gas_price, None, None)?;
+        return Ok(tx);
+    }
+    let (max_fee, priority_fee) = match (fees.max_fee_per_gas, fees.max_priority_fee_per_gas) {
+        (Some(max_fee), Some(priority_fee)) => (max_fee, priority_fee),
+        (max_fee, priority_fee) => match provider.estimate_eip1559_fees(None).await {
+            Ok((estimate_max_fee, estimate_priority_fee)) => {
+                let priority_fee = match (max_fee, priority_fee) {
+                    (_, Some(priority_fee)) => priority_fee,
+                    // 只指定max_fee时，估算的priority_fee不能超过max_fee
+                    (Some(max_fee), None) => estimate_priority_fee.min(max_fee),
+                    (None, None) => estimate_priority_fee,
+                };
+                // 估算的max_fee = 2 * base_fee + priority_fee，替换为实际使用的priority_fee
+                let max_fee = max_fee.unwrap_or(
+                    estimate_max_fee.saturating_sub(estimate_priority_fee) + priority_fee,
+                );
+                info!("estimate eip1559 fees, max_fee_per_gas: {estimate_max_fee}, max_priority_fee_per_gas: {estimate_priority_fee}");
+                (max_fee, priority_fee)
+            }
+            Err(e) if max_fee.is_none() && priority_fee.is_none() => {
+                info!("estimate eip1559 fees failed: {e}, fallback to legacy transaction.");
+                return Ok(convert_tx_type(&tx, TxType::Legacy));
+            }
+            Err(e) => return Err(e.into()),
+        },
+    };
+    if max_fee < priority_fee {
+        bail!("max_fee_per_gas must not be less than max_priority_fee_per_gas.");
+    }
+    let mut tx = convert_tx_type(&tx, TxType::Eip1559);
+    set_fees(&mut tx, None, Some(max_fee), Some(priority_fee))?;
+    Ok(tx)
+}
+
+/// 设置gas费用，legacy与eip2930使用gas_price，eip1559使用max_fee和priority_fee
+pub fn set_fees(
+    tx: &mut TypedTransaction,
+    gas_price: Option<U256>,
+    max_fee_per_gas: Option<U256>,
+    max_priority_fee_per_gas: Option<U256>,
+) -> Result<()> {
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            if gas_price.is_some() {
+                bail!("eip1559 transaction uses max_fee_per_gas instead of gas_price.");
+            }
+            inner.max_fee_per_gas = max_fee_per_gas.or(inner.max_fee_per_gas);
+            inner.max_priority_fee_per_gas =
+                max_priority_fee_per_gas.or(inner.max_priority_fee_per_gas);
+        }
+        _ => {
+            if max_fee_per_gas.is_some() || max_priority_fee_per_gas.is_some() {
+                bail!("max_fee_per_gas/max_priority_fee_per_gas need an eip1559 transaction.");
+            }
+            if let Some(gas_price) = gas_price {
+                tx.set_gas_price(gas_price);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 检查签名所需的字段是否完整，离线签名时无法再从链上补全
+pub fn check_ready_to_sign(tx: &TypedTransaction) -> Result<()> {
+    let mut missing = vec![];
+    if tx.chain_id().is_none() {
+        missing.push("chain_id");
+    }
+    if tx.nonce().is_none() {
+        missing.push("nonce");
+    }
+    if tx.gas().is_none() {
+        missing.push("gas");
+    }
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            if inner.max_fee_per_gas.is_none() {
+                missing.push("max_fee_per_gas");
+            }
+            if inner.max_priority_fee_per_gas.is_none() {
+                missing.push("max_priority_fee_per_gas");
+            }
+        }
+        _ => {
+            if tx.gas_price().is_none() {
+                missing.push("gas_price");
+            }
+        }
+    }
+    if !missing.is_empty() {
+        bail!(
+            "transaction missing fields: {}, set them explicitly or build with --rpc-url or --network.",
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+pub fn sign_typed_transaction(
+    wallet: &LocalWallet,
+    tx: &TypedTransaction,
+) -> Result<SignedTransaction> {
+    check_ready_to_sign(tx)?;
+    let sig = wallet.sign_transaction_sync(tx)?;
+    let raw = tx.rlp_signed(&sig);
+    Ok(SignedTransaction {
+        raw_transaction: format!("0x{}", u8_array_convert_string(&raw)),
+        hash: format!("0x{}", u8_array_convert_string(&keccak256(&raw))),
+    })
+}
+
+pub async fn broadcast_raw_transaction(provider: &Provider<Http>, raw: &str) -> Result<TxHash> {
+    let pending_tx = provider.send_raw_transaction(parse_hex_data(raw)?).await?;
+    Ok(pending_tx.tx_hash())
+}
+
+/// 未指定的chain id、gas和费用使用网络配置中的默认值
+pub async fn build_transaction(args: TxBuildArgs, profile: &NetworkProfile) -> Result<Output> {
+    let access_list = match args.access_list {
+        Some(path) => Some(serde_json::from_str::<AccessList>(&fs::read_to_string(
+            path,
+        )?)?),
+        None => None,
+    };
+    let mut tx = new_typed_transaction(TxType::from_str(&args.tx_type)?, access_list);
+    tx.set_to(args.to.parse::<Address>()?);
+    tx.set_value(args.value);
+    tx.set_chain_id(profile.chain_id(args.chain_id)?);
+    if let Some(from) = args.from {
+        tx.set_from(from.parse::<Address>()?);
+    }
+    if let Some(data) = args.data {
+        tx.set_data(parse_hex_data(&data)?);
+    }
+    if let Some(nonce) = args.nonce {
+        tx.set_nonce(nonce);
+    }
+    if let Some(gas_limit) = profile.gas_limit(args.gas_limit) {
+        tx.set_gas(gas_limit);
+    }
+    let fees = profile.fees(
+        args.gas_price,
+        args.max_fee_per_gas,
+        args.max_priority_fee_per_gas,
+    )?;
+    set_fees(
+        &mut tx,
+        fees.gas_price,
+        fees.max_fee_per_gas,
+        fees.max_priority_fee_per_gas,
+    )?;
+    if let Some(rpc_url) = args.rpc_url.or_else(|| profile.rpc_url.clone()) {
+        // 从链上补全缺失的nonce、gas与费用，fill_transaction不补全nonce，按from地址的pending交易数查询
+        let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+        if tx.nonce().is_none() {
+            let from = *tx
+                .from()
+                .ok_or_else(|| anyhow!("from is required to fill nonce from rpc."))?;
+            let nonce = provider
+                .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+                .await?;
+            tx.set_nonce(nonce);
+        }
+        provider.fill_transaction(&mut tx, None).await?;
+    }
+    if let Err(e) = check_ready_to_sign(&tx) {
+        info!("unsigned transaction is not complete yet: {e}");
+    }
+    let unsigned = UnsignedTransaction::new(tx)?;
+    Output::artifact(args.output, "unsigned_transaction", &unsigned)
+}
+
+pub fn sign_transaction_file(
+    input: String,
+    private_key: String,
+    output: Option<String>,
+) -> Result<Output> {
+    let unsigned: UnsignedTransaction = serde_json::from_str(&read_input(&input)?)?;
+    let tx = unsigned.into_transaction();
+    let wallet = private_key.as_str().parse::<LocalWallet>()?;
+    // from决定了tx-build查询的nonce，与签名地址不一致时交易无法上链
+    if let Some(from) = tx.from() {
+        if *from != wallet.address() {
+            bail!(
+                "transaction from {from:?} does not match the signing key address {:?}.",
+                wallet.address()
+            );
+        }
+    }
+    let signed = sign_typed_transaction(&wallet, &tx)?;
+    info!("from: {:?}, txHash: {}", wallet.address(), signed.hash);
+    Output::artifact(output, "signed_transaction", &signed)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BroadcastTransaction {
+    pub tx_hash: TxHash,
+}
+
+pub async fn broadcast_transaction(
+    rpc_url: String,
+    raw: Option<String>,
+    input: Option<String>,
+) -> Result<BroadcastTransaction> {
+    let raw = match (raw, input) {
+        (Some(raw), _) => raw,
+        (None, Some(input)) => {
+            let signed: SignedTransaction = serde_json::from_str(&read_input(&input)?)?;
+            signed.raw_transaction
+        }
+        (None, None) => bail!("params error: raw or input must have one."),
+    };
+    let provider = Provider::<Http>::try_from(rpc_url.as_str())?;
+    let tx_hash = broadcast_raw_transaction(&provider, &raw).await?;
+    Ok(BroadcastTransaction { tx_hash })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ethers::utils::rlp::Rlp;
+    use serde_json::{json, Value};
+
+    const TEST_PRIVATE_KEY: &str =
+        "1cb90607624a78a065b51ded6fc701c381aa6b0aef37ed278f15774dd5b85758";
+
+    fn complete_tx(tx_type: TxType) -> TypedTransaction {
+        let mut tx = new_typed_transaction(tx_type, None);
+        tx.set_to(
+            "0x9BF5a8AF3333e2bF300FB00A0B7B8aDddc90dd43"
+                .parse::<Address>()
+                .unwrap(),
+        );
+        tx.set_value(100000000000000000u128);
+        tx.set_chain_id(5);
+        tx.set_nonce(3);
+        tx.set_gas(21000);
+        if tx_type == TxType::Eip1559 {
+            set_fees(
+                &mut tx,
+                None,
+                Some(30_000_000_000u64.into()),
+                Some(1_000_000_000u64.into()),
+            )
+            .unwrap();
+        } else {
+            set_fees(&mut tx, Some(20_000_000_000u64.into()), None, None).unwrap();
+        }
+        tx
+    }
+
+    #[test]
+    fn test_sign_offline() {
+        let wallet = TEST_PRIVATE_KEY.parse::<LocalWallet>().unwrap();
+        for tx_type in [TxType::Legacy, TxType::Eip2930, TxType::Eip1559] {
+            let tx = complete_tx(tx_type);
+            // 未签名交易经过json保存后再读取，内容保持不变
+            let json = serde_json::to_string(&UnsignedTransaction::new(tx).unwrap()).unwrap();
+            let unsigned: UnsignedTransaction = serde_json::from_str(&json).unwrap();
+            let tx = unsigned.into_transaction();
+            let signed = sign_typed_transaction(&wallet, &tx).unwrap();
+            let raw = parse_hex_data(&signed.raw_transaction).unwrap();
+            let (decoded, sig) = TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap();
+            assert_eq!(decoded.nonce(), Some(&U256::from(3)));
+            assert_eq!(decoded.chain_id(), Some(U64::from(5)));
+            assert_eq!(sig.recover(decoded.sighash()).unwrap(), wallet.address());
+            assert_eq!(signed.hash.len(), 66);
+        }
+    }
+
+    #[test]
+    fn test_sign_from_mismatch() {
+        use crate::output::pipe;
+
+        let wallet = TEST_PRIVATE_KEY.parse::<LocalWallet>().unwrap();
+        let unsigned = |from: Address| {
+            let mut tx = complete_tx(TxType::Eip1559);
+            tx.set_from(from);
+            serde_json::to_string(&UnsignedTransaction::new(tx).unwrap()).unwrap()
+        };
+        let other = pipe(
+            "unsigned-other-from.json",
+            &unsigned(Address::from_low_u64_be(2)),
+        );
+        let err = sign_transaction_file(other, TEST_PRIVATE_KEY.to_string(), None).unwrap_err();
+        assert!(err.to_string().contains("does not match"), "{err}");
+        let own = pipe("unsigned-own-from.json", &unsigned(wallet.address()));
+        assert!(sign_transaction_file(own, TEST_PRIVATE_KEY.to_string(), None).is_ok());
+    }
+
+    #[test]
+    fn test_sign_incomplete_tx() {
+        let wallet = TEST_PRIVATE_KEY.parse::<LocalWallet>().unwrap();
+        let mut tx = new_typed_transaction(TxType::Eip1559, None);
+        tx.set_chain_id(5);
+        tx.set_gas(21000);
+        let err = sign_typed_transaction(&wallet, &tx)
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("nonce"));
+        assert!(err.contains("max_fee_per_gas"));
+        assert!(set_fees(&mut tx, Some(1u64.into()), None, None).is_err());
+        assert_eq!(TxType::from_str("2").unwrap(), TxType::Eip1559);
+    }
+
+    #[test]
+    fn test_fee_options() {
+        let fees = FeeOptions::parse(
+            None,
+            Some("30 GWei".to_string()),
+            Some("1.5gwei".to_string()),
+        )
+        .unwrap();
+        assert_eq!(fees.max_fee_per_gas, Some(U256::from(30_000_000_000u64)));
+        assert_eq!(
+            fees.max_priority_fee_per_gas,
+            Some(U256::from(1_500_000_000u64))
+        );
+        assert!(FeeOptions::parse(Some("1".to_string()), Some("1".to_string()), None).is_err());
+        let tx = complete_tx(TxType::Legacy);
+        let converted = convert_tx_type(&tx, TxType::Eip1559);
+        assert!(matches!(converted, TypedTransaction::Eip1559(_)));
+        assert_eq!(converted.nonce(), tx.nonce());
+        assert_eq!(converted.chain_id(), tx.chain_id());
+        assert_eq!(converted.to(), tx.to());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        use crate::output::{pipe, run_cli};
+
+        // tx-build的输出可以直接作为tx-sign的输入，tx-sign的输出可以作为tx-broadcast的输入
+        let unsigned = run_cli(&[
+            "eth",
+            "tx-build",
+            "-t",
+            "0x0000000000000000000000000000000000000001",
+            "-v",
+            "1",
+            "-i",
+            "5",
+            "-n",
+            "3",
+            "-l",
+            "21000",
+            "--max-fee-per-gas",
+            "30 gwei",
+            "--max-priority-fee-per-gas",
+            "1 gwei",
+        ])
+        .unwrap();
+        let input = pipe("unsigned-tx.json", &unsigned);
+        let signed = run_cli(&["eth", "tx-sign", "-f", &input, "-s", TEST_PRIVATE_KEY]).unwrap();
+        let signed: SignedTransaction = serde_json::from_str(&signed).unwrap();
+        let raw = parse_hex_data(&signed.raw_transaction).unwrap();
+        let (decoded, _) = TypedTransaction::decode_signed(&Rlp::new(&raw)).unwrap();
+        assert_eq!(decoded.nonce(), Some(&U256::from(3)));
+        assert_eq!(decoded.value(), Some(&U256::from(1)));
+    }
+
+    /// base_fee为10 gwei，ethers估算的priority_fee为3 gwei，max_fee为20 gwei
+    fn handle_fees(method: &str, _params: &Value) -> Result<Value, String> {
+        match method {
+            "eth_getBlockByNumber" => Ok(json!({"number": "0x1", "baseFeePerGas": "0x2540be400"})),
+            "eth_feeHistory" => Ok(json!({
+                "oldestBlock": "0x1",
+                "baseFeePerGas": ["0x2540be400", "0x2540be400"],
+                "gasUsedRatio": [0.5],
+                "reward": [["0x3b9aca00"]]
+            })),
+            _ => Err(format!("method {method} not supported")),
+        }
+    }
+
+    /// 不支持eip1559的链，区块中没有baseFeePerGas
+    fn handle_legacy_fees(method: &str, _params: &Value) -> Result<Value, String> {
+        match method {
+            "eth_getBlockByNumber" => Ok(json!({"number": "0x1"})),
+            _ => Err(format!("method {method} not supported")),
+        }
+    }
+
+    #[test]
+    fn test_apply_fees() {
+        use crate::mock_rpc::mock_rpc_server;
+
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        let gwei = |value: u64| U256::from(value) * U256::exp10(9);
+        let apply = |url: &str, max_fee: Option<u64>, priority_fee: Option<u64>| {
+            let provider = Provider::<Http>::try_from(url).unwrap();
+            let fees = FeeOptions {
+                gas_price: None,
+                max_fee_per_gas: max_fee.map(gwei),
+                max_priority_fee_per_gas: priority_fee.map(gwei),
+            };
+            rt.block_on(apply_fees(
+                &provider,
+                TransactionRequest::new().into(),
+                &fees,
+            ))
+        };
+        let eip1559_fees = |tx: TypedTransaction| match tx {
+            TypedTransaction::Eip1559(inner) => (
+                inner.max_fee_per_gas.unwrap(),
+                inner.max_priority_fee_per_gas.unwrap(),
+            ),
+            tx => panic!("expect eip1559 transaction, got {tx:?}"),
+        };
+
+        let url = mock_rpc_server(handle_fees);
+        // 费用均由eth_feeHistory估算
+        let tx = apply(&url, None, None).unwrap();
+        assert_eq!(eip1559_fees(tx), (gwei(20), gwei(3)));
+        // 指定的priority_fee替换估算值，max_fee随之调整
+        let tx = apply(&url, None, Some(1)).unwrap();
+        assert_eq!(eip1559_fees(tx), (gwei(18), gwei(1)));
+        // 只指定max_fee且低于估算的priority_fee时，priority_fee取max_fee
+        let tx = apply(&url, Some(2), None).unwrap();
+        assert_eq!(eip1559_fees(tx), (gwei(2), gwei(2)));
+        let tx = apply(&url, Some(30), None).unwrap();
+        assert_eq!(eip1559_fees(tx), (gwei(30), gwei(3)));
+        // 同时指定且max_fee低于priority_fee
+        assert!(apply(&url, Some(1), Some(2))
+            .unwrap_err()
+            .to_string()
+            .contains("must not be less than"));
+
+        let url = mock_rpc_server(handle_legacy_fees);
+        // 估算失败且未指定费用时回退为legacy交易，gas_price由节点补全
+        let tx = apply(&url, None, None).unwrap();
+        assert!(matches!(tx, TypedTransaction::Legacy(_)));
+        assert_eq!(tx.gas_price(), None);
+        // 指定了eip1559费用时估算失败直接报错
+        assert!(apply(&url, Some(30), None).is_err());
+        let tx = apply(&url, Some(30), Some(1)).unwrap();
+        assert_eq!(eip1559_fees(tx), (gwei(30), gwei(1)));
+    }
+
+    fn handle_fill(method: &str, params: &Value) -> Result<Value, String> {
+        match method {
+            "eth_getTransactionCount" => {
+                assert_eq!(params[0], "0x0000000000000000000000000000000000000002");
+                assert_eq!(params[1], "pending");
+                Ok(json!("0x7"))
+            }
+            "eth_gasPrice" => Ok(json!("0x4a817c800")),
+            "eth_estimateGas" => Ok(json!("0x5208")),
+            _ => Err(format!("method {method} not supported")),
+        }
+    }
+
+    #[test]
+    fn test_build_fill_from_rpc() {
+        use crate::mock_rpc::mock_rpc_server;
+        use crate::output::run_cli;
+
+        let url = mock_rpc_server(handle_fill);
+        let args = [
+            "--output-format",
+            "json",
+            "eth",
+            "tx-build",
+            "-y",
+            "legacy",
+            "-t",
+            "0x0000000000000000000000000000000000000001",
+            "-i",
+            "5",
+            "-r",
+            &url,
+        ];
+        // 查询nonce需要from地址
+        assert!(run_cli(&args)
+            .unwrap_err()
+            .to_string()
+            .contains("from is required"));
+
+        let unsigned = run_cli(
+            &[
+                &args[..],
+                &["-f", "0x0000000000000000000000000000000000000002"],
+            ]
+            .concat(),
+        )
+        .unwrap();
+        let mut unsigned: Value = serde_json::from_str(&unsigned).unwrap();
+        let unsigned: UnsignedTransaction =
+            serde_json::from_value(unsigned["unsigned_transaction"].take()).unwrap();
+        let tx = unsigned.into_transaction();
+        assert_eq!(tx.nonce(), Some(&U256::from(7)));
+        assert_eq!(tx.gas(), Some(&U256::from(21000)));
+        assert_eq!(tx.gas_price(), Some(U256::from(20_000_000_000u64)));
+        assert!(check_ready_to_sign(&tx).is_ok());
+    }
+}
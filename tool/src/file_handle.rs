@@ -1,13 +1,14 @@
 use anyhow::Result as AnyResult;
 use csv::Writer;
 use regex::Regex;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs::{read_to_string, File};
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
+use std::str::FromStr;
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 struct Account {
     address: String,
     native_token: u128,
@@ -50,43 +51,117 @@ pub fn format_line(line: String, reg: &str) -> Option<Account> {
     }
 }
 
-fn save_csv(accounts: Vec<Account>, path_str: &str) -> Result<(), Box<dyn Error>> {
-    let file_path = Path::new(path_str);
-    let mut writer = Writer::from_writer(File::create(file_path)?);
-
-    writer.write_record(&["address", "native_token", "erc20_token"])?;
-    for a in accounts {
-        writer.write_record(&[
-            a.address,
-            a.native_token.to_string(),
-            a.erc20_token.to_string(),
-        ])?;
+/// 查询结果的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for RecordFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(RecordFormat::Json),
+            "csv" => Ok(RecordFormat::Csv),
+            _ => Err(anyhow::anyhow!("format {s} not supported.")),
+        }
+    }
+}
+
+/// 按字段写入csv，第一行为字段名
+pub fn write_records<T: Serialize, W: Write>(
+    writer: W,
+    records: &[T],
+) -> Result<(), Box<dyn Error>> {
+    let mut writer = Writer::from_writer(writer);
+    for record in records {
+        writer.serialize(record)?;
     }
     writer.flush()?;
     Ok(())
 }
 
+pub fn save_records<T: Serialize>(path_str: &str, records: &[T]) -> Result<(), Box<dyn Error>> {
+    write_records(File::create(Path::new(path_str))?, records)
+}
+
+/// 指定文件时按格式写入查询结果，命令结果统一由调用方输出到stdout
+pub fn output_records<T: Serialize>(
+    records: &[T],
+    format: RecordFormat,
+    output: Option<String>,
+) -> AnyResult<()> {
+    match (format, output) {
+        (RecordFormat::Json, output) => {
+            crate::encrypt_decrypt::write_output(output, &serde_json::to_string_pretty(records)?)
+        }
+        (RecordFormat::Csv, Some(path)) => {
+            save_records(&path, records).map_err(|e| anyhow::anyhow!("save csv failed: {e}"))?;
+            tracing::info!("write to {path} success.");
+            Ok(())
+        }
+        (RecordFormat::Csv, None) => Ok(()),
+    }
+}
+
+/// 指定文件时写入列不固定的表格
+pub fn output_table(
+    header: &[String],
+    rows: &[Vec<String>],
+    output: Option<String>,
+) -> AnyResult<()> {
+    let Some(path) = output else {
+        return Ok(());
+    };
+    let mut writer = Writer::from_writer(File::create(Path::new(&path))?);
+    writer.write_record(header)?;
+    for row in rows {
+        writer.write_record(row)?;
+    }
+    writer.flush()?;
+    tracing::info!("write to {path} success.");
+    Ok(())
+}
+
+fn save_csv(accounts: Vec<Account>, path_str: &str) -> Result<(), Box<dyn Error>> {
+    save_records(path_str, &accounts)
+}
+
 pub fn to_csv_file(path_str: &str, json_data: &str) -> Result<(), Box<dyn Error>> {
     let accounts: Vec<Account> = serde_json::from_str(json_data)?;
     save_csv(accounts, path_str)?;
     Ok(())
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Log2Csv {
+    pub output_file: String,
+    pub records: usize,
+}
+
 pub fn log2_csv_file(
     input_file: String,
     output_file: String,
     key_word: String,
     reg: String,
-) -> AnyResult<()> {
-    let lines = read_file_line(input_file.as_str(), key_word.as_str()).unwrap();
+) -> AnyResult<Log2Csv> {
+    let lines = read_file_line(input_file.as_str(), key_word.as_str())
+        .map_err(|e| anyhow::anyhow!("read {input_file} failed: {e}"))?;
     let mut accounts = vec![];
     for line in lines {
         if let Some(account) = format_line(line, reg.as_str()) {
             accounts.push(account);
         }
     }
-    save_csv(accounts, output_file.as_str()).expect("save csv file failed");
-    Ok(())
+    let records = accounts.len();
+    save_csv(accounts, output_file.as_str())
+        .map_err(|e| anyhow::anyhow!("save csv file failed: {e}"))?;
+    Ok(Log2Csv {
+        output_file,
+        records,
+    })
 }
 
 #[cfg(test)]
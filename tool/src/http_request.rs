@@ -34,7 +34,7 @@ pub async fn fetch_url(uri: hyper::Uri, body_str: String) -> Result<()> {
     // asynchronously aggregate the chunks of the body
     let body = res.collect().await?.aggregate();
     // try to parse as json with serde_json
-    let result = serde_json::from_reader(body.reader())?;
+    let result: serde_json::Value = serde_json::from_reader(body.reader())?;
     info!("result: {:?}", result);
     Ok(())
 }
@@ -0,0 +1,247 @@
+use anyhow::{anyhow, bail, Result};
+use hmac::Hmac;
+use salsa20::cipher::typenum::U4;
+use salsa20::cipher::StreamCipherCore;
+use salsa20::SalsaCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// 由密码派生密钥的KDF，参数命名与Ethereum keystore中的kdfparams保持一致
+/// geth默认的scrypt参数 n = 2^18, r = 8, p = 1
+pub const DEFAULT_SCRYPT_N: u64 = 262144;
+pub const DEFAULT_SCRYPT_R: u32 = 8;
+pub const DEFAULT_SCRYPT_P: u32 = 1;
+pub const DEFAULT_PBKDF2_C: u32 = 262144;
+pub const DEFAULT_DKLEN: usize = 32;
+pub const PBKDF2_PRF: &str = "hmac-sha256";
+/// 参数可能来自不可信的文件，限制上限避免耗尽内存或CPU
+pub const MAX_SCRYPT_N: u64 = 1 << 20;
+pub const MAX_SCRYPT_R: u32 = 32;
+pub const MAX_SCRYPT_P: u32 = 16;
+/// scrypt所需内存为128 * n * r字节，geth默认参数正好为256MiB
+pub const MAX_SCRYPT_MEMORY: u64 = 256 * 1024 * 1024;
+pub const MAX_PBKDF2_C: u32 = 10_000_000;
+pub const MAX_DKLEN: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScryptParams {
+    pub n: u64,
+    pub r: u32,
+    pub p: u32,
+    pub dklen: usize,
+}
+
+impl Default for ScryptParams {
+    fn default() -> Self {
+        ScryptParams {
+            n: DEFAULT_SCRYPT_N,
+            r: DEFAULT_SCRYPT_R,
+            p: DEFAULT_SCRYPT_P,
+            dklen: DEFAULT_DKLEN,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Pbkdf2Params {
+    pub c: u32,
+    pub prf: String,
+    pub dklen: usize,
+}
+
+impl Default for Pbkdf2Params {
+    fn default() -> Self {
+        Pbkdf2Params {
+            c: DEFAULT_PBKDF2_C,
+            prf: PBKDF2_PRF.to_string(),
+            dklen: DEFAULT_DKLEN,
+        }
+    }
+}
+
+fn check_dklen(dklen: usize) -> Result<()> {
+    if dklen == 0 || dklen > MAX_DKLEN {
+        bail!("dklen must be in 1..={MAX_DKLEN}, got {dklen}");
+    }
+    Ok(())
+}
+
+pub fn scrypt_derive(password: &[u8], salt: &[u8], params: &ScryptParams) -> Result<Vec<u8>> {
+    // n 必须为2的幂，scrypt库需要的是log2(n)
+    if params.n < 2 || !params.n.is_power_of_two() {
+        bail!("scrypt n must be a power of 2, got {}", params.n);
+    }
+    if params.n > MAX_SCRYPT_N || params.r > MAX_SCRYPT_R || params.p > MAX_SCRYPT_P {
+        bail!(
+            "scrypt params too large, n <= {MAX_SCRYPT_N}, r <= {MAX_SCRYPT_R}, p <= {MAX_SCRYPT_P}."
+        );
+    }
+    if params.r == 0 || params.p == 0 {
+        bail!("scrypt r and p must be positive.");
+    }
+    if 128 * params.n * params.r as u64 > MAX_SCRYPT_MEMORY {
+        bail!("scrypt params require more than {MAX_SCRYPT_MEMORY} bytes of memory.");
+    }
+    check_dklen(params.dklen)?;
+    let log_n = params.n.trailing_zeros() as u8;
+    let mut out = vec![0u8; params.dklen];
+    if u32::from(log_n) >= params.r * 16 {
+        // scrypt库按RFC 7914要求n < 2^(16r)，但geth等钱包并不限制，
+        // Web3 Secret Storage的测试向量就使用了r = 1, n = 2^18
+        scrypt_unchecked(password, salt, params, &mut out);
+        return Ok(out);
+    }
+    let scrypt_params = scrypt::Params::new(log_n, params.r, params.p)
+        .map_err(|e| anyhow!("invalid scrypt params: {e}"))?;
+    scrypt::scrypt(password, salt, &scrypt_params, &mut out)
+        .map_err(|e| anyhow!("scrypt derive failed: {e}"))?;
+    Ok(out)
+}
+
+/// 与scrypt库相同的计算过程，不检查n与r的关系
+fn scrypt_unchecked(password: &[u8], salt: &[u8], params: &ScryptParams, out: &mut [u8]) {
+    let n = params.n as usize;
+    let len = 128 * params.r as usize;
+    let mut b = vec![0u8; len * params.p as usize];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, 1, &mut b);
+    let mut v = vec![0u8; len * n];
+    let mut t = vec![0u8; len];
+    for x in b.chunks_mut(len) {
+        // ROMix
+        for block in v.chunks_mut(len) {
+            block.copy_from_slice(x);
+            block_mix(block, x);
+        }
+        for _ in 0..n {
+            let j =
+                u32::from_le_bytes(x[len - 64..len - 60].try_into().unwrap()) as usize & (n - 1);
+            for ((t, x), v) in t.iter_mut().zip(x.iter()).zip(&v[j * len..(j + 1) * len]) {
+                *t = x ^ v;
+            }
+            block_mix(&t, x);
+        }
+    }
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, &b, 1, out);
+}
+
+fn block_mix(input: &[u8], output: &mut [u8]) {
+    let mut x = [0u8; 64];
+    x.copy_from_slice(&input[input.len() - 64..]);
+    for (i, chunk) in input.chunks(64).enumerate() {
+        for (x, c) in x.iter_mut().zip(chunk) {
+            *x ^= c;
+        }
+        let mut state = [0u32; 16];
+        for (s, c) in state.iter_mut().zip(x.chunks_exact(4)) {
+            *s = u32::from_le_bytes(c.try_into().unwrap());
+        }
+        SalsaCore::<U4>::from_raw_state(state).write_keystream_block((&mut x).into());
+        // 偶数块写入前半部分，奇数块写入后半部分
+        let pos = i / 2 * 64 + (i % 2) * input.len() / 2;
+        output[pos..pos + 64].copy_from_slice(&x);
+    }
+}
+
+pub fn pbkdf2_derive(password: &[u8], salt: &[u8], params: &Pbkdf2Params) -> Result<Vec<u8>> {
+    if params.prf != PBKDF2_PRF {
+        bail!("pbkdf2 prf {} not supported.", params.prf);
+    }
+    if params.c == 0 {
+        bail!("pbkdf2 iteration count must be positive.");
+    }
+    if params.c > MAX_PBKDF2_C {
+        bail!("pbkdf2 iteration count too large, c <= {MAX_PBKDF2_C}.");
+    }
+    check_dklen(params.dklen)?;
+    let mut out = vec![0u8; params.dklen];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, params.c, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::u8_array_convert_string;
+
+    #[test]
+    fn test_scrypt_rfc7914() {
+        let params = ScryptParams {
+            n: 1024,
+            r: 8,
+            p: 16,
+            dklen: 64,
+        };
+        let out = scrypt_derive(b"password", b"NaCl", &params).unwrap();
+        assert_eq!(u8_array_convert_string(&out), "fdbabe1c9d3472007856e7190d01e9fe7c6ad7cbc8237830e77376634b3731622eaf30d92e22a3886ff109279d9830dac727afb94a83ee6d8360cbdfa2cc0640");
+        let params = ScryptParams { n: 1000, ..params };
+        assert!(scrypt_derive(b"password", b"NaCl", &params).is_err());
+        // 不受RFC 7914限制的实现与scrypt库的计算结果一致
+        let params = ScryptParams {
+            n: 1024,
+            r: 8,
+            p: 16,
+            dklen: 64,
+        };
+        let mut unchecked = vec![0u8; 64];
+        scrypt_unchecked(b"password", b"NaCl", &params, &mut unchecked);
+        assert_eq!(unchecked, out);
+        // 超出上限的参数直接拒绝
+        for params in [
+            ScryptParams {
+                n: 1 << 20,
+                ..Default::default()
+            },
+            ScryptParams {
+                r: 0,
+                ..Default::default()
+            },
+            ScryptParams {
+                n: 1 << 40,
+                ..Default::default()
+            },
+            ScryptParams {
+                r: 1 << 20,
+                ..Default::default()
+            },
+            ScryptParams {
+                p: u32::MAX,
+                ..Default::default()
+            },
+            ScryptParams {
+                dklen: 1 << 40,
+                ..Default::default()
+            },
+            ScryptParams {
+                dklen: 0,
+                ..Default::default()
+            },
+        ] {
+            assert!(scrypt_derive(b"password", b"NaCl", &params).is_err());
+        }
+    }
+
+    #[test]
+    fn test_pbkdf2_hmac_sha256() {
+        let mut params = Pbkdf2Params {
+            c: 1,
+            prf: PBKDF2_PRF.to_string(),
+            dklen: 32,
+        };
+        let out = pbkdf2_derive(b"password", b"salt", &params).unwrap();
+        assert_eq!(
+            u8_array_convert_string(&out),
+            "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+        );
+        params.c = 2;
+        let out = pbkdf2_derive(b"password", b"salt", &params).unwrap();
+        assert_eq!(
+            u8_array_convert_string(&out),
+            "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+        );
+        params.c = u32::MAX;
+        assert!(pbkdf2_derive(b"password", b"salt", &params).is_err());
+        params.c = 1;
+        params.dklen = usize::MAX;
+        assert!(pbkdf2_derive(b"password", b"salt", &params).is_err());
+    }
+}
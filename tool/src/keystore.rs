@@ -0,0 +1,444 @@
+use aes::Aes128;
+use anyhow::{anyhow, bail, Result};
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use ethers::utils::hex;
+use rand::prelude::*;
+use secp256k1::SecretKey;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use std::fs;
+use tracing::info;
+
+use crate::encrypt_decrypt::Kdf;
+use crate::eth::{get_public_key, pub_key_to_address};
+use crate::kdf::{pbkdf2_derive, scrypt_derive, Pbkdf2Params, ScryptParams};
+use crate::output::Output;
+use crate::util::u8_array_convert_string;
+
+/// Web3 Secret Storage Definition (keystore v3)，与geth/MetaMask导出的json文件格式兼容
+/// https://ethereum.org/en/developers/docs/data-structures-and-encoding/web3-secret-storage/
+pub const KEYSTORE_VERSION: u32 = 3;
+const KEYSTORE_CIPHER: &str = "aes-128-ctr";
+const SALT_LEN: usize = 32;
+const IV_LEN: usize = 16;
+
+type Aes128Ctr = ctr::Ctr128BE<Aes128>;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherParams {
+    pub iv: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum KdfParams {
+    Scrypt {
+        #[serde(flatten)]
+        params: ScryptParams,
+        salt: String,
+    },
+    Pbkdf2 {
+        #[serde(flatten)]
+        params: Pbkdf2Params,
+        salt: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CryptoJson {
+    pub cipher: String,
+    pub cipherparams: CipherParams,
+    pub ciphertext: String,
+    pub kdf: String,
+    pub kdfparams: KdfParams,
+    pub mac: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeystoreV3 {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub address: Option<String>,
+    // 早期geth生成的文件字段名为Crypto
+    #[serde(alias = "Crypto")]
+    pub crypto: CryptoJson,
+    pub id: String,
+    pub version: u32,
+}
+
+fn keccak256(data: &[&[u8]]) -> Vec<u8> {
+    let mut hasher = Keccak256::new();
+    for d in data {
+        hasher.update(d);
+    }
+    hasher.finalize().to_vec()
+}
+
+fn decode_hex(name: &str, value: &str) -> Result<Vec<u8>> {
+    hex::decode(value.trim_start_matches("0x")).map_err(|e| anyhow!("invalid {name}: {e}"))
+}
+
+fn aes_128_ctr(key: &[u8], iv: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let mut cipher =
+        Aes128Ctr::new_from_slices(key, iv).map_err(|e| anyhow!("invalid key or iv: {e}"))?;
+    let mut out = data.to_vec();
+    cipher.apply_keystream(&mut out);
+    Ok(out)
+}
+
+/// 随机生成v4的uuid作为keystore的id
+fn random_uuid() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = u8_array_convert_string(&bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+fn private_key_to_plain_address(private_key: &[u8]) -> Result<String> {
+    SecretKey::from_slice(private_key)?;
+    let address = pub_key_to_address(get_public_key(&u8_array_convert_string(private_key)));
+    Ok(address.trim_start_matches("0x").to_string())
+}
+
+pub fn encrypt_key(private_key: &[u8], password: &str, kdf: &Kdf) -> Result<KeystoreV3> {
+    let mut rng = rand::thread_rng();
+    let mut salt = [0u8; SALT_LEN];
+    rng.fill_bytes(&mut salt);
+    let mut iv = [0u8; IV_LEN];
+    rng.fill_bytes(&mut iv);
+    let salt_hex = u8_array_convert_string(&salt);
+    let (kdf_name, derived_key, kdfparams) = match kdf {
+        Kdf::Scrypt(params) => (
+            "scrypt",
+            scrypt_derive(password.as_bytes(), &salt, params)?,
+            KdfParams::Scrypt {
+                params: params.clone(),
+                salt: salt_hex,
+            },
+        ),
+        Kdf::Pbkdf2(params) => (
+            "pbkdf2",
+            pbkdf2_derive(password.as_bytes(), &salt, params)?,
+            KdfParams::Pbkdf2 {
+                params: params.clone(),
+                salt: salt_hex,
+            },
+        ),
+        _ => bail!("keystore only supports scrypt or pbkdf2 kdf."),
+    };
+    if derived_key.len() < 32 {
+        bail!("keystore kdf dklen must be at least 32.");
+    }
+    // 前16字节作为AES-128的key，后16字节用于计算mac
+    let ciphertext = aes_128_ctr(&derived_key[..16], &iv, private_key)?;
+    let mac = keccak256(&[&derived_key[16..32], &ciphertext]);
+    Ok(KeystoreV3 {
+        address: Some(private_key_to_plain_address(private_key)?),
+        crypto: CryptoJson {
+            cipher: KEYSTORE_CIPHER.to_string(),
+            cipherparams: CipherParams {
+                iv: u8_array_convert_string(&iv),
+            },
+            ciphertext: u8_array_convert_string(&ciphertext),
+            kdf: kdf_name.to_string(),
+            kdfparams,
+            mac: u8_array_convert_string(&mac),
+        },
+        id: random_uuid(),
+        version: KEYSTORE_VERSION,
+    })
+}
+
+pub fn decrypt_key(keystore: &KeystoreV3, password: &str) -> Result<Vec<u8>> {
+    if keystore.version != KEYSTORE_VERSION {
+        bail!("keystore version {} not supported.", keystore.version);
+    }
+    let crypto = &keystore.crypto;
+    if crypto.cipher != KEYSTORE_CIPHER {
+        bail!("keystore cipher {} not supported.", crypto.cipher);
+    }
+    let derived_key = match (crypto.kdf.as_str(), &crypto.kdfparams) {
+        ("scrypt", KdfParams::Scrypt { params, salt }) => scrypt_derive(
+            password.as_bytes(),
+            &decode_hex("keystore salt", salt)?,
+            params,
+        )?,
+        ("pbkdf2", KdfParams::Pbkdf2 { params, salt }) => pbkdf2_derive(
+            password.as_bytes(),
+            &decode_hex("keystore salt", salt)?,
+            params,
+        )?,
+        (kdf, _) => bail!("keystore kdf {kdf} does not match its kdfparams."),
+    };
+    if derived_key.len() < 32 {
+        bail!("keystore kdf dklen must be at least 32.");
+    }
+    let ciphertext = decode_hex("keystore ciphertext", &crypto.ciphertext)?;
+    let mac = keccak256(&[&derived_key[16..32], &ciphertext]);
+    if u8_array_convert_string(&mac) != crypto.mac.to_lowercase() {
+        bail!("keystore mac mismatch: wrong password or corrupted keystore.");
+    }
+    aes_128_ctr(
+        &derived_key[..16],
+        &decode_hex("keystore iv", &crypto.cipherparams.iv)?,
+        &ciphertext,
+    )
+}
+
+pub fn read_keystore(path: &str) -> Result<KeystoreV3> {
+    let content = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&content)?)
+}
+
+/// 从文件读取密码（取第一行），未指定文件时在终端中提示输入，避免密码出现在shell history中
+pub fn read_password(password_file: Option<String>, confirm: bool) -> Result<String> {
+    if let Some(path) = password_file {
+        let content = fs::read_to_string(path)?;
+        return Ok(content.lines().next().unwrap_or_default().to_string());
+    }
+    let password = rpassword::prompt_password("Password: ")?;
+    if confirm && password != rpassword::prompt_password("Repeat password: ")? {
+        bail!("passwords do not match.");
+    }
+    Ok(password)
+}
+
+/// 解密keystore得到hex格式的私钥，供Transfer等需要私钥的命令使用
+pub fn private_key_from_keystore(path: &str, password_file: Option<String>) -> Result<String> {
+    let keystore = read_keystore(path)?;
+    let password = read_password(password_file, false)?;
+    Ok(u8_array_convert_string(&decrypt_key(&keystore, &password)?))
+}
+
+/// 私钥可以直接传入或者从keystore文件中解密得到，两者只能指定一个
+pub fn resolve_private_key(
+    private_key: Option<String>,
+    keystore: Option<String>,
+    password_file: Option<String>,
+) -> Result<String> {
+    match (private_key, keystore) {
+        (Some(_), Some(_)) => bail!("params error: private_key and keystore conflict."),
+        (None, Some(keystore)) => private_key_from_keystore(&keystore, password_file),
+        (Some(private_key), None) => Ok(private_key),
+        (None, None) => bail!("params error: private_key or keystore must have one."),
+    }
+}
+
+pub fn keystore_create(
+    kdf: Kdf,
+    random: bool,
+    password_file: Option<String>,
+    output: Option<String>,
+) -> Result<Output> {
+    let private_key = if random {
+        SecretKey::new(&mut rand::thread_rng())
+            .secret_bytes()
+            .to_vec()
+    } else {
+        let key = rpassword::prompt_password("Private key (hex): ")?;
+        SecretKey::from_slice(&decode_hex("private key", key.trim())?)?
+            .secret_bytes()
+            .to_vec()
+    };
+    let password = read_password(password_file, true)?;
+    let keystore = encrypt_key(&private_key, &password, &kdf)?;
+    info!(
+        "address: 0x{}",
+        keystore.address.clone().unwrap_or_default()
+    );
+    Output::artifact(output, "keystore", &keystore)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecryptedKeystore {
+    pub private_key: String,
+    pub address: String,
+}
+
+pub fn keystore_decrypt(
+    keystore: String,
+    password_file: Option<String>,
+) -> Result<DecryptedKeystore> {
+    let keystore = read_keystore(&keystore)?;
+    let password = read_password(password_file, false)?;
+    let private_key = decrypt_key(&keystore, &password)?;
+    Ok(DecryptedKeystore {
+        private_key: u8_array_convert_string(&private_key),
+        address: format!("0x{}", private_key_to_plain_address(&private_key)?),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const TEST_PRIVATE_KEY: &str =
+        "7a28b5ba57c53603b0b07b56bba752f7784bf506fa95edc395f5cf6c7514fe9d";
+
+    // Web3 Secret Storage Definition中公布的测试向量，密码为testpassword
+    const PBKDF2_KEYSTORE: &str = r#"{
+        "crypto" : {
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "6087dab2f9fdbbfaddc31a909735c1e6"
+            },
+            "ciphertext" : "5318b4d5bcd28de64ee5559e671353e16f075ecae9f99c7a79a38af5f869aa46",
+            "kdf" : "pbkdf2",
+            "kdfparams" : {
+                "c" : 262144,
+                "dklen" : 32,
+                "prf" : "hmac-sha256",
+                "salt" : "ae3cd4e7013836a3df6bd7241b12db061dbe2c6785853cce422d148a624ce0bd"
+            },
+            "mac" : "517ead924a9d0dc3124507e3393d175ce3ff7c1e96529c6c555ce9e51205e9b2"
+        },
+        "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version" : 3
+    }"#;
+
+    const SCRYPT_KEYSTORE: &str = r#"{
+        "crypto" : {
+            "cipher" : "aes-128-ctr",
+            "cipherparams" : {
+                "iv" : "83dbcc02d8ccb40e466191a123791e0e"
+            },
+            "ciphertext" : "d172bf743a674da9cdad04534d56926ef8358534d458fffccd4e6ad2fbde479c",
+            "kdf" : "scrypt",
+            "kdfparams" : {
+                "dklen" : 32,
+                "n" : 262144,
+                "p" : 8,
+                "r" : 1,
+                "salt" : "ab0c7876052600dd703518d6fc3fe8984592145b591fc8fb5c6d43190334ba19"
+            },
+            "mac" : "2103ac29920d71da29f15d75b4a16dbe95cfd7ff8faea1056c33131d846e3097"
+        },
+        "id" : "3198bc9c-6672-5ab3-d995-4942343ae5b6",
+        "version" : 3
+    }"#;
+
+    #[test]
+    fn test_decrypt_scrypt_vector() {
+        let keystore: KeystoreV3 = serde_json::from_str(SCRYPT_KEYSTORE).unwrap();
+        let key = decrypt_key(&keystore, "testpassword").unwrap();
+        assert_eq!(u8_array_convert_string(&key), TEST_PRIVATE_KEY);
+    }
+
+    #[test]
+    fn test_decrypt_malformed_hex() {
+        let keystore: KeystoreV3 = serde_json::from_str(PBKDF2_KEYSTORE).unwrap();
+        for bad in ["abc", "zz", "000g"] {
+            let mut tampered = keystore.clone();
+            tampered.crypto.ciphertext = bad.to_string();
+            assert!(decrypt_key(&tampered, "testpassword").is_err());
+            let mut tampered = keystore.clone();
+            tampered.crypto.cipherparams.iv = bad.to_string();
+            assert!(decrypt_key(&tampered, "testpassword").is_err());
+            let mut tampered = keystore.clone();
+            if let KdfParams::Pbkdf2 { salt, .. } = &mut tampered.crypto.kdfparams {
+                *salt = bad.to_string();
+            }
+            assert!(decrypt_key(&tampered, "testpassword").is_err());
+        }
+    }
+
+    #[test]
+    fn test_decrypt_pbkdf2_vector() {
+        let keystore: KeystoreV3 = serde_json::from_str(PBKDF2_KEYSTORE).unwrap();
+        let key = decrypt_key(&keystore, "testpassword").unwrap();
+        assert_eq!(u8_array_convert_string(&key), TEST_PRIVATE_KEY);
+        assert!(decrypt_key(&keystore, "wrongpassword").is_err());
+    }
+
+    #[test]
+    fn test_keystore_round_trip() {
+        let private_key = hex::decode(TEST_PRIVATE_KEY).unwrap();
+        let kdf = Kdf::Scrypt(ScryptParams {
+            n: 1024,
+            ..Default::default()
+        });
+        let keystore = encrypt_key(&private_key, "testpassword", &kdf).unwrap();
+        let json = serde_json::to_string(&keystore).unwrap();
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["version"], 3);
+        assert_eq!(value["crypto"]["kdf"], "scrypt");
+        assert_eq!(value["crypto"]["kdfparams"]["n"], 1024);
+        assert_eq!(value["id"].as_str().unwrap().len(), 36);
+        let keystore: KeystoreV3 = serde_json::from_str(&json).unwrap();
+        assert_eq!(decrypt_key(&keystore, "testpassword").unwrap(), private_key);
+        assert_eq!(
+            keystore.address.unwrap(),
+            "008aeeda4d805471df9b2a5b0f38a0c3bcba786b"
+        );
+        assert!(encrypt_key(&private_key, "testpassword", &Kdf::Keccak256).is_err());
+    }
+
+    #[test]
+    fn test_pipeline() {
+        use crate::output::{pipe, render, run_cli, OutputFormat};
+
+        // keystore-create输出的keystore可以直接作为keystore-decrypt的输入
+        let password_file = pipe("keystore-password", "testpassword\n");
+        let kdf = Kdf::Scrypt(ScryptParams {
+            n: 1024,
+            ..Default::default()
+        });
+        let output = keystore_create(kdf, true, Some(password_file.clone()), None).unwrap();
+        let keystore = render(&output, OutputFormat::Text).unwrap();
+        let wrapped: serde_json::Value =
+            serde_json::from_str(&render(&output, OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            wrapped["keystore"],
+            serde_json::from_str::<serde_json::Value>(&keystore).unwrap()
+        );
+        let keystore = pipe("keystore.json", &keystore);
+        let decrypted = run_cli(&[
+            "--output-format",
+            "json",
+            "eth",
+            "keystore-decrypt",
+            "-k",
+            &keystore,
+            "-p",
+            &password_file,
+        ])
+        .unwrap();
+        let decrypted: serde_json::Value = serde_json::from_str(&decrypted).unwrap();
+        assert_eq!(decrypted["private_key"].as_str().unwrap().len(), 64);
+    }
+
+    #[test]
+    fn test_private_key_conflicts_keystore() {
+        use crate::output::run_cli;
+
+        for command in ["transfer", "tx-sign", "sign-message", "sign-typed-data"] {
+            let err = run_cli(&[
+                "eth",
+                command,
+                "-s",
+                TEST_PRIVATE_KEY,
+                "-k",
+                "keystore.json",
+            ])
+            .unwrap_err();
+            assert!(
+                err.to_string().contains("cannot be used with"),
+                "{command}: {err}"
+            );
+        }
+        assert!(resolve_private_key(
+            Some(TEST_PRIVATE_KEY.to_string()),
+            Some("keystore.json".to_string()),
+            None
+        )
+        .is_err());
+    }
+}
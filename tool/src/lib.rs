@@ -1,160 +1,410 @@
 use crate::btc::{private_2_wif_key, private_key_convert};
 use crate::cli::{
-    BtcSubCommands, Cli, EthSubCommands,
-    SubCommands::{Btc, Decrypt, Encrypt, Eth, Log2Csv, Random, Reverse},
+    AddressSubCommands, BtcSubCommands, Cli, EthArgs, EthSubCommands, MnemonicSubCommands,
+    PsbtSubCommands, ScriptSubCommands, SeedSubCommands,
+    SubCommands::{
+        Address, Btc, Decrypt, Derive, Encrypt, Eth, Log2Csv, Mnemonic, Random, Reverse, Seed,
+        Shell, Vanity,
+    },
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use ethers::providers::spoof::nonce;
 use rand::{thread_rng, Rng};
-use tracing::{debug, error, info, warn};
+use serde_json::json;
+use tracing::debug;
 
+pub mod abi;
+pub mod address;
 pub mod bip32;
 pub mod btc;
+pub mod btc_build;
+pub mod btc_descriptor;
+pub mod btc_psbt;
+pub mod btc_script;
+pub mod btc_tx;
 pub mod cli;
+pub mod config;
+pub mod derive;
 pub mod encrypt_decrypt;
 pub mod eth;
+pub mod eth_query;
+pub mod eth_sign;
+pub mod eth_tx;
 
 pub mod file_handle;
 pub mod http_request;
+pub mod kdf;
+pub mod keystore;
+pub mod mnemonic;
+#[cfg(test)]
+mod mock_rpc;
+pub mod output;
+pub mod shamir;
+pub mod shell;
 pub mod util;
+pub mod vanity;
 
-use crate::encrypt_decrypt::{decrypt, encrypt};
+use crate::config::Config;
+use crate::encrypt_decrypt::{decrypt, encrypt, Kdf};
 use crate::eth::{private_key_to_address, pub_key_str_to_address, query_account_by_etherscan};
 use crate::file_handle::log2_csv_file;
+use crate::kdf::{DEFAULT_PBKDF2_C, DEFAULT_SCRYPT_N, DEFAULT_SCRYPT_P, DEFAULT_SCRYPT_R};
+use crate::output::{IntoOutput, Output};
 
-pub async fn start(args: Cli) -> Result<()> {
+/// 执行子命令，返回序列化后的结果，由调用方按输出格式输出到stdout
+pub async fn start(args: Cli) -> Result<Output> {
     debug!("cli args: {:?}", args);
-    let _ = match args.command {
+    match args.command {
         Encrypt {
             plaintext,
             password,
-        } => encrypt(plaintext, password),
-        Decrypt {
             cipher,
-            password,
-            iv,
-            tag,
-            aad,
-        } => decrypt(cipher, password, iv, tag, aad),
+            kdf,
+            scrypt_n,
+            scrypt_r,
+            scrypt_p,
+            pbkdf2_c,
+            output,
+        } => Kdf::from_args(&kdf, scrypt_n, scrypt_r, scrypt_p, pbkdf2_c)
+            .and_then(|kdf| encrypt(plaintext, password, cipher, kdf, output))
+            .into_output(),
+        Decrypt { input, password } => decrypt(input, password).into_output(),
         Random { min, max } => {
+            if min > max {
+                bail!("min {min} must not be greater than max {max}.");
+            }
             let random = thread_rng().gen_range(min..max + 1);
-            info!("random: {:?}", random);
-            Ok(())
+            Ok(json!({ "random": random }).into())
         }
         Log2Csv {
             input_file,
             output_file,
             key_word,
             reg,
-        } => log2_csv_file(input_file, output_file, key_word, reg),
-        Reverse { text, code } => reverse(text, code),
-        Eth(EthSubCommands) => handle_eth_sub_command(EthSubCommands).await,
+        } => log2_csv_file(input_file, output_file, key_word, reg).into_output(),
+        Reverse { text, code } => reverse(text, code).into_output(),
+        Shell {
+            network,
+            config,
+            history,
+        } => shell::run(network, config, history, args.output_format).into_output(),
+        Derive(args) => derive::derive(args).into_output(),
+        Vanity(args) => vanity::vanity(args).into_output(),
+        Address(AddressSubCommands::Inspect {
+            address,
+            network,
+            output,
+        }) => address::inspect(address, network, output).into_output(),
+        Mnemonic(MnemonicSubCommands::Generate {
+            words,
+            language,
+            hex,
+            dice,
+            passphrase,
+            output,
+        }) => mnemonic::generate(words, language, hex, dice, passphrase, output).into_output(),
+        Mnemonic(MnemonicSubCommands::Validate { mnemonic, language }) => {
+            mnemonic::validate(mnemonic, language).into_output()
+        }
+        Seed(SeedSubCommands::Split {
+            mnemonic,
+            threshold,
+            shares,
+            output,
+        }) => shamir::split(mnemonic, threshold, shares, output).into_output(),
+        Seed(SeedSubCommands::Combine {
+            shares,
+            input,
+            language,
+            output,
+        }) => shamir::combine(shares, input, language, output).into_output(),
+        Eth(args) => {
+            let EthArgs {
+                network,
+                config,
+                command,
+            } = *args;
+            handle_eth_sub_command(command, network, config).await
+        }
         Btc(BtcSubCommands) => handle_btc_sub_command(BtcSubCommands),
-    };
-    Ok(())
+    }
 }
 
-pub fn handle_btc_sub_command(btc_sub_commands: BtcSubCommands) -> Result<()> {
+pub fn handle_btc_sub_command(btc_sub_commands: BtcSubCommands) -> Result<Output> {
     match btc_sub_commands {
         BtcSubCommands::PrivateKeyConvert {
             private_key,
             format,
-        } => private_key_convert(private_key, format),
-        BtcSubCommands::Sec2Address { private_key } => btc::secret_to_address(private_key),
-        BtcSubCommands::Pub2Address { public_key } => btc::network_pub_key_to_address(public_key),
-        BtcSubCommands::Address2Script { address } => btc::address_to_script(address),
-        BtcSubCommands::RawTx2TxHash { raw_tx } => btc::get_tx_hash(raw_tx),
+        } => private_key_convert(private_key, format).into_output(),
+        BtcSubCommands::Sec2Address { private_key } => {
+            btc::secret_to_address(private_key).into_output()
+        }
+        BtcSubCommands::Pub2Address { public_key } => {
+            btc::network_pub_key_to_address(public_key).into_output()
+        }
+        BtcSubCommands::Address2Script { address } => btc::address_to_script(address).into_output(),
+        BtcSubCommands::RawTx2TxHash { raw_tx } => btc::get_tx_hash(raw_tx).into_output(),
+        BtcSubCommands::Script(ScriptSubCommands::Decode {
+            script,
+            network,
+            output,
+        }) => btc_script::decode_script(script, network, output).into_output(),
+        BtcSubCommands::Script(ScriptSubCommands::Encode { asm }) => {
+            btc_script::encode_script(asm).into_output()
+        }
+        BtcSubCommands::BuildTx(args) => btc_build::build_tx(args).into_output(),
+        BtcSubCommands::Descriptor {
+            descriptor,
+            start,
+            count,
+            network,
+            output,
+        } => btc_descriptor::derive_descriptor(descriptor, start, count, network, output)
+            .into_output(),
+        BtcSubCommands::Psbt(PsbtSubCommands::Decode {
+            input,
+            network,
+            output,
+        }) => btc_psbt::decode_psbt(input, network, output).into_output(),
+        BtcSubCommands::Psbt(PsbtSubCommands::Sign {
+            input,
+            x_prv,
+            output,
+        }) => btc_psbt::sign_psbt(input, x_prv, output).into_output(),
+        BtcSubCommands::Psbt(PsbtSubCommands::Combine { inputs, output }) => {
+            btc_psbt::combine_psbt(inputs, output).into_output()
+        }
+        BtcSubCommands::Psbt(PsbtSubCommands::Finalize { input, output }) => {
+            btc_psbt::finalize_psbt(input, output).into_output()
+        }
+        BtcSubCommands::Psbt(PsbtSubCommands::Extract { input, output }) => {
+            btc_psbt::extract_psbt(input, output).into_output()
+        }
+        BtcSubCommands::DecodeTx {
+            raw,
+            input,
+            network,
+            output,
+        } => btc_tx::decode_transaction(raw, input, network, output).into_output(),
         BtcSubCommands::Bip32 {
             x_private_key,
             x_public_key,
             path,
-        } => {
-            if x_public_key.is_some() {
-                btc::bip32_to_address(x_public_key.unwrap().to_string(), path)
-            } else if x_private_key.is_some() {
-                btc::bip32_to_address(x_private_key.unwrap().to_string(), path)
-            } else {
-                error!("params error: x_private_key or x_public_key must have one.");
-                Ok(())
-            }
-        }
+        } => match x_public_key.or(x_private_key) {
+            Some(xkey) => btc::bip32_to_address(xkey, path).into_output(),
+            None => bail!("params error: x_private_key or x_public_key must have one."),
+        },
         BtcSubCommands::Bip39 {
             mnemonic,
             passphrase,
-        } => btc::bip39_to_key(mnemonic, passphrase),
+        } => btc::bip39_to_key(mnemonic, passphrase).into_output(),
+        BtcSubCommands::XkeyConvert { key, target } => {
+            btc::convert_x_key(key, target).into_output()
+        }
     }
 }
 
-pub async fn handle_eth_sub_command(eth_sub_commands: EthSubCommands) -> Result<()> {
+/// 未通过命令行指定的rpc地址、chain id、explorer key和gas参数从网络配置中读取，
+/// 配置文件只在需要这些参数的命令中加载
+pub async fn handle_eth_sub_command(
+    eth_sub_commands: EthSubCommands,
+    network: Option<String>,
+    config: Option<String>,
+) -> Result<Output> {
+    let profile = || Config::load(config.as_deref())?.profile(network.as_deref());
     match eth_sub_commands {
-        EthSubCommands::Sec2Address { private_key } => private_key_to_address(private_key),
-        EthSubCommands::Pub2Address { public_key } => pub_key_str_to_address(public_key),
+        EthSubCommands::Sec2Address { private_key } => {
+            private_key_to_address(private_key).into_output()
+        }
+        EthSubCommands::Pub2Address { public_key } => {
+            pub_key_str_to_address(public_key).into_output()
+        }
         EthSubCommands::ChainInfo {
             address,
             api_key,
             chain_id,
-        } => query_account_by_etherscan(address, api_key, chain_id).await,
+        } => {
+            let profile = profile()?;
+            query_account_by_etherscan(
+                address,
+                profile.explorer_api_key(api_key)?,
+                profile.chain_id(chain_id)?,
+            )
+            .await
+            .into_output()
+        }
         EthSubCommands::Bip32 {
             x_private_key,
             x_public_key,
             path,
-        } => {
-            if x_public_key.is_some() {
-                eth::bip32_to_address(x_public_key.unwrap().to_string(), path)
-            } else if x_private_key.is_some() {
-                eth::bip32_to_address(x_private_key.unwrap().to_string(), path)
-            } else {
-                error!("params error: x_private_key or x_public_key must have one.");
-                Ok(())
-            }
-        }
+        } => match x_public_key.or(x_private_key) {
+            Some(xkey) => eth::bip32_to_address(xkey, path).into_output(),
+            None => bail!("params error: x_private_key or x_public_key must have one."),
+        },
         EthSubCommands::Bip39 {
             mnemonic,
             passphrase,
-        } => eth::bip39_to_key(mnemonic, passphrase),
+        } => eth::bip39_to_key(mnemonic, passphrase).into_output(),
         EthSubCommands::ExportPrivateKey {
             mnemonic,
             passphrase,
             path,
-        } => eth::mnemonic_to_key_pair_by_path(mnemonic, passphrase, path),
+        } => eth::mnemonic_to_key_pair_by_path(mnemonic, passphrase, path).into_output(),
         EthSubCommands::ContractCallParse {
             data,
             abi,
             func_name,
-        } => eth::decode_call_data(data, abi, func_name),
+        } => eth::decode_call_data(data, abi, func_name).into_output(),
+        EthSubCommands::EncodeCall { sig, args } => eth::encode_call_data(sig, args).into_output(),
+        EthSubCommands::SignMessage {
+            private_key,
+            keystore,
+            password_file,
+            message,
+            output,
+        } => keystore::resolve_private_key(private_key, keystore, password_file)
+            .and_then(|private_key| eth_sign::sign_message(private_key, message, output))
+            .into_output(),
+        EthSubCommands::SignTypedData {
+            private_key,
+            keystore,
+            password_file,
+            input,
+            output,
+        } => keystore::resolve_private_key(private_key, keystore, password_file)
+            .and_then(|private_key| eth_sign::sign_typed_data(private_key, input, output))
+            .into_output(),
+        EthSubCommands::Recover {
+            message,
+            typed_data,
+            hash,
+            signature,
+        } => eth_sign::resolve_hash(message, typed_data, hash)
+            .and_then(|hash| eth_sign::recover(hash, signature))
+            .into_output(),
+        EthSubCommands::Verify {
+            message,
+            typed_data,
+            hash,
+            signature,
+            address,
+        } => eth_sign::resolve_hash(message, typed_data, hash)
+            .and_then(|hash| eth_sign::verify(hash, signature, address))
+            .into_output(),
+        EthSubCommands::TxBuild(args) => {
+            // 默认网络只提供chain id和费用等离线参数，显式指定--network或--rpc-url时才连接节点补全交易
+            let mut profile = profile()?;
+            if network.is_none() {
+                profile.rpc_url = None;
+            }
+            eth_tx::build_transaction(args, &profile)
+                .await
+                .into_output()
+        }
+        EthSubCommands::Call(args) => eth_query::call(args, &profile()?).await.into_output(),
+        EthSubCommands::Logs(args) => eth_query::logs(args, &profile()?).await.into_output(),
+        EthSubCommands::Balances(args) => {
+            eth_query::balances(args, &profile()?).await.into_output()
+        }
+        EthSubCommands::TxSign {
+            input,
+            private_key,
+            keystore,
+            password_file,
+            output,
+        } => keystore::resolve_private_key(private_key, keystore, password_file)
+            .and_then(|private_key| eth_tx::sign_transaction_file(input, private_key, output))
+            .into_output(),
+        EthSubCommands::TxBroadcast {
+            rpc_url,
+            raw,
+            input,
+        } => eth_tx::broadcast_transaction(profile()?.rpc_url(rpc_url)?, raw, input)
+            .await
+            .into_output(),
         EthSubCommands::Amount {
             rpc_url,
             address,
             gas_price,
             gas_limit,
             block_id,
-        } => eth::calculate_balance(rpc_url, address, gas_price, gas_limit, block_id).await,
-        EthSubCommands::Convert { value } => eth::eth_convert(value),
+        } => {
+            let profile = profile()?;
+            eth::calculate_balance(
+                profile.rpc_url(rpc_url)?,
+                address,
+                profile.fee_cap(gas_price)?,
+                profile.gas_limit(gas_limit).unwrap_or(21000),
+                block_id,
+            )
+            .await
+            .into_output()
+        }
+        EthSubCommands::Convert { value } => eth::eth_convert(value).into_output(),
+        EthSubCommands::KeystoreCreate {
+            kdf,
+            random,
+            password_file,
+            output,
+        } => Kdf::from_args(
+            &kdf,
+            DEFAULT_SCRYPT_N,
+            DEFAULT_SCRYPT_R,
+            DEFAULT_SCRYPT_P,
+            DEFAULT_PBKDF2_C,
+        )
+        .and_then(|kdf| keystore::keystore_create(kdf, random, password_file, output))
+        .into_output(),
+        EthSubCommands::KeystoreDecrypt {
+            keystore,
+            password_file,
+        } => keystore::keystore_decrypt(keystore, password_file).into_output(),
         EthSubCommands::Transfer {
             private_key,
+            keystore,
+            password_file,
             rpc_url,
             to,
             value,
             chain_id,
             is_broadcast,
             contract_address,
+            data,
+            abi,
+            function,
+            args,
             gas_price,
+            max_fee_per_gas,
+            max_priority_fee_per_gas,
             gas_limit,
             nonce,
         } => {
+            let private_key = keystore::resolve_private_key(private_key, keystore, password_file)?;
+            let profile = profile()?;
+            let fees = profile.fees(gas_price, max_fee_per_gas, max_priority_fee_per_gas)?;
+            // 提供abi时按函数名和参数编码调用，to为合约地址
+            let data = match (abi, function) {
+                (Some(_), _) if data.is_some() => bail!("data and abi can not be used together."),
+                (Some(abi), Some(function)) => Some(eth::encode_abi_call(&abi, &function, &args)?),
+                (Some(_), None) => bail!("function is required when abi is given."),
+                (None, Some(_)) => bail!("abi is required when function is given."),
+                (None, None) => data,
+            };
             eth::create_transaction(
                 private_key,
-                rpc_url,
+                profile.rpc_url(rpc_url)?,
                 to,
                 value,
-                chain_id,
+                profile.chain_id(chain_id)?,
                 is_broadcast,
                 contract_address,
-                gas_price,
-                gas_limit,
+                data,
+                fees,
+                profile.gas_limit(gas_limit),
                 nonce,
             )
             .await
+            .into_output()
         }
     }
 }
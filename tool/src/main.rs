@@ -1,12 +1,15 @@
-use anyhow::Result;
 use clap::Parser;
+use std::process::ExitCode;
+use tool_lib::output::render;
 use tool_lib::{cli::Cli, start};
-use tracing::{info, metadata::LevelFilter, Level};
+use tracing::{error, info, metadata::LevelFilter, Level};
 use tracing_subscriber::EnvFilter;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    // 日志输出到stderr，stdout只输出命令结果
     tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
         .with_env_filter(
             EnvFilter::builder()
                 .with_default_directive(LevelFilter::from_level(Level::INFO).into())
@@ -15,8 +18,19 @@ async fn main() -> Result<()> {
         )
         .init();
     let args = Cli::parse();
+    let format = args.output_format;
     info!("crypto cli start...");
-    let result = start(args).await?;
-    info!("crypto cli finished {:?}", result);
-    Ok(())
+    match start(args).await.and_then(|result| render(&result, format)) {
+        Ok(rendered) => {
+            if !rendered.is_empty() {
+                println!("{rendered}");
+            }
+            info!("crypto cli finished");
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e:#}");
+            ExitCode::FAILURE
+        }
+    }
 }
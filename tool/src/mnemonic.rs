@@ -0,0 +1,373 @@
+use anyhow::{anyhow, bail, Result};
+use bip32::Prefix;
+use bip39::{Language, Mnemonic};
+use ethers::utils::hex;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::bip32::mnemonic_to_x_prv;
+use crate::encrypt_decrypt::write_output;
+use crate::util::u8_array_convert_string;
+
+/// 每个单词对应11位，其中 entropy_bits / 32 位为checksum
+const BITS_PER_WORD: usize = 11;
+const WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+/// 每个单词最多给出的建议数量
+const MAX_SUGGESTIONS: usize = 3;
+/// 编辑距离超过该值不作为建议
+const MAX_EDIT_DISTANCE: usize = 2;
+
+pub fn parse_language(language: &str) -> Result<Language> {
+    Ok(match language.to_lowercase().replace('_', "-").as_str() {
+        "english" | "en" => Language::English,
+        "chinese-simplified" | "zh-hans" | "zh-cn" => Language::SimplifiedChinese,
+        "chinese-traditional" | "zh-hant" | "zh-tw" => Language::TraditionalChinese,
+        "czech" | "cs" => Language::Czech,
+        "french" | "fr" => Language::French,
+        "italian" | "it" => Language::Italian,
+        "japanese" | "ja" => Language::Japanese,
+        "korean" | "ko" => Language::Korean,
+        "spanish" | "es" => Language::Spanish,
+        _ => bail!("unsupported mnemonic language {language}"),
+    })
+}
+
+pub fn language_name(language: Language) -> &'static str {
+    match language {
+        Language::English => "english",
+        Language::SimplifiedChinese => "chinese-simplified",
+        Language::TraditionalChinese => "chinese-traditional",
+        Language::Czech => "czech",
+        Language::French => "french",
+        Language::Italian => "italian",
+        Language::Japanese => "japanese",
+        Language::Korean => "korean",
+        Language::Spanish => "spanish",
+    }
+}
+
+/// 单词数量对应的熵字节数，12 -> 16, 24 -> 32
+pub fn entropy_len(word_count: usize) -> Result<usize> {
+    if !WORD_COUNTS.contains(&word_count) {
+        bail!("word count must be one of {WORD_COUNTS:?}, got {word_count}");
+    }
+    Ok(word_count * BITS_PER_WORD * 32 / 33 / 8)
+}
+
+/// 骰子熵：至少需要 entropy_bits / log2(6) 次投掷，按Coldcard的方式取投掷结果字符串的SHA-256并截断
+pub fn dice_entropy(rolls: &str, word_count: usize) -> Result<Vec<u8>> {
+    let rolls: String = rolls.chars().filter(|ch| !ch.is_whitespace()).collect();
+    if let Some(ch) = rolls.chars().find(|ch| !('1'..='6').contains(ch)) {
+        bail!("dice rolls must be 1-6, got {ch:?}");
+    }
+    let len = entropy_len(word_count)?;
+    let required = ((len * 8) as f64 / 6f64.log2()).ceil() as usize;
+    if rolls.len() < required {
+        bail!(
+            "{word_count} words need at least {required} dice rolls, got {}",
+            rolls.len()
+        );
+    }
+    Ok(Sha256::digest(rolls.as_bytes())[..len].to_vec())
+}
+
+/// 熵来源，都没有指定时使用系统随机数
+pub fn generate_mnemonic(
+    word_count: Option<usize>,
+    language: Language,
+    hex_entropy: Option<&str>,
+    dice: Option<&str>,
+) -> Result<Mnemonic> {
+    let entropy = match (hex_entropy, dice) {
+        (Some(_), Some(_)) => bail!("hex entropy and dice can not be used together."),
+        (Some(hex_entropy), None) => {
+            let entropy = hex::decode(hex_entropy.trim().trim_start_matches("0x"))
+                .map_err(|e| anyhow!("invalid hex entropy: {e}"))?;
+            let expected = word_count.map(entropy_len).transpose()?;
+            if expected.is_some_and(|expected| expected != entropy.len()) {
+                bail!(
+                    "{} words need {} bytes entropy, got {}",
+                    word_count.unwrap_or_default(),
+                    expected.unwrap_or_default(),
+                    entropy.len()
+                );
+            }
+            entropy
+        }
+        (None, Some(dice)) => dice_entropy(dice, word_count.unwrap_or(24))?,
+        (None, None) => {
+            let mut entropy = vec![0u8; entropy_len(word_count.unwrap_or(24))?];
+            OsRng.fill_bytes(&mut entropy);
+            entropy
+        }
+    };
+    Mnemonic::from_entropy_in(language, &entropy).map_err(|e| anyhow!("invalid entropy: {e}"))
+}
+
+/// 按字符计算的编辑距离
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// 前4个字符唯一确定的单词优先，其次按编辑距离排序
+pub fn suggest_words(word: &str, language: Language) -> Vec<String> {
+    let prefix: String = word.chars().take(4).collect();
+    let by_prefix = language.words_by_prefix(&prefix);
+    if prefix.chars().count() == 4 && by_prefix.len() == 1 {
+        return vec![by_prefix[0].to_string()];
+    }
+    let mut candidates: Vec<(usize, &str)> = language
+        .word_list()
+        .iter()
+        .map(|candidate| (edit_distance(word, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= MAX_EDIT_DISTANCE)
+        .collect();
+    candidates.sort();
+    candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+/// 未指定语言时选择包含单词最多的词表
+fn detect_language(words: &[&str]) -> Language {
+    Language::all()
+        .iter()
+        .copied()
+        .max_by_key(|language| {
+            let count = words
+                .iter()
+                .filter(|word| language.find_word(word).is_some())
+                .count();
+            // 数量相同时优先英文
+            (count, *language == Language::English)
+        })
+        .unwrap_or(Language::English)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct InvalidWord {
+    /// 从0开始
+    pub index: usize,
+    pub word: String,
+    pub suggestions: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MnemonicCheck {
+    pub valid: bool,
+    pub language: String,
+    pub word_count: usize,
+    pub invalid_words: Vec<InvalidWord>,
+    /// 所有单词都在词表中时才会校验checksum
+    pub checksum_valid: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+pub fn check_mnemonic(phrase: &str, language: Option<Language>) -> MnemonicCheck {
+    let normalized = phrase.trim().to_lowercase();
+    let words: Vec<&str> = normalized.split_whitespace().collect();
+    let language = language.unwrap_or_else(|| detect_language(&words));
+    let invalid_words: Vec<InvalidWord> = words
+        .iter()
+        .enumerate()
+        .filter(|(_, word)| language.find_word(word).is_none())
+        .map(|(index, word)| InvalidWord {
+            index,
+            word: word.to_string(),
+            suggestions: suggest_words(word, language),
+        })
+        .collect();
+    let (checksum_valid, error) = if !WORD_COUNTS.contains(&words.len()) {
+        (
+            None,
+            Some(format!("word count must be one of {WORD_COUNTS:?}")),
+        )
+    } else if !invalid_words.is_empty() {
+        (None, Some("unknown words".to_string()))
+    } else {
+        match Mnemonic::parse_in(language, words.join(" ")) {
+            Ok(_) => (Some(true), None),
+            Err(e) => (Some(false), Some(e.to_string())),
+        }
+    };
+    MnemonicCheck {
+        valid: error.is_none(),
+        language: language_name(language).to_string(),
+        word_count: words.len(),
+        invalid_words,
+        checksum_valid,
+        error,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GeneratedMnemonic {
+    pub mnemonic: String,
+    pub language: String,
+    pub word_count: usize,
+    pub entropy: String,
+    /// 助记词和passphrase生成的根扩展私钥
+    pub x_prv: String,
+}
+
+pub fn generate(
+    words: Option<usize>,
+    language: String,
+    hex_entropy: Option<String>,
+    dice: Option<String>,
+    passphrase: String,
+    output: Option<String>,
+) -> Result<GeneratedMnemonic> {
+    let language = parse_language(&language)?;
+    if hex_entropy.is_some() || dice.is_some() {
+        warn!("mnemonic from user supplied entropy is only as random as the input.");
+    }
+    let mnemonic = generate_mnemonic(words, language, hex_entropy.as_deref(), dice.as_deref())?;
+    let x_prv = mnemonic_to_x_prv(mnemonic.to_string(), passphrase);
+    let generated = GeneratedMnemonic {
+        mnemonic: mnemonic.to_string(),
+        language: language_name(language).to_string(),
+        word_count: mnemonic.word_count(),
+        entropy: u8_array_convert_string(&mnemonic.to_entropy()),
+        x_prv: x_prv.to_string(Prefix::XPRV).to_string(),
+    };
+    write_output(output, &serde_json::to_string_pretty(&generated)?)?;
+    Ok(generated)
+}
+
+/// 无效时返回错误，错误信息中带上拼错单词的建议
+pub fn validate(mnemonic: String, language: Option<String>) -> Result<MnemonicCheck> {
+    let language = language.as_deref().map(parse_language).transpose()?;
+    let check = check_mnemonic(&mnemonic, language);
+    if !check.valid {
+        let suggestions: Vec<String> = check
+            .invalid_words
+            .iter()
+            .map(|word| {
+                format!(
+                    "word {} {:?} -> {:?}",
+                    word.index, word.word, word.suggestions
+                )
+            })
+            .collect();
+        bail!(
+            "invalid mnemonic: {}{}",
+            check.error.unwrap_or_default(),
+            if suggestions.is_empty() {
+                String::new()
+            } else {
+                format!(" ({})", suggestions.join(", "))
+            }
+        );
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn test_generate() {
+        for word_count in WORD_COUNTS {
+            let mnemonic =
+                generate_mnemonic(Some(word_count), Language::English, None, None).unwrap();
+            assert_eq!(mnemonic.word_count(), word_count);
+        }
+        assert!(generate_mnemonic(Some(13), Language::English, None, None).is_err());
+
+        let mnemonic = generate_mnemonic(
+            None,
+            Language::English,
+            Some("00000000000000000000000000000000"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mnemonic.to_string(), MNEMONIC);
+        // BIP-39 测试向量
+        let mnemonic = generate_mnemonic(
+            Some(24),
+            Language::English,
+            Some("0x7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mnemonic.to_string(), "legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth useful legal winner thank year wave sausage worth title");
+        assert!(generate_mnemonic(Some(24), Language::English, Some("00"), None).is_err());
+        for hex_entropy in ["0", "zz", "0x7f7f7f7f7f7f7f7f7f7f7f7f7f7f7f7g"] {
+            assert!(generate_mnemonic(None, Language::English, Some(hex_entropy), None).is_err());
+        }
+
+        let mnemonic = generate_mnemonic(
+            None,
+            Language::Japanese,
+            Some("00000000000000000000000000000000"),
+            None,
+        )
+        .unwrap();
+        assert_eq!(mnemonic.word_iter().next(), Some("あいこくしん"));
+    }
+
+    #[test]
+    fn test_dice_entropy() {
+        let rolls = "123456".repeat(9);
+        assert!(dice_entropy(&rolls, 12).is_ok());
+        assert_eq!(dice_entropy(&rolls, 12).unwrap().len(), 16);
+        // 24个单词需要100次
+        assert!(dice_entropy(&rolls, 24).is_err());
+        assert!(dice_entropy(&"7".repeat(60), 12).is_err());
+        let mnemonic = generate_mnemonic(Some(12), Language::English, None, Some(&rolls)).unwrap();
+        assert_eq!(
+            mnemonic.to_entropy(),
+            Sha256::digest(rolls.as_bytes())[..16].to_vec()
+        );
+    }
+
+    #[test]
+    fn test_check_mnemonic() {
+        let check = check_mnemonic(MNEMONIC, None);
+        assert!(check.valid);
+        assert_eq!(check.checksum_valid, Some(true));
+
+        let check = check_mnemonic(&MNEMONIC.replace("about", "abuot"), None);
+        assert!(!check.valid);
+        assert_eq!(check.invalid_words[0].index, 11);
+        assert!(check.invalid_words[0]
+            .suggestions
+            .contains(&"about".to_string()));
+
+        // 前4个字母唯一确定单词
+        assert_eq!(suggest_words("abanden", Language::English), vec!["abandon"]);
+
+        let check = check_mnemonic(&MNEMONIC.replace("about", "abandon"), None);
+        assert!(check.invalid_words.is_empty());
+        assert_eq!(check.checksum_valid, Some(false));
+
+        let check = check_mnemonic("abandon about", None);
+        assert!(!check.valid);
+        assert_eq!(check.checksum_valid, None);
+
+        let mnemonic = generate_mnemonic(Some(12), Language::French, None, None).unwrap();
+        let check = check_mnemonic(&mnemonic.to_string(), None);
+        assert!(check.valid);
+        assert_eq!(check.language, "french");
+    }
+}
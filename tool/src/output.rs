@@ -0,0 +1,263 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::encrypt_decrypt::write_output;
+
+/// 命令结果输出到stdout的格式，日志统一输出到stderr
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// 子命令的结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum Output {
+    /// 按输出格式渲染
+    Value(Value),
+    /// 供其他命令读取的内容，如envelope、交易、keystore、psbt。
+    /// text格式原样输出以便管道给下一个命令，json格式包装为 `{name: content}`
+    Artifact { name: &'static str, content: Value },
+}
+
+impl Output {
+    /// 指定文件时写入文件并返回 `{"output": path}`，否则输出到stdout
+    pub fn artifact<T: Serialize>(
+        output: Option<String>,
+        name: &'static str,
+        content: &T,
+    ) -> Result<Self> {
+        let content = serde_json::to_value(content)?;
+        match output {
+            Some(path) => {
+                write_output(Some(path.clone()), &artifact_text(&content)?)?;
+                Ok(Output::Value(json!({ "output": path })))
+            }
+            None => Ok(Output::Artifact { name, content }),
+        }
+    }
+}
+
+/// 字符串原样输出，如base64的psbt；其他内容输出为格式化的json
+fn artifact_text(content: &Value) -> Result<String> {
+    Ok(match content {
+        Value::String(content) => content.trim_end().to_string(),
+        content => serde_json::to_string_pretty(content)?,
+    })
+}
+
+impl From<Value> for Output {
+    fn from(value: Value) -> Self {
+        Output::Value(value)
+    }
+}
+
+/// 子命令的结果统一序列化为json，再按输出格式渲染
+pub trait IntoOutput {
+    fn into_output(self) -> Result<Output>;
+}
+
+impl<T: Serialize> IntoOutput for Result<T> {
+    fn into_output(self) -> Result<Output> {
+        Ok(Output::Value(serde_json::to_value(self?)?))
+    }
+}
+
+impl IntoOutput for Result<Output> {
+    fn into_output(self) -> Result<Output> {
+        self
+    }
+}
+
+pub fn render(output: &Output, format: OutputFormat) -> Result<String> {
+    match (output, format) {
+        (Output::Artifact { content, .. }, OutputFormat::Text) => artifact_text(content),
+        (Output::Artifact { name, content }, OutputFormat::Json) => {
+            Ok(serde_json::to_string_pretty(&json!({ *name: content }))?)
+        }
+        (Output::Value(Value::Null), _) => Ok(String::new()),
+        (Output::Value(value), OutputFormat::Json) => Ok(serde_json::to_string_pretty(value)?),
+        (Output::Value(value), OutputFormat::Text) => render_text(value),
+    }
+}
+
+/// 按命令行执行并返回输出到stdout的内容，用于测试命令之间的管道
+#[cfg(test)]
+pub(crate) fn run_cli(args: &[&str]) -> Result<String> {
+    use clap::Parser;
+
+    let cli = crate::cli::Cli::try_parse_from(std::iter::once("tool").chain(args.iter().copied()))?;
+    let format = cli.output_format;
+    let output = tokio::runtime::Runtime::new()?.block_on(crate::start(cli))?;
+    render(&output, format)
+}
+
+/// 写入临时文件，代替管道中的stdin
+#[cfg(test)]
+pub(crate) fn pipe(name: &str, content: &str) -> String {
+    let path = std::env::temp_dir().join(format!("tool-{}-{name}", std::process::id()));
+    std::fs::write(&path, content).unwrap();
+    path.to_string_lossy().to_string()
+}
+
+/// 对象按 `key: value` 逐行输出，对象数组输出为csv表格，其他数组每个元素一行，嵌套的值输出为紧凑的json
+fn render_text(value: &Value) -> Result<String> {
+    Ok(match value {
+        Value::Object(map) => map
+            .iter()
+            .filter(|(_, value)| !value.is_null())
+            .map(|(key, value)| format!("{key}: {}", render_scalar(value)))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        Value::Array(items) if !items.is_empty() && items.iter().all(Value::is_object) => {
+            render_table(items)?
+        }
+        Value::Array(items) => items
+            .iter()
+            .map(render_scalar)
+            .collect::<Vec<_>>()
+            .join("\n"),
+        value => render_scalar(value),
+    })
+}
+
+/// 表头取所有对象字段的并集，按首次出现的顺序排列
+fn render_table(items: &[Value]) -> Result<String> {
+    let mut header: Vec<&String> = vec![];
+    for key in items
+        .iter()
+        .filter_map(Value::as_object)
+        .flat_map(|map| map.keys())
+    {
+        if !header.contains(&key) {
+            header.push(key);
+        }
+    }
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(&header)?;
+    for map in items.iter().filter_map(Value::as_object) {
+        writer.write_record(
+            header
+                .iter()
+                .map(|key| map.get(*key).map(render_scalar).unwrap_or_default()),
+        )?;
+    }
+    let table = String::from_utf8(writer.into_inner()?)?;
+    Ok(table.trim_end().to_string())
+}
+
+fn render_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_render() {
+        let value = json!({"address": "0xabc", "index": 1, "path": null, "keys": ["a", "b"]});
+        assert_eq!(
+            render(&value.clone().into(), OutputFormat::Text).unwrap(),
+            "address: 0xabc\nindex: 1\nkeys: [\"a\",\"b\"]"
+        );
+        let json = render(&value.clone().into(), OutputFormat::Json).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+
+        let value = json!([{"index": 0}, "tx"]);
+        assert_eq!(
+            render(&value.clone().into(), OutputFormat::Text).unwrap(),
+            "{\"index\":0}\ntx"
+        );
+        let value = json!([
+            {"address": "bc1q", "path": "m/0/0"},
+            {"address": "1A, 1B", "path": "m/0/1", "used": true}
+        ]);
+        assert_eq!(
+            render(&value.clone().into(), OutputFormat::Text).unwrap(),
+            "address,path,used\nbc1q,m/0/0,\n\"1A, 1B\",m/0/1,true"
+        );
+        assert_eq!(render(&Value::Null.into(), OutputFormat::Text).unwrap(), "");
+        assert_eq!(render(&Value::Null.into(), OutputFormat::Json).unwrap(), "");
+
+        // 管道中使用的内容在text格式下原样输出，json格式下按名称包装
+        let artifact = Output::artifact(None, "psbt", &"cHNidP8B\n").unwrap();
+        assert_eq!(render(&artifact, OutputFormat::Text).unwrap(), "cHNidP8B");
+        let json = render(&artifact, OutputFormat::Json).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            json!({"psbt": "cHNidP8B\n"})
+        );
+        let envelope = json!({"version": 1, "cipher": "aes-256-gcm"});
+        let artifact = Output::artifact(None, "envelope", &envelope).unwrap();
+        let text = render(&artifact, OutputFormat::Text).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&text).unwrap(), envelope);
+        let json = render(&artifact, OutputFormat::Json).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            json!({ "envelope": envelope })
+        );
+
+        // 写入文件时文件内容与text格式一致，stdout只输出文件路径
+        let path = pipe("artifact.json", "");
+        let written = Output::artifact(Some(path.clone()), "envelope", &envelope).unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        let json = render(&written, OutputFormat::Json).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Value>(&json).unwrap(),
+            json!({ "output": path })
+        );
+        assert_eq!(
+            render(&written, OutputFormat::Text).unwrap(),
+            format!("output: {path}")
+        );
+    }
+
+    #[test]
+    fn test_output_flag() {
+        use crate::cli::Cli;
+        use clap::Parser;
+
+        // 全局的 --output-format 可以放在子命令之后，与子命令的 -o/--output 不冲突
+        let cli = Cli::try_parse_from([
+            "tool",
+            "address",
+            "inspect",
+            "1A",
+            "--output-format",
+            "json",
+            "--output",
+            "info.json",
+        ])
+        .unwrap();
+        assert_eq!(cli.output_format, OutputFormat::Json);
+        let cli = Cli::try_parse_from(["tool", "random", "-i", "1", "-a", "2"]).unwrap();
+        assert_eq!(cli.output_format, OutputFormat::Text);
+        assert!(Cli::try_parse_from(["tool", "--output-format", "yaml", "random"]).is_err());
+    }
+
+    #[test]
+    fn test_into_output() {
+        #[derive(Serialize)]
+        struct TxHash {
+            tx_hash: String,
+        }
+        let value = Ok(TxHash {
+            tx_hash: "00".to_string(),
+        })
+        .into_output()
+        .unwrap();
+        assert_eq!(value, Output::Value(json!({"tx_hash": "00"})));
+        assert!(Err::<TxHash, _>(anyhow::anyhow!("failed"))
+            .into_output()
+            .is_err());
+    }
+}
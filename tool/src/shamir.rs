@@ -0,0 +1,391 @@
+//! GF(256)上的Shamir秘密分享，用于备份BIP-39助记词
+//!
+//! 被分享的秘密为助记词的熵 S 加上4字节摘要 D = SHA-256(S)[..4]，每个字节独立使用一个 k-1 次的随机多项式，
+//! 份额 x 取 1..=n，恢复时在 x = 0 处做拉格朗日插值并校验摘要。有限域使用AES的既约多项式 x^8 + x^4 + x^3 + x + 1。
+//!
+//! 每个份额编码为字节后按BIP-39英文词表每11位一个单词输出：
+//!
+//! | 字节 | 内容 |
+//! |------|------|
+//! | 0 | 版本 0x01 |
+//! | 1..3 | 同一次拆分的随机标识 |
+//! | 3 | 门限 k |
+//! | 4 | 份额的x坐标 |
+//! | 5 | 熵长度 L |
+//! | 6..10+L | S \|\| D 对应的多项式值 |
+//! | 末尾4字节 | 前面所有字节的 SHA-256 前4字节 |
+
+use anyhow::{anyhow, bail, Result};
+use bip39::{Language, Mnemonic};
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+use crate::encrypt_decrypt::{read_input, write_output};
+use crate::mnemonic::parse_language;
+
+const SHARE_VERSION: u8 = 0x01;
+const HEADER_LEN: usize = 6;
+const DIGEST_LEN: usize = 4;
+const CHECKSUM_LEN: usize = 4;
+const BITS_PER_WORD: usize = 11;
+/// AES的既约多项式
+const REDUCING_POLYNOMIAL: u16 = 0x11b;
+
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    while b != 0 {
+        if b & 1 == 1 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= (REDUCING_POLYNOMIAL & 0xff) as u8;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 = a^-1
+fn gf_inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    let mut exponent = 254u8;
+    while exponent != 0 {
+        if exponent & 1 == 1 {
+            result = gf_mul(result, base);
+        }
+        base = gf_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// 按Horner法则计算多项式在x处的值，coefficients[0]为常数项
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, coefficient| gf_mul(acc, x) ^ coefficient)
+}
+
+/// 拉格朗日插值求 f(0)
+fn interpolate(points: &[(u8, u8)]) -> u8 {
+    let mut secret = 0u8;
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        let mut numerator = 1u8;
+        let mut denominator = 1u8;
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i != j {
+                numerator = gf_mul(numerator, *xj);
+                denominator = gf_mul(denominator, xi ^ xj);
+            }
+        }
+        secret ^= gf_mul(*yi, gf_mul(numerator, gf_inv(denominator)));
+    }
+    secret
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub identifier: [u8; 2],
+    pub threshold: u8,
+    pub index: u8,
+    pub entropy_len: u8,
+    pub value: Vec<u8>,
+}
+
+fn checksum(data: &[u8]) -> [u8; CHECKSUM_LEN] {
+    let hash = Sha256::digest(data);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+impl Share {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![
+            SHARE_VERSION,
+            self.identifier[0],
+            self.identifier[1],
+            self.threshold,
+            self.index,
+            self.entropy_len,
+        ];
+        bytes.extend_from_slice(&self.value);
+        let checksum = checksum(&bytes);
+        bytes.extend_from_slice(&checksum);
+        bytes
+    }
+
+    /// 末尾不足一个单词的部分补0
+    pub fn to_mnemonic(&self) -> String {
+        let word_list = Language::English.word_list();
+        let bits: Vec<bool> = self
+            .to_bytes()
+            .iter()
+            .flat_map(|byte| (0..8).rev().map(move |i| byte >> i & 1 == 1))
+            .collect();
+        bits.chunks(BITS_PER_WORD)
+            .map(|chunk| {
+                let index = (0..BITS_PER_WORD).fold(0usize, |acc, i| {
+                    acc << 1 | usize::from(chunk.get(i).copied().unwrap_or_default())
+                });
+                word_list[index]
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    pub fn from_mnemonic(mnemonic: &str) -> Result<Self> {
+        let mut bits = vec![];
+        for word in mnemonic.split_whitespace() {
+            let word = word.to_lowercase();
+            let index = Language::English
+                .find_word(&word)
+                .ok_or_else(|| anyhow!("unknown share word {word}"))?;
+            bits.extend((0..BITS_PER_WORD).rev().map(|i| index >> i & 1 == 1));
+        }
+        let bytes: Vec<u8> = bits
+            .chunks_exact(8)
+            .map(|chunk| chunk.iter().fold(0u8, |acc, bit| acc << 1 | u8::from(*bit)))
+            .collect();
+        if bytes.len() < HEADER_LEN {
+            bail!("share is too short");
+        }
+        if bytes[0] != SHARE_VERSION {
+            bail!("unsupported share version {}", bytes[0]);
+        }
+        let entropy_len = bytes[5];
+        let total = HEADER_LEN + entropy_len as usize + DIGEST_LEN + CHECKSUM_LEN;
+        // 补位只能是不足一个单词的0
+        if bytes.len() < total
+            || bits[total * 8..].iter().any(|bit| *bit)
+            || bits.len() - total * 8 >= BITS_PER_WORD
+        {
+            bail!("invalid share length");
+        }
+        let (body, expected) = bytes[..total].split_at(total - CHECKSUM_LEN);
+        if checksum(body) != expected {
+            bail!("share checksum mismatch");
+        }
+        if bytes[4] == 0 {
+            bail!("share index must not be 0");
+        }
+        Ok(Share {
+            identifier: [bytes[1], bytes[2]],
+            threshold: bytes[3],
+            index: bytes[4],
+            entropy_len,
+            value: body[HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+/// 拆分助记词的熵为n份，任意k份可以恢复
+pub fn split_entropy(entropy: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>> {
+    if threshold == 0 || threshold > shares {
+        bail!("threshold must be between 1 and shares.");
+    }
+    if shares == u8::MAX {
+        bail!("at most 254 shares.");
+    }
+    let mut secret = entropy.to_vec();
+    secret.extend_from_slice(&Sha256::digest(entropy)[..DIGEST_LEN]);
+    let mut identifier = [0u8; 2];
+    OsRng.fill_bytes(&mut identifier);
+    // 每个字节一个多项式，常数项为秘密
+    let polynomials: Vec<Vec<u8>> = secret
+        .iter()
+        .map(|byte| {
+            let mut coefficients = vec![0u8; threshold as usize];
+            OsRng.fill_bytes(&mut coefficients[1..]);
+            coefficients[0] = *byte;
+            coefficients
+        })
+        .collect();
+    Ok((1..=shares)
+        .map(|index| Share {
+            identifier,
+            threshold,
+            index,
+            entropy_len: entropy.len() as u8,
+            value: polynomials
+                .iter()
+                .map(|coefficients| evaluate(coefficients, index))
+                .collect(),
+        })
+        .collect())
+}
+
+/// 恢复熵，份额需来自同一次拆分且数量不少于门限
+pub fn combine_shares(shares: &[Share]) -> Result<Vec<u8>> {
+    let first = shares.first().ok_or_else(|| anyhow!("no shares"))?;
+    if shares.iter().any(|share| {
+        share.identifier != first.identifier
+            || share.threshold != first.threshold
+            || share.entropy_len != first.entropy_len
+    }) {
+        bail!("shares are from different splits");
+    }
+    let mut indexes = HashSet::new();
+    let shares: Vec<&Share> = shares
+        .iter()
+        .filter(|share| indexes.insert(share.index))
+        .collect();
+    if shares.len() < first.threshold as usize {
+        bail!(
+            "need {} distinct shares, got {}",
+            first.threshold,
+            shares.len()
+        );
+    }
+    let shares = &shares[..first.threshold as usize];
+    let secret: Vec<u8> = (0..first.value.len())
+        .map(|i| {
+            let points: Vec<(u8, u8)> = shares
+                .iter()
+                .map(|share| (share.index, share.value[i]))
+                .collect();
+            interpolate(&points)
+        })
+        .collect();
+    let (entropy, digest) = secret.split_at(first.entropy_len as usize);
+    if Sha256::digest(entropy)[..DIGEST_LEN] != *digest {
+        bail!("recovered secret digest mismatch");
+    }
+    Ok(entropy.to_vec())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ShareMnemonic {
+    pub index: u8,
+    pub threshold: u8,
+    pub mnemonic: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CombinedSeed {
+    pub mnemonic: String,
+}
+
+pub fn split(
+    mnemonic: String,
+    threshold: u8,
+    shares: u8,
+    output: Option<String>,
+) -> Result<Vec<ShareMnemonic>> {
+    let mnemonic =
+        Mnemonic::parse(mnemonic.trim()).map_err(|e| anyhow!("invalid mnemonic: {e}"))?;
+    let shares: Vec<ShareMnemonic> = split_entropy(&mnemonic.to_entropy(), threshold, shares)?
+        .iter()
+        .map(|share| ShareMnemonic {
+            index: share.index,
+            threshold: share.threshold,
+            mnemonic: share.to_mnemonic(),
+        })
+        .collect();
+    write_output(output, &serde_json::to_string_pretty(&shares)?)?;
+    Ok(shares)
+}
+
+/// 份额可以通过参数指定，或者从文件按行读取
+pub fn combine(
+    shares: Vec<String>,
+    input: Option<String>,
+    language: String,
+    output: Option<String>,
+) -> Result<CombinedSeed> {
+    let mut lines = shares;
+    if let Some(input) = input {
+        lines.extend(
+            read_input(&input)?
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string),
+        );
+    }
+    let shares = lines
+        .iter()
+        .map(|line| Share::from_mnemonic(line))
+        .collect::<Result<Vec<_>>>()?;
+    let entropy = combine_shares(&shares)?;
+    let mnemonic = Mnemonic::from_entropy_in(parse_language(&language)?, &entropy)
+        .map_err(|e| anyhow!("invalid entropy: {e}"))?;
+    let combined = CombinedSeed {
+        mnemonic: mnemonic.to_string(),
+    };
+    write_output(output, &serde_json::to_string_pretty(&combined)?)?;
+    Ok(combined)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_gf256() {
+        // AES中的示例：{57} * {83} = {c1}
+        assert_eq!(gf_mul(0x57, 0x83), 0xc1);
+        for a in 1..=255u8 {
+            assert_eq!(gf_mul(a, gf_inv(a)), 1);
+        }
+    }
+
+    #[test]
+    fn test_split_combine() {
+        let entropy = [0x7fu8; 32];
+        let shares = split_entropy(&entropy, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        for combination in [[0, 1, 2], [4, 2, 0], [1, 3, 4]] {
+            let selected: Vec<Share> = combination.iter().map(|i| shares[*i].clone()).collect();
+            assert_eq!(combine_shares(&selected).unwrap(), entropy);
+        }
+        assert!(combine_shares(&shares[..2]).is_err());
+        // 重复的份额不计数
+        assert!(
+            combine_shares(&[shares[0].clone(), shares[0].clone(), shares[1].clone()]).is_err()
+        );
+        let other = split_entropy(&entropy, 3, 5).unwrap();
+        if other[0].identifier != shares[0].identifier {
+            assert!(
+                combine_shares(&[other[0].clone(), shares[1].clone(), shares[2].clone()]).is_err()
+            );
+        }
+        assert!(split_entropy(&entropy, 4, 3).is_err());
+        assert!(split_entropy(&entropy, 0, 3).is_err());
+
+        let single = split_entropy(&entropy[..16], 1, 1).unwrap();
+        assert_eq!(combine_shares(&single).unwrap(), entropy[..16]);
+    }
+
+    #[test]
+    fn test_share_mnemonic() {
+        let mnemonic = Mnemonic::parse(
+            "legal winner thank year wave sausage worth useful legal winner thank yellow",
+        )
+        .unwrap();
+        let shares = split_entropy(&mnemonic.to_entropy(), 2, 3).unwrap();
+        let words: Vec<String> = shares.iter().map(Share::to_mnemonic).collect();
+        assert_eq!(words[0].split(' ').count(), 22);
+        let decoded: Vec<Share> = words
+            .iter()
+            .map(|words| Share::from_mnemonic(words).unwrap())
+            .collect();
+        assert_eq!(decoded, shares);
+        let entropy = combine_shares(&decoded[1..]).unwrap();
+        assert_eq!(Mnemonic::from_entropy(&entropy).unwrap(), mnemonic);
+
+        // 改动一个单词后checksum失败
+        let mut tampered: Vec<&str> = words[0].split(' ').collect();
+        tampered[8] = if tampered[8] == "abandon" {
+            "ability"
+        } else {
+            "abandon"
+        };
+        assert!(Share::from_mnemonic(&tampered.join(" ")).is_err());
+        assert!(Share::from_mnemonic("abandon abandon").is_err());
+    }
+}
@@ -0,0 +1,537 @@
+use anyhow::{anyhow, bail, Result};
+use clap::{Arg, ArgAction, CommandFactory, Parser};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use tokio::runtime::Handle;
+use tokio::task::block_in_place;
+use tracing::{debug, error};
+
+use crate::cli::{Cli, SubCommands};
+use crate::config::Config;
+use crate::output::{render, OutputFormat};
+use crate::start;
+
+/// 默认历史文件相对于home目录的路径
+const DEFAULT_HISTORY_PATH: &str = ".config/tool/history";
+
+/// 这些参数的值在历史记录中替换为 `***`
+const SECRET_ARGS: [&str; 13] = [
+    "private_key",
+    "x_private_key",
+    "x_prv",
+    "key",
+    "mnemonic",
+    "passphrase",
+    "password",
+    "plaintext",
+    "hex",
+    "dice",
+    "shares",
+    "api_key",
+    // rpc地址中可能带有服务商的api key
+    "rpc_url",
+];
+
+const BUILTINS: [&str; 7] = ["set", "unset", "vars", "network", "help", "exit", "quit"];
+
+const HELP: &str = "\
+set <name> [value]   保存值到会话，不指定value时隐藏输入，命令中用 $name 引用
+unset <name>         删除会话中的值
+vars                 列出会话中保存的名称
+network [name]       查看或切换eth命令默认使用的网络
+exit | quit          退出
+其他输入按子命令解析，如 `eth sec2-address -s $key`，`<子命令> --help` 查看帮助";
+
+/// 会话状态，保存的值只在内存中，退出后丢弃
+#[derive(Debug, Default)]
+pub struct Session {
+    vars: BTreeMap<String, String>,
+    network: Option<String>,
+    config: Option<String>,
+    output_format: OutputFormat,
+}
+
+impl Session {
+    pub fn new(
+        network: Option<String>,
+        config: Option<String>,
+        output_format: OutputFormat,
+    ) -> Result<Self> {
+        let session = Session {
+            config,
+            output_format,
+            ..Default::default()
+        };
+        Ok(Session {
+            network: session.check_network(network)?,
+            ..session
+        })
+    }
+
+    /// 切换网络前先校验配置中存在该网络
+    fn check_network(&self, network: Option<String>) -> Result<Option<String>> {
+        if let Some(network) = &network {
+            Config::load(self.config.as_deref())?.profile(Some(network))?;
+        }
+        Ok(network)
+    }
+
+    pub fn set(&mut self, name: &str, value: String) -> Result<()> {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_')
+        {
+            bail!("invalid name {name}: only letters, digits and _ are allowed.");
+        }
+        self.vars.insert(name.to_string(), value);
+        Ok(())
+    }
+
+    /// 整个参数为 `$name` 或 `--flag=$name` 时替换为会话中的值
+    fn expand(&self, token: String) -> Result<String> {
+        let (prefix, name) = match token.split_once("=$") {
+            Some((prefix, name)) if prefix.starts_with('-') => (format!("{prefix}="), name),
+            _ => match token.strip_prefix('$') {
+                Some(name) => (String::new(), name),
+                None => return Ok(token),
+            },
+        };
+        let value = self
+            .vars
+            .get(name)
+            .ok_or_else(|| anyhow!("${name} is not set, use `set {name}` first."))?;
+        Ok(format!("{prefix}{value}"))
+    }
+
+    /// 按子命令解析，eth命令未指定时使用会话的网络和配置文件
+    pub fn parse(&self, tokens: Vec<String>) -> Result<Cli> {
+        let has_flag = |flag: &str| {
+            tokens
+                .iter()
+                .any(|token| token == flag || token.starts_with(&format!("{flag}=")))
+        };
+        let (network_flag, config_flag, output_flag) = (
+            has_flag("--network"),
+            has_flag("--config"),
+            has_flag("--output-format"),
+        );
+        let mut tokens = tokens.into_iter();
+        let mut args = vec!["tool".to_string()];
+        args.extend(tokens.next());
+        // eth的全局参数放在eth之后
+        if args.get(1).map(String::as_str) == Some("eth") {
+            if let (Some(network), false) = (&self.network, network_flag) {
+                args.extend(["--network".to_string(), network.clone()]);
+            }
+            if let (Some(config), false) = (&self.config, config_flag) {
+                args.extend(["--config".to_string(), config.clone()]);
+            }
+        }
+        for token in tokens {
+            args.push(self.expand(token)?);
+        }
+        let mut cli = Cli::try_parse_from(args)?;
+        if let SubCommands::Shell { .. } = cli.command {
+            bail!("already in shell.");
+        }
+        if !output_flag {
+            cli.output_format = self.output_format;
+        }
+        Ok(cli)
+    }
+
+    /// 执行一行输入，返回false时退出
+    pub async fn execute(&mut self, line: &str) -> Result<bool> {
+        let tokens = shlex::split(line).ok_or_else(|| anyhow!("unbalanced quotes."))?;
+        let Some(first) = tokens.first() else {
+            return Ok(true);
+        };
+        match (first.as_str(), &tokens[1..]) {
+            ("exit" | "quit", _) => return Ok(false),
+            ("help", []) => println!("{HELP}"),
+            ("set", [name]) => {
+                let value = rpassword::prompt_password(format!("{name}: "))?;
+                self.set(name, value)?;
+            }
+            ("set", [name, value]) => self.set(name, value.clone())?,
+            ("unset", [name]) => {
+                self.vars
+                    .remove(name)
+                    .ok_or_else(|| anyhow!("{name} is not set."))?;
+            }
+            ("vars", []) => {
+                for name in self.vars.keys() {
+                    println!("{name}");
+                }
+            }
+            ("network", []) => println!("{}", self.network.as_deref().unwrap_or("")),
+            ("network", [network]) => {
+                self.network = self.check_network(Some(network.clone()))?;
+            }
+            (builtin, _) if BUILTINS.contains(&builtin) => {
+                bail!("invalid arguments for {builtin}, type `help` for usage.")
+            }
+            _ => {
+                let cli = match self.parse(tokens) {
+                    Ok(cli) => cli,
+                    // --help、--version等由clap输出
+                    Err(e) => match e.downcast::<clap::Error>() {
+                        Ok(e) => {
+                            e.print()?;
+                            return Ok(true);
+                        }
+                        Err(e) => return Err(e),
+                    },
+                };
+                let format = cli.output_format;
+                // 子命令中的panic不退出会话
+                let result = tokio::spawn(Box::pin(start(cli)))
+                    .await
+                    .map_err(|e| anyhow!("command panicked: {e}"))??;
+                let rendered = render(&result, format)?;
+                if !rendered.is_empty() {
+                    println!("{rendered}");
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// 写入历史文件的内容，`set` 命令和解析失败的输入不记录，
+/// 只有已知的非密钥参数的值原样保存，其他值和位置参数都替换为 `***`
+pub fn history_entry(session: &Session, line: &str) -> Option<String> {
+    let tokens = shlex::split(line)?;
+    match tokens.first().map(String::as_str) {
+        None | Some("set") => return None,
+        Some(builtin) if BUILTINS.contains(&builtin) => {
+            return shlex::try_join(tokens.iter().map(String::as_str)).ok();
+        }
+        _ => {
+            session.parse(tokens.clone()).ok()?;
+        }
+    }
+    redact_tokens(tokens)
+}
+
+fn redact_tokens(tokens: Vec<String>) -> Option<String> {
+    let root = Cli::command();
+    let mut commands = vec![&root];
+    let mut positional = 0;
+    let mut only_positional = false;
+    let mut redact_next = None;
+    let mut entry = vec![];
+    for token in tokens {
+        if let Some(secret) = redact_next.take() {
+            entry.push(redact(token, secret));
+            continue;
+        }
+        let command = *commands.last()?;
+        let find_arg = |matches: &dyn Fn(&Arg) -> bool| {
+            commands
+                .iter()
+                .rev()
+                .flat_map(|command| command.get_arguments())
+                .find(|arg| matches(arg))
+        };
+        if only_positional {
+            entry.push(redact(token, true));
+        } else if token == "--" {
+            only_positional = true;
+            entry.push(token);
+        } else if let Some(flag) = token.strip_prefix("--") {
+            let (name, value) = match flag.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (flag, None),
+            };
+            let arg = find_arg(&|arg| arg.get_long() == Some(name));
+            let secret = arg.is_none_or(is_secret);
+            match value {
+                Some(value) => {
+                    entry.push(format!("--{name}={}", redact(value.to_string(), secret)))
+                }
+                None => {
+                    if arg.is_some_and(|arg| takes_value(arg.get_action())) {
+                        redact_next = Some(secret);
+                    }
+                    entry.push(token);
+                }
+            }
+        } else if let Some(shorts) = token.strip_prefix('-').filter(|shorts| !shorts.is_empty()) {
+            // 多个短参数可以合并，如 -fs VALUE，取值的参数之后的部分为它的值
+            let mut flags = String::from("-");
+            for (index, short) in shorts.char_indices() {
+                let arg = find_arg(&|arg| arg.get_short() == Some(short));
+                let Some(arg) = arg else {
+                    flags.push_str(&redact(shorts[index..].to_string(), true));
+                    break;
+                };
+                flags.push(short);
+                if takes_value(arg.get_action()) {
+                    let value = shorts[index + short.len_utf8()..].trim_start_matches('=');
+                    match value.is_empty() {
+                        true => redact_next = Some(is_secret(arg)),
+                        false => flags.push_str(&redact(value.to_string(), is_secret(arg))),
+                    }
+                    break;
+                }
+            }
+            entry.push(flags);
+        } else if let Some(subcommand) = command.find_subcommand(&token) {
+            commands.push(subcommand);
+            positional = 0;
+            entry.push(token);
+        } else {
+            let secret = command
+                .get_positionals()
+                .nth(positional)
+                .is_none_or(is_secret);
+            positional += 1;
+            entry.push(redact(token, secret));
+        }
+    }
+    shlex::try_join(entry.iter().map(String::as_str)).ok()
+}
+
+fn is_secret(arg: &Arg) -> bool {
+    SECRET_ARGS.contains(&arg.get_id().as_str())
+}
+
+fn takes_value(action: &ArgAction) -> bool {
+    matches!(action, ArgAction::Set | ArgAction::Append)
+}
+
+/// 引用会话中的值时保留原文
+fn redact(value: String, secret: bool) -> String {
+    match secret && !value.starts_with('$') {
+        true => "***".to_string(),
+        false => value,
+    }
+}
+
+/// 补全光标所在的单词，第一个单词补全内置命令和子命令，之后补全子命令和长参数
+pub fn complete(line: &str) -> (usize, Vec<String>) {
+    let start = line
+        .rfind(char::is_whitespace)
+        .map(|index| index + 1)
+        .unwrap_or(0);
+    let word = &line[start..];
+    let root = Cli::command();
+    let mut command = &root;
+    for token in line[..start].split_whitespace() {
+        if let Some(subcommand) = command.find_subcommand(token) {
+            command = subcommand;
+        }
+    }
+    let mut candidates: Vec<String> = if word.starts_with('-') {
+        command
+            .get_arguments()
+            .filter_map(|arg| arg.get_long())
+            .map(|long| format!("--{long}"))
+            .collect()
+    } else {
+        command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_string())
+            .filter(|name| name != "shell")
+            .collect()
+    };
+    if start == 0 {
+        candidates.extend(BUILTINS.iter().map(|builtin| builtin.to_string()));
+    }
+    candidates.retain(|candidate| candidate.starts_with(word));
+    candidates.sort();
+    (start, candidates)
+}
+
+struct ShellHelper;
+
+impl Completer for ShellHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&line[..pos]))
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
+
+/// 读取输入是阻塞的，编辑器不能跨await持有，每行命令在当前运行时中执行
+pub fn run(
+    network: Option<String>,
+    config: Option<String>,
+    history: Option<String>,
+    output_format: OutputFormat,
+) -> Result<Value> {
+    let mut session = Session::new(network, config, output_format)?;
+    let history = match history {
+        Some(history) => Some(PathBuf::from(history)),
+        None => dirs::home_dir().map(|home| home.join(DEFAULT_HISTORY_PATH)),
+    };
+    let mut editor = Editor::<ShellHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(ShellHelper));
+    if let Some(history) = &history {
+        if editor.load_history(history).is_err() {
+            debug!("history {} not loaded", history.display());
+        }
+    }
+    loop {
+        let prompt = match &session.network {
+            Some(network) => format!("tool({network})> "),
+            None => "tool> ".to_string(),
+        };
+        let line = match editor.readline(&prompt) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+        if let Some(entry) = history_entry(&session, &line) {
+            editor.add_history_entry(entry)?;
+        }
+        let handle = Handle::current();
+        match block_in_place(|| handle.block_on(session.execute(&line))) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => error!("{e:#}"),
+        }
+    }
+    if let Some(history) = &history {
+        if let Some(parent) = history.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        editor.save_history(history)?;
+    }
+    Ok(Value::Null)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_history_entry() {
+        let mut session = Session::default();
+        session.set("key", "0x01".to_string()).unwrap();
+        let entry = |line: &str| history_entry(&session, line);
+        let key = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        assert_eq!(
+            entry(&format!("eth sec2-address -s {key}")).unwrap(),
+            "eth sec2-address -s '***'"
+        );
+        assert_eq!(
+            entry(&format!(
+                "btc private-key-convert --private-key={key} -f wif"
+            ))
+            .unwrap(),
+            "btc private-key-convert '--private-key=***' -f wif"
+        );
+        assert_eq!(
+            entry(&format!("eth sec2-address -s{key}")).unwrap(),
+            "eth sec2-address '-s***'"
+        );
+        assert_eq!(
+            entry(&format!("btc private-key-convert -fwif -s {key}")).unwrap(),
+            "btc private-key-convert -fwif -s '***'"
+        );
+        // 引用会话中的值和非密钥参数原样保存
+        assert_eq!(
+            entry("eth sec2-address -s $key --network bsc").unwrap(),
+            "eth sec2-address -s '$key' --network bsc"
+        );
+        assert_eq!(
+            entry("mnemonic validate -m 'abandon abandon about' -l english").unwrap(),
+            "mnemonic validate -m '***' -l english"
+        );
+        assert_eq!(
+            entry("address inspect 1A --output-format json").unwrap(),
+            "address inspect 1A --output-format json"
+        );
+        assert_eq!(
+            entry("eth tx-broadcast -r https://rpc.example/v3/apikey --raw 0x00").unwrap(),
+            "eth tx-broadcast -r '***' --raw 0x00"
+        );
+        assert_eq!(entry("network bsc").unwrap(), "network bsc");
+        assert_eq!(entry(&format!("set key {key}")), None);
+        assert_eq!(entry("eth sec2-address -s 'unbalanced"), None);
+
+        // 解析失败的输入不记录，即使记录也不会保留无法识别的值
+        for line in [
+            format!("eth sec2-adress -s {key}"),
+            format!("eth sec2-address {key}"),
+            format!("btc private-key-convert -fs {key}"),
+        ] {
+            assert_eq!(entry(&line), None);
+            let redacted = redact_tokens(shlex::split(&line).unwrap()).unwrap();
+            assert!(!redacted.contains(key), "{redacted}");
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let mut session = Session::default();
+        session.set("key", "0x01".to_string()).unwrap();
+        session.network = Some("bsc".to_string());
+        let cli = session
+            .parse(shlex::split("eth sec2-address -s $key").unwrap())
+            .unwrap();
+        let SubCommands::Eth(args) = cli.command else {
+            panic!("expect eth command");
+        };
+        assert_eq!(args.network.as_deref(), Some("bsc"));
+        assert!(matches!(
+            args.command,
+            crate::cli::EthSubCommands::Sec2Address { private_key } if private_key == "0x01"
+        ));
+        // 命令行指定的网络优先
+        let cli = session
+            .parse(shlex::split("eth --network sepolia sec2-address -s=$key").unwrap())
+            .unwrap();
+        let SubCommands::Eth(args) = cli.command else {
+            panic!("expect eth command");
+        };
+        assert_eq!(args.network.as_deref(), Some("sepolia"));
+
+        assert!(session
+            .parse(shlex::split("eth sec2-address -s $missing").unwrap())
+            .is_err());
+        assert!(session.parse(vec!["shell".to_string()]).is_err());
+        assert!(session.set("a-b", String::new()).is_err());
+    }
+
+    #[test]
+    fn test_complete() {
+        let (start, candidates) = complete("se");
+        assert_eq!(start, 0);
+        assert_eq!(candidates, vec!["seed", "set"]);
+        let (start, candidates) = complete("eth sec");
+        assert_eq!(start, 4);
+        assert_eq!(candidates, vec!["sec2-address"]);
+        let (_, candidates) = complete("eth sec2-address --pri");
+        assert_eq!(candidates, vec!["--private-key"]);
+        assert!(complete("sh").1.is_empty());
+    }
+}
@@ -0,0 +1,495 @@
+use anyhow::{anyhow, bail, Result};
+use bip32::{ChildNumber, XPrv};
+use ethers::types::Address;
+use ethers::utils::to_checksum;
+use secp256k1::{SecretKey, SECP256K1};
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use crate::bip32::{derive_x_prv, mnemonic_to_x_prv};
+use crate::btc::{private_2_wif_key, public_key_to_address, AddressType, Network};
+use crate::cli::VanityArgs;
+use crate::encrypt_decrypt::write_output;
+use crate::eth::pub_key_to_address;
+use crate::util::u8_array_convert_string;
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+const ETH_ADDRESS_LEN: usize = 40;
+const ETH_COIN_TYPE: u32 = 60;
+/// 搜索进度的输出间隔
+const REPORT_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VanityChain {
+    Eth,
+    Btc(AddressType, Network),
+}
+
+impl VanityChain {
+    /// ETH地址为小写形式，大小写敏感时另外计算EIP-55校验和
+    pub fn address(&self, secret_key: &SecretKey) -> Result<String> {
+        let public_key = secret_key.public_key(SECP256K1);
+        match self {
+            VanityChain::Eth => Ok(pub_key_to_address(public_key)),
+            VanityChain::Btc(address_type, network) => {
+                public_key_to_address(&public_key, *address_type, *network)
+            }
+        }
+    }
+
+    /// 地址中固定的开头，如 "1"、"3"、"bc1q"；测试网P2PKH可能是m或n
+    fn leads(&self) -> Vec<String> {
+        match self {
+            VanityChain::Eth => vec![String::new()],
+            VanityChain::Btc(AddressType::P2pkh, Network::Mainnet) => vec!["1".to_string()],
+            VanityChain::Btc(AddressType::P2pkh, _) => vec!["m".to_string(), "n".to_string()],
+            VanityChain::Btc(AddressType::P2shP2wpkh, Network::Mainnet) => vec!["3".to_string()],
+            VanityChain::Btc(AddressType::P2shP2wpkh, _) => vec!["2".to_string()],
+            VanityChain::Btc(AddressType::P2wpkh, network) => vec![format!("{}1q", network.hrp())],
+            VanityChain::Btc(AddressType::P2tr, network) => vec![format!("{}1p", network.hrp())],
+        }
+    }
+
+    fn default_path(&self) -> String {
+        match self {
+            VanityChain::Eth => format!("m/44'/{ETH_COIN_TYPE}'/0'/0"),
+            VanityChain::Btc(address_type, network) => format!(
+                "m/{}'/{}'/0'/0",
+                address_type.purpose(),
+                network.coin_type()
+            ),
+        }
+    }
+}
+
+/// 地址的前缀和后缀，BTC的前缀需要包含地址固定的开头
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub prefix: String,
+    pub suffix: String,
+    pub case_sensitive: bool,
+}
+
+impl Pattern {
+    pub fn new(
+        chain: VanityChain,
+        prefix: &str,
+        suffix: &str,
+        case_sensitive: bool,
+    ) -> Result<Self> {
+        let (prefix, suffix) = match chain {
+            VanityChain::Eth => {
+                let prefix = prefix.strip_prefix("0x").unwrap_or(prefix);
+                if prefix.len() + suffix.len() > ETH_ADDRESS_LEN {
+                    bail!("prefix and suffix are longer than an address");
+                }
+                (prefix.to_string(), suffix.to_string())
+            }
+            VanityChain::Btc(AddressType::P2wpkh | AddressType::P2tr, _) => {
+                // bech32地址只有小写
+                (prefix.to_lowercase(), suffix.to_lowercase())
+            }
+            VanityChain::Btc(..) => (prefix.to_string(), suffix.to_string()),
+        };
+        let leads = chain.leads();
+        let Some(lead) = leads.iter().find(|lead| prefix.starts_with(lead.as_str())) else {
+            bail!("prefix must start with {}", leads.join(" or "));
+        };
+        let alphabet = match chain {
+            VanityChain::Eth => "0123456789abcdefABCDEF",
+            VanityChain::Btc(AddressType::P2wpkh | AddressType::P2tr, _) => BECH32_CHARSET,
+            VanityChain::Btc(..) => BASE58_ALPHABET,
+        };
+        let invalid = prefix[lead.len()..]
+            .chars()
+            .chain(suffix.chars())
+            .find(|ch| {
+                !alphabet
+                    .chars()
+                    .any(|c| c == *ch || (!case_sensitive && c.eq_ignore_ascii_case(ch)))
+            });
+        if let Some(ch) = invalid {
+            bail!("character {ch:?} can not appear in the address");
+        }
+        Ok(Pattern {
+            prefix,
+            suffix,
+            case_sensitive,
+        })
+    }
+
+    pub fn matches(&self, chain: VanityChain, address: &str) -> bool {
+        let address = match chain {
+            VanityChain::Eth => address.trim_start_matches("0x"),
+            VanityChain::Btc(..) => address,
+        };
+        let loose = |address: &str| {
+            address.len() >= self.prefix.len() + self.suffix.len()
+                && address[..self.prefix.len()].eq_ignore_ascii_case(&self.prefix)
+                && address[address.len() - self.suffix.len()..].eq_ignore_ascii_case(&self.suffix)
+        };
+        if !self.case_sensitive {
+            return loose(address);
+        }
+        if chain == VanityChain::Eth {
+            // 先按小写过滤，匹配后再计算校验和，减少hash次数
+            if !loose(address) {
+                return false;
+            }
+            let Ok(parsed) = Address::from_str(address) else {
+                return false;
+            };
+            let checksum = to_checksum(&parsed, None);
+            return self.exact(&checksum[2..]);
+        }
+        self.exact(address)
+    }
+
+    fn exact(&self, address: &str) -> bool {
+        address.starts_with(&self.prefix) && address.ends_with(&self.suffix)
+    }
+
+    /// 期望的尝试次数，近似认为地址中每个字符均匀分布
+    pub fn difficulty(&self, chain: VanityChain) -> f64 {
+        let leads = chain.leads();
+        let lead_len = leads
+            .iter()
+            .find(|lead| self.prefix.starts_with(lead.as_str()))
+            .map_or(0, String::len);
+        let chars = self.prefix[lead_len..].chars().chain(self.suffix.chars());
+        let lead_factor = leads.len() as f64;
+        let per_char = |ch: char| match chain {
+            VanityChain::Eth if self.case_sensitive && ch.is_ascii_alphabetic() => 32.0,
+            VanityChain::Eth => 16.0,
+            VanityChain::Btc(AddressType::P2wpkh | AddressType::P2tr, _) => 32.0,
+            VanityChain::Btc(..) if self.case_sensitive => 58.0,
+            VanityChain::Btc(..) => {
+                let variants = BASE58_ALPHABET
+                    .chars()
+                    .filter(|c| c.eq_ignore_ascii_case(&ch))
+                    .count();
+                58.0 / variants.max(1) as f64
+            }
+        };
+        chars.map(per_char).product::<f64>() * lead_factor
+    }
+}
+
+/// 私钥来源：随机生成，或者从助记词路径下按index派生
+pub enum KeySource {
+    Random,
+    Mnemonic {
+        parent: Box<XPrv>,
+        path: String,
+        start: u32,
+        count: u32,
+    },
+}
+
+impl KeySource {
+    /// 第n个私钥，助记词路径范围用完时返回None
+    fn key(&self, n: u64) -> Option<Result<(SecretKey, Option<String>)>> {
+        match self {
+            KeySource::Random => Some(Ok((SecretKey::new(&mut rand::thread_rng()), None))),
+            KeySource::Mnemonic {
+                parent,
+                path,
+                start,
+                count,
+            } => {
+                if n >= *count as u64 {
+                    return None;
+                }
+                let index = start.checked_add(n as u32)?;
+                let key = ChildNumber::new(index, false)
+                    .and_then(|child| parent.derive_child(child))
+                    .map_err(|e| anyhow!("derive child {index} failed: {e}"))
+                    .and_then(|child| Ok(SecretKey::from_slice(&child.private_key().to_bytes())?))
+                    .map(|secret_key| (secret_key, Some(format!("{path}/{index}"))));
+                Some(key)
+            }
+        }
+    }
+
+    /// 总的私钥数量，随机生成时没有上限
+    fn total(&self) -> Option<u64> {
+        match self {
+            KeySource::Random => None,
+            KeySource::Mnemonic { count, .. } => Some(*count as u64),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct VanityAddress {
+    pub address: String,
+    pub private_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wif: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    pub attempts: u64,
+    pub elapsed_secs: f64,
+    pub keys_per_second: f64,
+}
+
+fn format_duration(secs: f64) -> String {
+    if !secs.is_finite() {
+        return "unknown".to_string();
+    }
+    let secs = secs as u64;
+    match secs {
+        0..=59 => format!("{secs}s"),
+        60..=3599 => format!("{}m{}s", secs / 60, secs % 60),
+        3600..=86399 => format!("{}h{}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d{}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+/// 匹配的地址、私钥和派生路径
+type Matched = (String, SecretKey, Option<String>);
+
+/// 多线程搜索，第n个私钥由第 n % threads 个线程处理；任一线程找到后所有线程停止
+pub fn search(
+    chain: VanityChain,
+    pattern: &Pattern,
+    source: &KeySource,
+    threads: usize,
+) -> Result<Option<VanityAddress>> {
+    let threads = threads.max(1);
+    let expected = pattern.difficulty(chain);
+    let found = AtomicBool::new(false);
+    let attempts = AtomicU64::new(0);
+    let result: Mutex<Option<Result<Matched>>> = Mutex::new(None);
+    let start = Instant::now();
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads)
+            .map(|worker| {
+                let (found, attempts, result) = (&found, &attempts, &result);
+                scope.spawn(move || {
+                    for round in 0u64.. {
+                        if found.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let Some(key) = source.key(round * threads as u64 + worker as u64) else {
+                            return;
+                        };
+                        let matched = key.and_then(|(secret_key, path)| {
+                            let address = chain.address(&secret_key)?;
+                            Ok(pattern
+                                .matches(chain, &address)
+                                .then_some((address, secret_key, path)))
+                        });
+                        attempts.fetch_add(1, Ordering::Relaxed);
+                        let matched = match matched {
+                            Ok(None) => continue,
+                            Ok(Some(matched)) => Ok(matched),
+                            Err(e) => Err(e),
+                        };
+                        if !found.swap(true, Ordering::Relaxed) {
+                            *result.lock().unwrap() = Some(matched);
+                        }
+                        return;
+                    }
+                })
+            })
+            .collect();
+        let mut last_report = Instant::now();
+        while !handles.iter().all(|handle| handle.is_finished()) {
+            thread::sleep(Duration::from_millis(50));
+            if last_report.elapsed() < REPORT_INTERVAL {
+                continue;
+            }
+            last_report = Instant::now();
+            let attempts = attempts.load(Ordering::Relaxed);
+            let rate = attempts as f64 / start.elapsed().as_secs_f64();
+            let remaining = match source.total() {
+                Some(total) => (total.saturating_sub(attempts)) as f64,
+                None => (expected - attempts as f64).max(0.0),
+            };
+            info!(
+                "{attempts} keys, {rate:.0} keys/s, expected {expected:.0} keys, eta {}",
+                format_duration(remaining.min(expected) / rate)
+            );
+        }
+    });
+    let attempts = attempts.into_inner();
+    let elapsed = start.elapsed().as_secs_f64();
+    let Some(matched) = result.into_inner().unwrap() else {
+        return Ok(None);
+    };
+    let (address, secret_key, path) = matched?;
+    let private_key = u8_array_convert_string(&secret_key.secret_bytes());
+    let wif = match chain {
+        VanityChain::Btc(_, Network::Mainnet) => Some(private_2_wif_key(private_key.clone(), true)),
+        _ => None,
+    };
+    let address = match chain {
+        VanityChain::Eth => to_checksum(&Address::from_str(&address)?, None),
+        VanityChain::Btc(..) => address,
+    };
+    Ok(Some(VanityAddress {
+        address,
+        private_key,
+        wif,
+        path,
+        attempts,
+        elapsed_secs: elapsed,
+        keys_per_second: attempts as f64 / elapsed,
+    }))
+}
+
+pub fn vanity(args: VanityArgs) -> Result<VanityAddress> {
+    let chain = match args.chain.to_lowercase().as_str() {
+        "eth" => VanityChain::Eth,
+        "btc" => VanityChain::Btc(
+            AddressType::from_str(&args.address_type)?,
+            Network::from_str(&args.network)?,
+        ),
+        chain => bail!("chain {chain} not supported."),
+    };
+    let pattern = Pattern::new(chain, &args.prefix, &args.suffix, args.case_sensitive)?;
+    let source = match args.mnemonic {
+        Some(mnemonic) => {
+            bip39::Mnemonic::from_str(mnemonic.trim())
+                .map_err(|e| anyhow!("invalid mnemonic: {e}"))?;
+            let path = args.path.unwrap_or_else(|| chain.default_path());
+            let root = mnemonic_to_x_prv(mnemonic.trim().to_string(), args.passphrase);
+            KeySource::Mnemonic {
+                parent: Box::new(derive_x_prv(&root, &path)?),
+                path,
+                start: args.start,
+                count: args.count,
+            }
+        }
+        None => KeySource::Random,
+    };
+    let threads = match args.threads {
+        0 => thread::available_parallelism().map_or(1, usize::from),
+        threads => threads,
+    };
+    info!(
+        "search with {threads} threads, expected {:.0} keys.",
+        pattern.difficulty(chain)
+    );
+    let found = search(chain, &pattern, &source, threads)?
+        .ok_or_else(|| anyhow!("no address matches in the path range."))?;
+    info!(
+        "found {} after {} keys, {:.0} keys/s.",
+        found.address, found.attempts, found.keys_per_second
+    );
+    write_output(args.output, &serde_json::to_string_pretty(&found)?)?;
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    const MNEMONIC: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    fn mnemonic_source(chain: VanityChain, count: u32) -> KeySource {
+        let path = chain.default_path();
+        let root = mnemonic_to_x_prv(MNEMONIC.to_string(), "".to_string());
+        KeySource::Mnemonic {
+            parent: Box::new(derive_x_prv(&root, &path).unwrap()),
+            path,
+            start: 0,
+            count,
+        }
+    }
+
+    #[test]
+    fn test_pattern() {
+        let eth = VanityChain::Eth;
+        let pattern = Pattern::new(eth, "0x9858Ef", "", true).unwrap();
+        assert!(pattern.matches(eth, "0x9858effd232b4033e47d90003d41ec34ecaeda94"));
+        assert!(!Pattern::new(eth, "9858EF", "", true)
+            .unwrap()
+            .matches(eth, "0x9858effd232b4033e47d90003d41ec34ecaeda94"));
+        assert!(Pattern::new(eth, "9858EF", "94", false)
+            .unwrap()
+            .matches(eth, "0x9858effd232b4033e47d90003d41ec34ecaeda94"));
+        assert!(Pattern::new(eth, "0xzz", "", false).is_err());
+        assert_eq!(
+            Pattern::new(eth, "dead", "", false)
+                .unwrap()
+                .difficulty(eth),
+            65536.0
+        );
+        assert_eq!(
+            Pattern::new(eth, "dead", "", true).unwrap().difficulty(eth),
+            32.0 * 32.0 * 32.0 * 32.0
+        );
+
+        let btc = VanityChain::Btc(AddressType::P2pkh, Network::Mainnet);
+        assert!(Pattern::new(btc, "2abc", "", false).is_err());
+        // 0、O、I、l不在base58中
+        assert!(Pattern::new(btc, "1O", "", true).is_err());
+        assert!(Pattern::new(btc, "1o", "", false).is_ok());
+        assert_eq!(
+            Pattern::new(btc, "1", "", false).unwrap().difficulty(btc),
+            1.0
+        );
+        assert_eq!(
+            Pattern::new(btc, "1a", "", true).unwrap().difficulty(btc),
+            58.0
+        );
+        assert_eq!(
+            Pattern::new(btc, "1a", "", false).unwrap().difficulty(btc),
+            29.0
+        );
+        let segwit = VanityChain::Btc(AddressType::P2wpkh, Network::Mainnet);
+        assert!(Pattern::new(segwit, "bc1qb", "", false).is_err());
+        assert!(Pattern::new(segwit, "BC1QCR8", "", false)
+            .unwrap()
+            .matches(segwit, "bc1qcr8te4kr609gcawutmrza0j4xv80jy8z306fyu"));
+    }
+
+    #[test]
+    fn test_search_random() {
+        let chain = VanityChain::Eth;
+        let pattern = Pattern::new(chain, "a", "", false).unwrap();
+        let found = search(chain, &pattern, &KeySource::Random, 2)
+            .unwrap()
+            .unwrap();
+        assert!(found.address.to_lowercase().starts_with("0xa"));
+        let secret_key = SecretKey::from_str(&found.private_key).unwrap();
+        assert!(chain
+            .address(&secret_key)
+            .unwrap()
+            .eq_ignore_ascii_case(&found.address));
+    }
+
+    #[test]
+    fn test_search_mnemonic() {
+        let chain = VanityChain::Eth;
+        let pattern = Pattern::new(chain, "9858Ef", "", true).unwrap();
+        let found = search(chain, &pattern, &mnemonic_source(chain, 10), 3)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.address, "0x9858EfFD232B4033E47d90003D41EC34EcaEda94");
+        assert_eq!(found.path.as_deref(), Some("m/44'/60'/0'/0/0"));
+
+        let chain = VanityChain::Btc(AddressType::P2wpkh, Network::Mainnet);
+        let pattern = Pattern::new(chain, "bc1qnjg0", "", false).unwrap();
+        let found = search(chain, &pattern, &mnemonic_source(chain, 10), 4)
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.address, "bc1qnjg0jd8228aq7egyzacy8cys3knf9xvrerkf9g");
+        assert_eq!(found.path.as_deref(), Some("m/84'/0'/0'/0/1"));
+        assert!(found.wif.is_some());
+
+        // 范围内没有匹配
+        let pattern = Pattern::new(chain, "bc1qqqqqqq", "", false).unwrap();
+        assert!(search(chain, &pattern, &mnemonic_source(chain, 5), 2)
+            .unwrap()
+            .is_none());
+    }
+}